@@ -0,0 +1,35 @@
+//! Example using Repl with ReplError, which any error converts into with `?`.
+
+use reedline_repl_rs::clap::{Arg, ArgMatches, Command};
+use reedline_repl_rs::{Repl, ReplError};
+
+/// Read a file and count its lines
+fn count(args: ArgMatches, _context: &mut ()) -> Result<Option<String>, ReplError> {
+    let path = args.get_one::<String>("path").unwrap();
+    let text = std::fs::read_to_string(path)?;
+    let limit: usize = args.get_one::<String>("limit").unwrap().parse()?;
+    let lines = text.lines().count();
+    if lines > limit {
+        return Err(ReplError::msg(format!(
+            "{} has more than {} lines",
+            path, limit
+        )));
+    }
+    Ok(Some(format!("{} lines", lines)))
+}
+
+fn main() -> Result<(), ReplError> {
+    let mut repl = Repl::new(())
+        .with_name("MyApp")
+        .with_version("v0.1.0")
+        .with_description("My very cool app")
+        .with_command(
+            Command::new("count")
+                .arg(Arg::new("path").required(true))
+                .arg(Arg::new("limit").default_value("1000"))
+                .about("Count the lines of a file"),
+            count,
+        );
+    repl.run()?;
+    Ok(())
+}
@@ -0,0 +1,33 @@
+//! Example offering the subcommands of an existing clap application in a REPL
+use reedline_repl_rs::clap::{Arg, ArgMatches, Command};
+use reedline_repl_rs::{Repl, Result};
+
+/// The command line interface of the application
+fn cli() -> Command {
+    Command::new("todo")
+        .subcommand(
+            Command::new("add")
+                .arg(Arg::new("task").required(true).help("Task to add"))
+                .about("Add a task"),
+        )
+        .subcommand(Command::new("list").about("List all tasks"))
+}
+
+/// Dispatch shared by the command line and the REPL
+fn run(matches: ArgMatches, tasks: &mut Vec<String>) -> Result<Option<String>> {
+    match matches.subcommand() {
+        Some(("add", args)) => {
+            tasks.push(args.get_one::<String>("task").unwrap().clone());
+            Ok(None)
+        }
+        Some(("list", _)) => Ok(Some(tasks.join("\n"))),
+        _ => Ok(None),
+    }
+}
+
+fn main() -> Result<()> {
+    let mut repl = Repl::new(vec![])
+        .with_name("todo")
+        .with_clap_commands(cli(), run);
+    repl.run()
+}
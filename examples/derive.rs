@@ -0,0 +1,39 @@
+//! Example defining commands from functions with the repl_command attribute
+use reedline_repl_rs::{repl_command, Repl, Result};
+
+#[derive(Default)]
+struct Context {
+    total: i64,
+}
+
+/// Add two numbers
+#[repl_command]
+fn add(first: i64, second: i64) -> Result<Option<String>> {
+    Ok(Some((first + second).to_string()))
+}
+
+/// Add numbers to the running total
+#[repl_command]
+fn sum(verbose: bool, numbers: Vec<i64>, context: &mut Context) -> Result<Option<String>> {
+    context.total += numbers.iter().sum::<i64>();
+    if verbose {
+        Ok(Some(format!("total is now {}", context.total)))
+    } else {
+        Ok(None)
+    }
+}
+
+/// Show the running total
+#[repl_command(name = "total")]
+fn show_total(context: &mut Context) -> Result<Option<String>> {
+    Ok(Some(context.total.to_string()))
+}
+
+fn main() -> Result<()> {
+    let mut repl = Repl::new(Context::default())
+        .with_name("Calculator")
+        .with_command(add::command(), add::callback)
+        .with_command(sum::command(), sum::callback)
+        .with_command(show_total::command(), show_total::callback);
+    repl.run()
+}
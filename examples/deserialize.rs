@@ -0,0 +1,61 @@
+//! Example deserializing the arguments of commands into structs with the serde feature
+use reedline_repl_rs::clap::{Arg, ArgAction, ArgMatches, Command};
+use reedline_repl_rs::{DeserializeArgs, Repl, Result};
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+struct AddArgs {
+    first: i32,
+    second: i32,
+}
+
+/// Add two numbers
+fn add<T>(args: ArgMatches, _context: &mut T) -> Result<Option<String>> {
+    let args: AddArgs = args.deserialize()?;
+
+    Ok(Some((args.first + args.second).to_string()))
+}
+
+#[derive(Deserialize)]
+struct GreetArgs {
+    #[serde(default)]
+    names: Vec<String>,
+    greeting: Option<String>,
+    shout: bool,
+}
+
+/// Greet everyone given
+fn greet<T>(args: ArgMatches, _context: &mut T) -> Result<Option<String>> {
+    let args: GreetArgs = args.deserialize()?;
+    let greeting = args.greeting.unwrap_or_else(|| "Hello".to_string());
+    let message = format!("{} {}!", greeting, args.names.join(" and "));
+
+    Ok(Some(if args.shout {
+        message.to_uppercase()
+    } else {
+        message
+    }))
+}
+
+fn main() -> Result<()> {
+    let mut repl = Repl::new(())
+        .with_name("MyApp")
+        .with_version("v0.1.0")
+        .with_description("My very cool app")
+        .with_command(
+            Command::new("add")
+                .arg(Arg::new("first").required(true))
+                .arg(Arg::new("second").required(true))
+                .about("Add two numbers together"),
+            add,
+        )
+        .with_command(
+            Command::new("greet")
+                .arg(Arg::new("names").num_args(0..))
+                .arg(Arg::new("greeting").long("greeting"))
+                .arg(Arg::new("shout").long("shout").action(ArgAction::SetTrue))
+                .about("Greet everyone given"),
+            greet,
+        );
+    repl.run()
+}
@@ -0,0 +1,56 @@
+//! Example with modes changing the available commands
+use reedline_repl_rs::clap::{Arg, ArgMatches, Command};
+use reedline_repl_rs::{Modes, Repl, Result};
+
+#[derive(Default)]
+struct Context {
+    modes: Modes,
+    hostname: String,
+}
+
+/// Enter configuration mode
+fn configure(_args: ArgMatches, context: &mut Context) -> Result<Option<String>> {
+    context.modes.push("config");
+    Ok(None)
+}
+
+/// Set the hostname, only available in configuration mode
+fn hostname(args: ArgMatches, context: &mut Context) -> Result<Option<String>> {
+    context.hostname = args.get_one::<String>("name").unwrap().to_string();
+    Ok(None)
+}
+
+/// Leave the active mode
+fn end(_args: ArgMatches, context: &mut Context) -> Result<Option<String>> {
+    context.modes.pop();
+    Ok(None)
+}
+
+/// Show the configuration
+fn show(_args: ArgMatches, context: &mut Context) -> Result<Option<String>> {
+    Ok(Some(format!("hostname {}", context.hostname)))
+}
+
+fn main() -> Result<()> {
+    let mut repl = Repl::new(Context::default())
+        .with_name("Router")
+        .with_modes(|context| &context.modes)
+        .with_command(Command::new("show").about("Show the configuration"), show)
+        .with_command(
+            Command::new("configure").about("Enter configuration mode"),
+            configure,
+        )
+        .with_mode_command(
+            "config",
+            Command::new("hostname")
+                .arg(Arg::new("name").required(true).help("New hostname"))
+                .about("Set the hostname"),
+            hostname,
+        )
+        .with_mode_command(
+            "config",
+            Command::new("end").about("Leave configuration mode"),
+            end,
+        );
+    repl.run()
+}
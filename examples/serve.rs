@@ -0,0 +1,42 @@
+//! Example serving the commands to clients connecting with e.g. `nc localhost 7000`
+use reedline_repl_rs::clap::{Arg, ArgMatches, Command};
+use reedline_repl_rs::{Repl, Result};
+use std::collections::HashMap;
+use std::net::TcpListener;
+
+/// Store a value
+fn set(args: ArgMatches, context: &mut HashMap<String, String>) -> Result<Option<String>> {
+    let key = args.get_one::<String>("key").unwrap();
+    let value = args.get_one::<String>("value").unwrap();
+    context.insert(key.to_string(), value.to_string());
+
+    Ok(None)
+}
+
+/// Show a stored value
+fn get(args: ArgMatches, context: &mut HashMap<String, String>) -> Result<Option<String>> {
+    let key = args.get_one::<String>("key").unwrap();
+
+    Ok(context.get(key).cloned())
+}
+
+fn main() -> Result<()> {
+    let mut repl = Repl::new(HashMap::new())
+        .with_name("MyApp")
+        .with_version("v0.1.0")
+        .with_description("My very cool app")
+        .with_command(
+            Command::new("set")
+                .arg(Arg::new("key").required(true))
+                .arg(Arg::new("value").required(true))
+                .about("Store a value"),
+            set,
+        )
+        .with_command(
+            Command::new("get")
+                .arg(Arg::new("key").required(true))
+                .about("Show a stored value"),
+            get,
+        );
+    repl.serve(TcpListener::bind("127.0.0.1:7000")?)
+}
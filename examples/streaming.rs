@@ -0,0 +1,47 @@
+//! Example streaming the output of a command line by line while it runs
+use reedline_repl_rs::clap::{value_parser, Arg, ArgMatches, Command};
+use reedline_repl_rs::{OutputStream, PlainRenderer, Repl, Result};
+use std::thread::sleep;
+use std::time::Duration;
+
+/// Count up to the given number, one line per second
+fn count<T>(args: ArgMatches, _context: &mut T) -> Result<Option<OutputStream>> {
+    let to = *args.get_one::<u32>("to").unwrap();
+    let lines = (1..=to).map(|i| {
+        sleep(Duration::from_secs(1));
+        i.to_string()
+    });
+
+    Ok(Some(OutputStream::new(lines)))
+}
+
+/// Say hello at once
+fn hello<T>(args: ArgMatches, _context: &mut T) -> Result<Option<OutputStream>> {
+    let who = args.get_one::<String>("who").unwrap();
+
+    Ok(Some(format!("Hello, {}", who).into()))
+}
+
+fn main() -> Result<()> {
+    let mut repl = Repl::new_with_renderer((), PlainRenderer)
+        .with_name("MyApp")
+        .with_version("v0.1.0")
+        .with_description("My very cool app")
+        .with_command(
+            Command::new("count")
+                .arg(
+                    Arg::new("to")
+                        .value_parser(value_parser!(u32))
+                        .required(true),
+                )
+                .about("Count up slowly"),
+            count,
+        )
+        .with_command(
+            Command::new("hello")
+                .arg(Arg::new("who").required(true))
+                .about("Greetings!"),
+            hello,
+        );
+    repl.run()
+}
@@ -0,0 +1,53 @@
+//! Example returning structured values from commands, rendered as text or JSON
+use reedline_repl_rs::clap::{Arg, ArgMatches, Command};
+use reedline_repl_rs::{Repl, Result};
+use std::fmt;
+
+/// Disk usage of a mount point
+struct Usage {
+    mount: String,
+    used: u64,
+    total: u64,
+}
+
+impl fmt::Display for Usage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {} of {} GB used", self.mount, self.used, self.total)
+    }
+}
+
+/// Renders a usage as a JSON object for scripts consuming the output
+fn json(usage: &Usage) -> String {
+    format!(
+        r#"{{"mount": "{}", "used": {}, "total": {}}}"#,
+        usage.mount, usage.used, usage.total
+    )
+}
+
+/// Show the disk usage of a mount point
+fn usage<T>(args: ArgMatches, _context: &mut T) -> Result<Option<Usage>> {
+    let mount = args.get_one::<String>("mount").unwrap();
+
+    Ok(Some(Usage {
+        mount: mount.to_string(),
+        used: 42,
+        total: 256,
+    }))
+}
+
+fn main() -> Result<()> {
+    let mut repl = Repl::new(())
+        .with_name("MyApp")
+        .with_version("v0.1.0")
+        .with_description("My very cool app")
+        .with_command(
+            Command::new("usage")
+                .arg(Arg::new("mount").required(true))
+                .about("Show disk usage"),
+            usage,
+        );
+    if std::env::args().any(|arg| arg == "--json") {
+        repl = repl.with_output_renderer(json);
+    }
+    repl.run()
+}
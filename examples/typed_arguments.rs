@@ -0,0 +1,194 @@
+//! Example using clap value parsers to validate argument types before the callback runs
+use reedline_repl_rs::clap::{value_parser, Arg, ArgMatches, Command};
+use reedline_repl_rs::{matches_regex, parse_bool, parse_duration, validated, Repl, Result};
+use std::net::IpAddr;
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// Add two numbers. `add one two` is rejected by clap before this is called
+fn add<T>(args: ArgMatches, _context: &mut T) -> Result<Option<String>> {
+    let first = args.get_one::<i32>("first").unwrap();
+    let second = args.get_one::<i32>("second").unwrap();
+
+    Ok(Some((first + second).to_string()))
+}
+
+/// Sum all given numbers, which are collected into a single argument
+fn sum<T>(args: ArgMatches, _context: &mut T) -> Result<Option<String>> {
+    let numbers = args.get_many::<i64>("numbers").unwrap();
+
+    Ok(Some(numbers.sum::<i64>().to_string()))
+}
+
+/// Scale a number by a factor
+fn scale<T>(args: ArgMatches, _context: &mut T) -> Result<Option<String>> {
+    let value = args.get_one::<f64>("value").unwrap();
+    let factor = args.get_one::<f64>("factor").unwrap();
+
+    Ok(Some((value * factor).to_string()))
+}
+
+/// Show whether a path exists
+fn exists<T>(args: ArgMatches, _context: &mut T) -> Result<Option<String>> {
+    let path = args.get_one::<PathBuf>("path").unwrap();
+
+    Ok(Some(path.exists().to_string()))
+}
+
+/// Describe a ping, `--timeout` and `--verbose` are optional
+fn ping<T>(args: ArgMatches, _context: &mut T) -> Result<Option<String>> {
+    let host = args.get_one::<IpAddr>("host").unwrap();
+    let timeout = args.get_one::<Duration>("timeout");
+    let verbose = args.get_one::<bool>("verbose").copied().unwrap_or(false);
+
+    let mut message = format!("pinging {}", host);
+    if let Some(timeout) = timeout {
+        message += &format!(" for up to {:?}", timeout);
+    }
+    if verbose {
+        message += " verbosely";
+    }
+    Ok(Some(message))
+}
+
+/// Set the color mode, clap rejects anything but `on`, `off` and `auto`
+fn color<T>(args: ArgMatches, _context: &mut T) -> Result<Option<String>> {
+    let mode = args.get_one::<String>("mode").unwrap();
+
+    Ok(Some(format!("color is {}", mode)))
+}
+
+/// Rejects even ports
+fn odd_port(value: &str) -> std::result::Result<(), String> {
+    match value.parse::<u16>() {
+        Ok(port) if port % 2 == 1 => Ok(()),
+        _ => Err(format!("'{}' is not an odd port", value)),
+    }
+}
+
+/// Open a connection, clap checks the user name and the ports before this is called
+fn connect<T>(args: ArgMatches, _context: &mut T) -> Result<Option<String>> {
+    let user = args.get_one::<String>("user").unwrap();
+    let port = args.get_one::<u16>("port").unwrap();
+    let local = args.get_one::<String>("local").unwrap();
+
+    Ok(Some(format!("{}@{} from {}", user, port, local)))
+}
+
+fn main() -> Result<()> {
+    let mut repl = Repl::new(())
+        .with_name("MyApp")
+        .with_version("v0.1.0")
+        .with_description("My very cool app")
+        .with_command(
+            Command::new("add")
+                .arg(
+                    Arg::new("first")
+                        .value_name("INT")
+                        .value_parser(value_parser!(i32))
+                        .required(true),
+                )
+                .arg(
+                    Arg::new("second")
+                        .value_name("INT")
+                        .value_parser(value_parser!(i32))
+                        .required(true),
+                )
+                .about("Add two numbers together"),
+            add,
+        )
+        .with_command(
+            Command::new("sum")
+                .arg(
+                    Arg::new("numbers")
+                        .value_name("INT")
+                        .value_parser(value_parser!(i64))
+                        .num_args(1..)
+                        .required(true),
+                )
+                .about("Sum all given numbers"),
+            sum,
+        )
+        .with_command(
+            Command::new("scale")
+                .arg(
+                    Arg::new("value")
+                        .value_name("FLOAT")
+                        .value_parser(value_parser!(f64))
+                        .required(true),
+                )
+                .arg(
+                    Arg::new("factor")
+                        .value_name("FLOAT")
+                        .value_parser(value_parser!(f64))
+                        .required(true),
+                )
+                .about("Multiply a value by a factor"),
+            scale,
+        )
+        .with_command(
+            Command::new("exists")
+                .arg(
+                    Arg::new("path")
+                        .value_name("PATH")
+                        .value_parser(value_parser!(PathBuf))
+                        .required(true),
+                )
+                .about("Check whether a path exists"),
+            exists,
+        )
+        .with_command(
+            Command::new("ping")
+                .arg(
+                    Arg::new("host")
+                        .value_name("IP")
+                        .value_parser(value_parser!(IpAddr))
+                        .required(true),
+                )
+                .arg(
+                    Arg::new("timeout")
+                        .long("timeout")
+                        .value_name("DURATION")
+                        .value_parser(parse_duration),
+                )
+                .arg(
+                    Arg::new("verbose")
+                        .long("verbose")
+                        .value_name("yes/no")
+                        .value_parser(parse_bool),
+                )
+                .about("Ping a host"),
+            ping,
+        )
+        .with_command(
+            Command::new("color")
+                .arg(
+                    Arg::new("mode")
+                        .value_parser(["on", "off", "auto"])
+                        .required(true),
+                )
+                .about("Set the color mode"),
+            color,
+        )
+        .with_command(
+            Command::new("connect")
+                .arg(
+                    Arg::new("user")
+                        .value_parser(matches_regex("^[a-z_][a-z0-9_]*$"))
+                        .required(true),
+                )
+                .arg(
+                    Arg::new("port")
+                        .value_parser(value_parser!(u16).range(1..))
+                        .required(true),
+                )
+                .arg(
+                    Arg::new("local")
+                        .value_parser(validated(odd_port))
+                        .required(true),
+                )
+                .about("Connect to a port"),
+            connect,
+        );
+    repl.run()
+}
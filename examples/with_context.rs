@@ -33,13 +33,21 @@ fn main() -> Result<()> {
         .with_description("My very cool List")
         .with_command(
             Command::new("append")
-                .arg(Arg::new("name").required(true))
+                .arg(
+                    Arg::new("name")
+                        .required(true)
+                        .help("Name to add to the end"),
+                )
                 .about("Append name to end of list"),
             append,
         )
         .with_command(
             Command::new("prepend")
-                .arg(Arg::new("name").required(true))
+                .arg(
+                    Arg::new("name")
+                        .required(true)
+                        .help("Name to add to the front"),
+                )
                 .about("Prepend name to front of list"),
             prepend,
         )
@@ -0,0 +1,223 @@
+//! Attribute macro for [reedline-repl-rs](https://docs.rs/reedline-repl-rs), enabled with its
+//! `derive` feature and re-exported as `reedline_repl_rs::repl_command`
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{
+    parse_macro_input, Expr, FnArg, GenericArgument, ItemFn, Lit, LitStr, Meta, Pat, PathArguments,
+    ReturnType, Type, Visibility,
+};
+
+/// Defines a REPL command from a function. Its parameters become the arguments of the
+/// command, parsed with clap's `value_parser!`:
+/// - `T` is a required positional argument
+/// - `Option<T>` is an optional positional argument
+/// - `Vec<T>` collects all remaining arguments, so it must come after the other positionals
+/// - `bool` is a `--flag`
+///
+/// A last `&mut Context` parameter receives the REPL context. The macro keeps the function
+/// and adds a module with the same name containing `command()`, the clap `Command`, and
+/// `callback`, which parses the arguments and calls the function:
+///
+/// ```rust,ignore
+/// /// Add two numbers
+/// #[repl_command]
+/// fn add(first: i32, second: i32) -> Result<Option<String>> {
+///     Ok(Some((first + second).to_string()))
+/// }
+///
+/// let repl = Repl::new(()).with_command(add::command(), add::callback);
+/// ```
+///
+/// The help defaults to the first line of the doc comment and can be set with
+/// `#[repl_command(help = "..")]`, the name defaults to the function name with `-` instead
+/// of `_` and can be set with `#[repl_command(name = "..")]`.
+#[proc_macro_attribute]
+pub fn repl_command(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let mut help: Option<LitStr> = None;
+    let mut name: Option<LitStr> = None;
+    let parser = syn::meta::parser(|meta| {
+        if meta.path.is_ident("help") {
+            help = Some(meta.value()?.parse()?);
+            Ok(())
+        } else if meta.path.is_ident("name") {
+            name = Some(meta.value()?.parse()?);
+            Ok(())
+        } else {
+            Err(meta.error("expected `help` or `name`"))
+        }
+    });
+    parse_macro_input!(attr with parser);
+    let function = parse_macro_input!(item as ItemFn);
+    match expand(function, help, name) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+/// How a parameter is passed on the command line
+enum Kind {
+    Required,
+    Optional,
+    Many,
+    Flag,
+}
+
+fn expand(
+    function: ItemFn,
+    help: Option<LitStr>,
+    name: Option<LitStr>,
+) -> syn::Result<TokenStream2> {
+    let ident = &function.sig.ident;
+    let vis = &function.vis;
+    // the generated functions must be reachable from where the function is
+    let item_vis = match vis {
+        Visibility::Inherited => quote!(pub(super)),
+        _ => quote!(pub),
+    };
+    let name = match name {
+        Some(name) => name.value(),
+        None => ident.to_string().replace('_', "-"),
+    };
+    let help = help
+        .map(|help| help.value())
+        .or_else(|| doc_summary(&function));
+    let about = help.map(|help| quote!(.about(#help)));
+
+    let mut args = vec![];
+    let mut values = vec![];
+    let mut context = None;
+    let count = function.sig.inputs.len();
+    for (index, input) in function.sig.inputs.iter().enumerate() {
+        let typed = match input {
+            FnArg::Typed(typed) => typed,
+            FnArg::Receiver(receiver) => {
+                return Err(syn::Error::new_spanned(
+                    receiver,
+                    "methods can't be REPL commands",
+                ))
+            }
+        };
+        if let Type::Reference(reference) = &*typed.ty {
+            if reference.mutability.is_some() && index + 1 == count {
+                context = Some(reference.elem.clone());
+                values.push(quote!(context));
+                continue;
+            }
+        }
+        let id = match &*typed.pat {
+            Pat::Ident(pat) => pat.ident.to_string(),
+            pat => return Err(syn::Error::new_spanned(pat, "expected a parameter name")),
+        };
+        let long = id.replace('_', "-");
+        let (kind, ty) = classify(&typed.ty);
+        args.push(match kind {
+            Kind::Required => quote!(Arg::new(#id).required(true).value_parser(value_parser!(#ty))),
+            Kind::Optional => quote!(Arg::new(#id).value_parser(value_parser!(#ty))),
+            Kind::Many => quote!(Arg::new(#id).num_args(0..).value_parser(value_parser!(#ty))),
+            Kind::Flag => quote!(Arg::new(#id).long(#long).action(ArgAction::SetTrue)),
+        });
+        values.push(match kind {
+            Kind::Required => {
+                quote!(args.get_one::<#ty>(#id).cloned().expect("argument is required"))
+            }
+            Kind::Optional => quote!(args.get_one::<#ty>(#id).cloned()),
+            Kind::Many => quote! {
+                args.get_many::<#ty>(#id)
+                    .map(|values| values.cloned().collect())
+                    .unwrap_or_default()
+            },
+            Kind::Flag => quote!(args.get_flag(#id)),
+        });
+    }
+
+    let output = match &function.sig.output {
+        ReturnType::Type(_, ty) => quote!(#ty),
+        ReturnType::Default => {
+            return Err(syn::Error::new_spanned(
+                &function.sig,
+                "REPL commands return `Result<Option<String>, E>`",
+            ))
+        }
+    };
+    let (generics, context) = match context {
+        Some(context) => (quote!(), quote!(#context)),
+        None => (quote!(<ReplContext>), quote!(ReplContext)),
+    };
+    let callback = if function.sig.asyncness.is_some() {
+        quote! {
+            #[allow(unused_variables)]
+            #item_vis fn callback #generics(
+                args: ::reedline_repl_rs::clap::ArgMatches,
+                context: &mut #context,
+            ) -> ::std::pin::Pin<Box<dyn ::std::future::Future<Output = #output> + '_>> {
+                Box::pin(super::#ident(#(#values),*))
+            }
+        }
+    } else {
+        quote! {
+            #[allow(unused_variables)]
+            #item_vis fn callback #generics(
+                args: ::reedline_repl_rs::clap::ArgMatches,
+                context: &mut #context,
+            ) -> #output {
+                super::#ident(#(#values),*)
+            }
+        }
+    };
+
+    Ok(quote! {
+        #function
+
+        #[allow(clippy::all)]
+        #vis mod #ident {
+            #[allow(unused_imports)]
+            use super::*;
+
+            /// The clap command of this REPL command
+            #item_vis fn command() -> ::reedline_repl_rs::clap::Command {
+                use ::reedline_repl_rs::clap::{value_parser, Arg, ArgAction, Command};
+                Command::new(#name) #(.arg(#args))* #about
+            }
+
+            /// Parses the arguments and calls the function
+            #callback
+        }
+    })
+}
+
+/// Splits `Option<T>` and `Vec<T>` into their kind and `T`
+fn classify(ty: &Type) -> (Kind, &Type) {
+    if let Type::Path(path) = ty {
+        if path.path.is_ident("bool") {
+            return (Kind::Flag, ty);
+        }
+        if let Some(segment) = path.path.segments.last() {
+            if let PathArguments::AngleBracketed(generic) = &segment.arguments {
+                if let Some(GenericArgument::Type(inner)) = generic.args.first() {
+                    if segment.ident == "Option" {
+                        return (Kind::Optional, inner);
+                    }
+                    if segment.ident == "Vec" {
+                        return (Kind::Many, inner);
+                    }
+                }
+            }
+        }
+    }
+    (Kind::Required, ty)
+}
+
+/// The first line of the doc comment
+fn doc_summary(function: &ItemFn) -> Option<String> {
+    function.attrs.iter().find_map(|attr| match &attr.meta {
+        Meta::NameValue(doc) if doc.path.is_ident("doc") => match &doc.value {
+            Expr::Lit(lit) => match &lit.lit {
+                Lit::Str(text) => Some(text.value().trim().to_string()),
+                _ => None,
+            },
+            _ => None,
+        },
+        _ => None,
+    })
+}
@@ -0,0 +1,149 @@
+use crate::error::*;
+
+/// Evaluates an argument of the form `$(expression)`, other arguments are returned unchanged
+pub(crate) fn expand_arithmetic(argument: &str) -> Result<String> {
+    match argument
+        .strip_prefix("$(")
+        .and_then(|rest| rest.strip_suffix(')'))
+    {
+        Some(expression) => evaluate(expression).map(format_number),
+        None => Ok(argument.to_string()),
+    }
+}
+
+/// The result of `value` if it is an arithmetic expression rather than a plain number,
+/// e.g. `10*1024` given for a numeric argument
+pub(crate) fn evaluate_value(value: &str) -> Option<String> {
+    let result = evaluate(value).map(format_number).ok()?;
+    (result != value).then_some(result)
+}
+
+/// Evaluates an arithmetic expression supporting `+ - * / %`, parentheses and unary signs
+pub(crate) fn evaluate(expression: &str) -> Result<f64> {
+    let mut parser = Parser {
+        chars: expression.chars().filter(|c| !c.is_whitespace()).collect(),
+        pos: 0,
+    };
+    match parser.expression() {
+        Some(value) if parser.pos == parser.chars.len() && value.is_finite() => Ok(value),
+        _ => Err(Error::ArithmeticError(expression.to_string())),
+    }
+}
+
+fn format_number(value: f64) -> String {
+    if value.fract() == 0.0 && value.abs() < 1e15 {
+        (value as i64).to_string()
+    } else {
+        value.to_string()
+    }
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn expression(&mut self) -> Option<f64> {
+        let mut value = self.term()?;
+        while let Some(op) = self.peek().filter(|c| *c == '+' || *c == '-') {
+            self.pos += 1;
+            let rhs = self.term()?;
+            value = if op == '+' { value + rhs } else { value - rhs };
+        }
+        Some(value)
+    }
+
+    fn term(&mut self) -> Option<f64> {
+        let mut value = self.factor()?;
+        while let Some(op) = self.peek().filter(|c| matches!(c, '*' | '/' | '%')) {
+            self.pos += 1;
+            let rhs = self.factor()?;
+            value = match op {
+                '*' => value * rhs,
+                '/' => value / rhs,
+                _ => value % rhs,
+            };
+        }
+        Some(value)
+    }
+
+    fn factor(&mut self) -> Option<f64> {
+        match self.peek()? {
+            '-' => {
+                self.pos += 1;
+                self.factor().map(|value| -value)
+            }
+            '+' => {
+                self.pos += 1;
+                self.factor()
+            }
+            '(' => {
+                self.pos += 1;
+                let value = self.expression()?;
+                if self.peek()? != ')' {
+                    return None;
+                }
+                self.pos += 1;
+                Some(value)
+            }
+            _ => self.number(),
+        }
+    }
+
+    fn number(&mut self) -> Option<f64> {
+        let start = self.pos;
+        while matches!(self.peek(), Some(c) if c.is_ascii_digit() || c == '.') {
+            self.pos += 1;
+        }
+        self.chars[start..self.pos]
+            .iter()
+            .collect::<String>()
+            .parse()
+            .ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn precedence_and_parentheses() {
+        assert_eq!(evaluate("1 + 2 * 3").unwrap(), 7.0);
+        assert_eq!(evaluate("(1 + 2) * 3").unwrap(), 9.0);
+        assert_eq!(evaluate("10 - 4 - 3").unwrap(), 3.0);
+        assert_eq!(evaluate("7 % 4 / 2").unwrap(), 1.5);
+        assert_eq!(evaluate("-(2 + -3)").unwrap(), 1.0);
+    }
+
+    #[test]
+    fn invalid_expressions() {
+        for expression in ["", "1 +", "(1", "2 (3)", "a", "1 / 0"] {
+            assert_eq!(
+                evaluate(expression),
+                Err(Error::ArithmeticError(expression.to_string()))
+            );
+        }
+    }
+
+    #[test]
+    fn only_arguments_in_dollar_parentheses_are_evaluated() {
+        assert_eq!(expand_arithmetic("$(6 * 7)").unwrap(), "42");
+        assert_eq!(expand_arithmetic("$(1 / 4)").unwrap(), "0.25");
+        assert_eq!(expand_arithmetic("6 * 7").unwrap(), "6 * 7");
+        assert!(expand_arithmetic("$(6 *)").is_err());
+    }
+
+    #[test]
+    fn bare_values_are_evaluated_if_not_plain_numbers() {
+        assert_eq!(evaluate_value("10*1024").as_deref(), Some("10240"));
+        assert_eq!(evaluate_value("10240"), None);
+        assert_eq!(evaluate_value("-3"), None);
+        assert_eq!(evaluate_value("ten"), None);
+    }
+}
@@ -0,0 +1,212 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// Set when the user presses Ctrl-C while a command runs, so long-running callbacks can
+/// stop early and return to the prompt. Clones share the same state, so keep a clone in
+/// your context, pass another one to
+/// [Repl::with_cancellation_token](struct.Repl.html#method.with_cancellation_token) and
+/// check [is_cancelled](#method.is_cancelled) in your loops. The token is reset before
+/// each command.
+///
+/// Ctrl-C is caught while a command runs on Unix and Windows, where Ctrl+Break cancels
+/// too. Elsewhere it still ends the process.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+    /// Create a token that is not cancelled
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether the running command should stop
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Cancel the running command, like pressing Ctrl-C
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    /// Resets the token and cancels it on Ctrl-C until the returned guard is dropped
+    pub(crate) fn watch(&self) -> CancelGuard {
+        self.cancelled.store(false, Ordering::SeqCst);
+        #[cfg(unix)]
+        let signal = sigint::watch(&self.cancelled);
+        #[cfg(windows)]
+        console::watch(&self.cancelled);
+        CancelGuard {
+            #[cfg(unix)]
+            signal,
+            #[cfg(windows)]
+            cancelled: Arc::clone(&self.cancelled),
+        }
+    }
+}
+
+/// Restores the default Ctrl-C behaviour when dropped
+pub(crate) struct CancelGuard {
+    #[cfg(unix)]
+    signal: Option<signal_hook::SigId>,
+    #[cfg(windows)]
+    cancelled: Arc<AtomicBool>,
+}
+
+impl Drop for CancelGuard {
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        sigint::unwatch(self.signal.take());
+        #[cfg(windows)]
+        console::unwatch(&self.cancelled);
+    }
+}
+
+/// SIGINT handlers setting the watched tokens. Unregistering a handler doesn't restore the
+/// default action, so a handler installed with the first watched token runs it whenever no
+/// token is watched and Ctrl-C ends the process again like before.
+#[cfg(unix)]
+mod sigint {
+    use signal_hook::consts::SIGINT;
+    use signal_hook::SigId;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::{Arc, Mutex, OnceLock, PoisonError};
+
+    /// Number of tokens watched right now, one per running command
+    static WATCHERS: Mutex<usize> = Mutex::new(0);
+
+    /// Whether no token is watched, read by the handler running the default action
+    static IDLE: OnceLock<Arc<AtomicBool>> = OnceLock::new();
+
+    pub(super) fn watch(cancelled: &Arc<AtomicBool>) -> Option<SigId> {
+        let idle = IDLE.get_or_init(|| {
+            let idle = Arc::new(AtomicBool::new(true));
+            let _ = signal_hook::flag::register_conditional_default(SIGINT, Arc::clone(&idle));
+            idle
+        });
+        let mut watchers = WATCHERS.lock().unwrap_or_else(PoisonError::into_inner);
+        *watchers += 1;
+        idle.store(false, Ordering::SeqCst);
+        signal_hook::flag::register(SIGINT, Arc::clone(cancelled)).ok()
+    }
+
+    pub(super) fn unwatch(signal: Option<SigId>) {
+        if let Some(signal) = signal {
+            signal_hook::low_level::unregister(signal);
+        }
+        let mut watchers = WATCHERS.lock().unwrap_or_else(PoisonError::into_inner);
+        *watchers = watchers.saturating_sub(1);
+        if *watchers == 0 {
+            if let Some(idle) = IDLE.get() {
+                idle.store(true, Ordering::SeqCst);
+            }
+        }
+    }
+}
+
+/// Console control handler setting the watched tokens on Ctrl-C and Ctrl+Break. Windows
+/// calls it on a thread of its own, and the default handler ending the process runs when
+/// no token is watched.
+#[cfg(windows)]
+mod console {
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::{Arc, Mutex, Once};
+    use windows_sys::Win32::Foundation::{BOOL, FALSE, TRUE};
+    use windows_sys::Win32::System::Console::{
+        SetConsoleCtrlHandler, CTRL_BREAK_EVENT, CTRL_C_EVENT,
+    };
+
+    /// Tokens of the commands running right now, one per REPL
+    static WATCHED: Mutex<Vec<Arc<AtomicBool>>> = Mutex::new(Vec::new());
+
+    pub(super) fn watch(cancelled: &Arc<AtomicBool>) {
+        static INSTALLED: Once = Once::new();
+        INSTALLED.call_once(|| unsafe {
+            SetConsoleCtrlHandler(Some(handler), TRUE);
+        });
+        if let Ok(mut watched) = WATCHED.lock() {
+            watched.push(Arc::clone(cancelled));
+        }
+    }
+
+    pub(super) fn unwatch(cancelled: &Arc<AtomicBool>) {
+        if let Ok(mut watched) = WATCHED.lock() {
+            if let Some(index) = watched.iter().position(|flag| Arc::ptr_eq(flag, cancelled)) {
+                watched.remove(index);
+            }
+        }
+    }
+
+    pub(super) unsafe extern "system" fn handler(ctrl_type: u32) -> BOOL {
+        if ctrl_type != CTRL_C_EVENT && ctrl_type != CTRL_BREAK_EVENT {
+            return FALSE;
+        }
+        match WATCHED.lock() {
+            Ok(watched) if !watched.is_empty() => {
+                for cancelled in watched.iter() {
+                    cancelled.store(true, Ordering::SeqCst);
+                }
+                TRUE
+            }
+            _ => FALSE,
+        }
+    }
+}
+
+#[cfg(all(test, windows))]
+mod tests {
+    use super::console::handler;
+    use super::*;
+    use windows_sys::Win32::Foundation::{FALSE, TRUE};
+    use windows_sys::Win32::System::Console::{CTRL_BREAK_EVENT, CTRL_CLOSE_EVENT};
+
+    #[test]
+    fn ctrl_break_cancels_watched_tokens() {
+        let token = CancellationToken::new();
+        let _guard = token.watch();
+        assert_eq!(unsafe { handler(CTRL_CLOSE_EVENT) }, FALSE);
+        assert!(!token.is_cancelled());
+        assert_eq!(unsafe { handler(CTRL_BREAK_EVENT) }, TRUE);
+        assert!(token.is_cancelled());
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use signal_hook::consts::SIGINT;
+    use std::os::unix::process::ExitStatusExt;
+    use std::process::Command;
+
+    /// Set for the copy of the test binary that checks Ctrl-C ends the process
+    const CHILD: &str = "REEDLINE_REPL_SIGINT_CHILD";
+
+    #[test]
+    fn ctrl_c_cancels_the_watched_token() {
+        let token = CancellationToken::new();
+        let _guard = token.watch();
+        signal_hook::low_level::raise(SIGINT).unwrap();
+        assert!(token.is_cancelled());
+    }
+
+    #[test]
+    fn ctrl_c_ends_the_process_once_the_command_returned() {
+        if std::env::var_os(CHILD).is_some() {
+            drop(CancellationToken::new().watch());
+            signal_hook::low_level::raise(SIGINT).unwrap();
+            std::process::exit(0);
+        }
+        let status = Command::new(std::env::current_exe().unwrap())
+            .args([
+                "--exact",
+                "cancel::tests::ctrl_c_ends_the_process_once_the_command_returned",
+            ])
+            .env(CHILD, "1")
+            .output()
+            .unwrap()
+            .status;
+        assert_eq!(status.signal(), Some(SIGINT));
+    }
+}
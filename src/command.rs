@@ -1,51 +1,638 @@
+use crate::arithmetic::evaluate_value;
+use crate::completer::argument_at;
+use crate::expand::expand_path;
+use crate::help_export::usage;
+use crate::interact::{input, password};
+use crate::messages::Messages;
+use crate::mode::Scope;
+use crate::suggestions::did_you_mean;
+use crate::tokenizer::is_flag;
 #[cfg(feature = "async")]
 use crate::AsyncCallback;
-use crate::Callback;
-use clap::Command;
+use crate::{
+    ArgCompleterCallback, Callback, CommandErrorHandler, GuardCallback, ParserCallback,
+    VisibleWhenCallback,
+};
+use clap::builder::ValueParser;
+use clap::error::{ContextKind, ContextValue, ErrorKind};
+use clap::parser::ValueSource;
+use clap::{Arg, ArgAction, ArgMatches, Command, ValueHint};
 use std::fmt;
 
-/// Struct to define a command in the REPL
+/// Option of a command set with a builder like `with_category`, kept by the command name
+/// and applied whenever a command of that name is registered
+pub(crate) enum CommandSetting<Context, E> {
+    Category(String),
+    ErrorHandler(CommandErrorHandler<Context, E>),
+    Deprecated(String),
+    Confirmation(String),
+    SensitiveArg(String),
+    ArgCompleter(String, ArgCompleterCallback<Context>),
+    HistoryExcluded,
+    Guard(GuardCallback<Context>),
+    VisibleWhen(VisibleWhenCallback<Context>),
+    Parser(ParserCallback<Context, E>),
+}
 
-pub(crate) struct ReplCommand<Context, E> {
+/// Struct to define a command in the REPL
+pub(crate) struct ReplCommand<Context, E, Output> {
     pub(crate) name: String,
     pub(crate) command: Command,
-    pub(crate) callback: Option<Callback<Context, E>>,
+    pub(crate) callback: Option<Callback<Context, E, Output>>,
     #[cfg(feature = "async")]
-    pub(crate) async_callback: Option<AsyncCallback<Context, E>>,
+    pub(crate) async_callback: Option<AsyncCallback<Context, E, Output>>,
+    pub(crate) visible_when: Option<VisibleWhenCallback<Context>>,
+    pub(crate) guard: Option<GuardCallback<Context>>,
+    pub(crate) parser: Option<ParserCallback<Context, E>>,
+    pub(crate) category: Option<String>,
+    pub(crate) mode: Option<String>,
+    pub(crate) app: Option<Command>,
+    pub(crate) sensitive: Vec<String>,
+    pub(crate) history: bool,
+    pub(crate) completers: Vec<(String, ArgCompleterCallback<Context>)>,
+    pub(crate) deprecated: Option<String>,
+    pub(crate) confirmation: Option<String>,
+    pub(crate) error_handler: Option<CommandErrorHandler<Context, E>>,
 }
 
-impl<Context, E> fmt::Debug for ReplCommand<Context, E> {
+impl<Context, E, Output> fmt::Debug for ReplCommand<Context, E, Output> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("Command").field("name", &self.name).finish()
     }
 }
 
-impl<Context, E> PartialEq for ReplCommand<Context, E> {
-    fn eq(&self, other: &ReplCommand<Context, E>) -> bool {
+impl<Context, E, Output> PartialEq for ReplCommand<Context, E, Output> {
+    fn eq(&self, other: &ReplCommand<Context, E, Output>) -> bool {
         self.name == other.name
     }
 }
 
-impl<Context, E> ReplCommand<Context, E> {
+impl<Context, E, Output> ReplCommand<Context, E, Output> {
     /// Create a new command with the given name and callback function
-    pub fn new(name: &str, command: Command, callback: Callback<Context, E>) -> Self {
+    pub fn new(name: &str, command: Command, callback: Callback<Context, E, Output>) -> Self {
         Self {
             name: name.to_string(),
             command,
             callback: Some(callback),
             #[cfg(feature = "async")]
             async_callback: None,
+            visible_when: None,
+            guard: None,
+            parser: None,
+            category: None,
+            mode: None,
+            app: None,
+            sensitive: vec![],
+            history: true,
+            completers: vec![],
+            deprecated: None,
+            confirmation: None,
+            error_handler: None,
+        }
+    }
+
+    /// Applies an option set with one of the `Repl::with_*` builders taking a command name
+    pub(crate) fn apply(&mut self, setting: &CommandSetting<Context, E>) {
+        match setting {
+            CommandSetting::Category(category) => self.category = Some(category.clone()),
+            CommandSetting::ErrorHandler(handler) => self.error_handler = Some(*handler),
+            CommandSetting::Deprecated(note) => self.deprecated = Some(note.clone()),
+            CommandSetting::Confirmation(question) => {
+                self.confirmation = Some(question.clone());
+                self.add_yes_flag();
+            }
+            CommandSetting::SensitiveArg(arg) => {
+                // an unknown argument, e.g. a typo, can't be asked for
+                if self
+                    .command
+                    .get_arguments()
+                    .any(|known| known.get_id() == arg)
+                {
+                    self.sensitive.push(arg.clone());
+                }
+            }
+            CommandSetting::ArgCompleter(arg, callback) => {
+                self.completers.push((arg.clone(), *callback))
+            }
+            CommandSetting::HistoryExcluded => self.history = false,
+            CommandSetting::Guard(guard) => self.guard = Some(*guard),
+            CommandSetting::VisibleWhen(predicate) => self.visible_when = Some(*predicate),
+            CommandSetting::Parser(parser) => self.parser = Some(*parser),
         }
     }
 
+    /// Adds the `--yes` flag skipping the confirmation, and `-y` if that is free
+    fn add_yes_flag(&mut self) {
+        if self
+            .command
+            .get_arguments()
+            .any(|arg| arg.get_id() == "yes")
+        {
+            return;
+        }
+        let short_free = !self
+            .command
+            .get_arguments()
+            .any(|arg| arg.get_short() == Some('y'));
+        let mut yes = Arg::new("yes")
+            .long("yes")
+            .action(ArgAction::SetTrue)
+            .help("Run without asking for confirmation");
+        if short_free {
+            yes = yes.short('y');
+        }
+        self.command = self.command.clone().arg(yes);
+    }
+
     /// Create a new async command with the given name and callback function
     #[cfg(feature = "async")]
-    pub fn new_async(name: &str, command: Command, callback: AsyncCallback<Context, E>) -> Self {
+    pub fn new_async(
+        name: &str,
+        command: Command,
+        callback: AsyncCallback<Context, E, Output>,
+    ) -> Self {
         Self {
             name: name.to_string(),
             command,
             callback: None,
             async_callback: Some(callback),
+            visible_when: None,
+            guard: None,
+            parser: None,
+            category: None,
+            mode: None,
+            app: None,
+            sensitive: vec![],
+            history: true,
+            completers: vec![],
+            deprecated: None,
+            confirmation: None,
+            error_handler: None,
+        }
+    }
+
+    /// Whether the command should be shown in help and completion for the given context,
+    /// commands created with clap's `Command::hide(true)` are never shown
+    pub fn is_visible(&self, context: &Context) -> bool {
+        if self.command.is_hide_set() {
+            return false;
+        }
+        match self.visible_when {
+            Some(visible_when) => visible_when(context),
+            None => true,
+        }
+    }
+
+    /// Whether the command can be used in `scope`, commands without a mode are available
+    /// in every mode but sub-REPLs
+    pub fn is_available(&self, scope: &Scope) -> bool {
+        match &self.mode {
+            Some(own) => Some(own) == scope.mode.as_ref(),
+            None => !scope.exclusive,
+        }
+    }
+
+    /// Usage line of the command like `query [OPTIONS] <sql>`
+    pub fn usage(&self) -> String {
+        usage(&self.name, &self.command)
+    }
+
+    /// The clap command as shown in help and completion, deprecated commands carry the
+    /// note in their description
+    pub fn help_command(&self, marker: &str) -> Command {
+        match &self.deprecated {
+            Some(note) => {
+                let about = self
+                    .command
+                    .get_about()
+                    .map(|about| format!("{} ", about))
+                    .unwrap_or_default();
+                let marker = Messages::fill(marker, &[note.as_str()]);
+                self.command.clone().about(about + &marker)
+            }
+            None => self.command.clone(),
+        }
+    }
+
+    /// Parses the arguments, collecting every invalid value instead of stopping at the first.
+    /// With `infer_choices` a value that is the prefix of exactly one possible value is
+    /// accepted as that value, with `expand_paths` values of path arguments are expanded.
+    /// With `arithmetic` a value failing validation, e.g. of a numeric argument, is replaced
+    /// by its result if it is an arithmetic expression like `10*1024`. Sensitive arguments
+    /// missing from the line are asked for, and with `prompt_missing` so are missing required
+    /// arguments.
+    pub fn get_matches(
+        &self,
+        argv: &[&str],
+        infer_choices: bool,
+        expand_paths: bool,
+        arithmetic: bool,
+        prompt_missing: bool,
+    ) -> Result<ArgMatches, Vec<clap::Error>> {
+        if self.parser.is_some() {
+            return parsed_values(&self.command, argv)
+                .try_get_matches_from(argv)
+                .map_err(|err| vec![err]);
+        }
+        let mut argv: Vec<String> = argv.iter().map(|arg| arg.to_string()).collect();
+        if expand_paths {
+            argv = self.expand_paths(argv);
+        }
+        // commands imported from a clap application are parsed by the whole application,
+        // so the callback gets the same matches as from the command line
+        let parser = match &self.app {
+            Some(app) => {
+                argv.insert(0, app.get_name().to_string());
+                allow_negative_numbers(app.clone())
+            }
+            None => allow_negative_numbers(self.command.clone()),
+        };
+        let mut errors = vec![];
+        let mut relaxed: Vec<String> = vec![];
+        loop {
+            // clap doesn't support changing arguments of a command that already parsed,
+            // the relaxed arguments are applied to a fresh copy
+            let mut command = relaxed.iter().fold(parser.clone(), |command, id| {
+                command.mut_arg(id, |arg| arg.value_parser(ValueParser::string()))
+            });
+            match command.try_get_matches_from_mut(&argv) {
+                Ok(matches) if errors.is_empty() => match self.missing_secret(&matches) {
+                    Some(id) => {
+                        self.ask_secret(id, &mut argv).map_err(|err| vec![err])?;
+                        continue;
+                    }
+                    None => return Ok(matches),
+                },
+                Ok(_) => return Err(errors),
+                Err(mut err) => {
+                    if prompt_missing
+                        && errors.is_empty()
+                        && err.kind() == ErrorKind::MissingRequiredArgument
+                        && ask_missing(&command, &err, &mut argv)
+                    {
+                        continue;
+                    }
+                    if err.kind() == ErrorKind::InvalidValue {
+                        if infer_choices && complete_choice(&mut argv, &err) {
+                            continue;
+                        }
+                        suggest_choice(&mut err);
+                    }
+                    if arithmetic
+                        && err.kind() == ErrorKind::ValueValidation
+                        && evaluate_invalid_value(&mut argv, &err)
+                    {
+                        continue;
+                    }
+                    // an option without its value is an invalid empty value, accepting any
+                    // value wouldn't change the error
+                    let invalid = match err.kind() {
+                        ErrorKind::ValueValidation => invalid_arg_id(&command, &err),
+                        ErrorKind::InvalidValue if has_invalid_value(&err) => {
+                            invalid_arg_id(&command, &err)
+                        }
+                        _ => None,
+                    };
+                    errors.push(err);
+                    // Accept any value for the invalid argument to find the remaining problems,
+                    // an argument that is still invalid once relaxed ends the search
+                    match invalid {
+                        Some(id) if !relaxed.contains(&id) => relaxed.push(id),
+                        _ => return Err(errors),
+                    }
+                }
+            }
+        }
+    }
+
+    /// Expands `~` and globs in the words of `argv` that are values of path arguments
+    fn expand_paths(&self, argv: Vec<String>) -> Vec<String> {
+        let mut expanded = vec![];
+        for (index, word) in argv.iter().enumerate() {
+            let args: Vec<&str> = argv[1..index.max(1)].iter().map(String::as_str).collect();
+            let is_path = index > 0
+                && !is_flag(word)
+                && argument_at(&self.command, &args).is_some_and(|arg| {
+                    matches!(
+                        arg.get_value_hint(),
+                        ValueHint::AnyPath
+                            | ValueHint::FilePath
+                            | ValueHint::DirPath
+                            | ValueHint::ExecutablePath
+                    )
+                });
+            if is_path {
+                expanded.extend(expand_path(word));
+            } else {
+                expanded.push(word.clone());
+            }
+        }
+        expanded
+    }
+
+    /// The first sensitive argument that wasn't given on the command line
+    fn missing_secret(&self, matches: &ArgMatches) -> Option<&str> {
+        let matches = match &self.app {
+            Some(_) => matches.subcommand()?.1,
+            None => matches,
+        };
+        self.sensitive
+            .iter()
+            .find(|id| matches.value_source(id) != Some(ValueSource::CommandLine))
+            .map(String::as_str)
+    }
+
+    /// Prompts for the value of a sensitive argument with hidden input and adds it to `argv`
+    fn ask_secret(&self, id: &str, argv: &mut Vec<String>) -> Result<(), clap::Error> {
+        let arg = self
+            .command
+            .get_arguments()
+            .find(|arg| arg.get_id() == id)
+            .expect("sensitive arguments are checked when applied");
+        let secret = password(&format!("{}: ", value_name(arg)))
+            .map_err(|err| clap::Error::raw(ErrorKind::Io, format!("{}\n", err)))?;
+        push_value(arg, secret, argv);
+
+        Ok(())
+    }
+}
+
+/// Command accepting the values of a custom parser, passed as `--name=value`, without
+/// validating them. The arguments of `command` are optional values too, so the callback
+/// can look up the ones the parser didn't return.
+fn parsed_values(command: &Command, argv: &[&str]) -> Command {
+    let mut names: Vec<String> = command
+        .get_arguments()
+        .map(|arg| arg.get_id().to_string())
+        .collect();
+    for arg in argv.iter().skip(1) {
+        if let Some((name, _)) = arg.strip_prefix("--").and_then(|arg| arg.split_once('=')) {
+            if !names.iter().any(|known| known == name) {
+                names.push(name.to_string());
+            }
+        }
+    }
+    Command::new(command.get_name().to_string())
+        .disable_help_flag(true)
+        .disable_version_flag(true)
+        .args(
+            names
+                .into_iter()
+                .map(|name| Arg::new(name.clone()).long(name)),
+        )
+}
+
+/// Lets arguments take negative numbers like `-5` as values, unless the command has a flag
+/// like `-1` they could be mistaken for
+fn allow_negative_numbers(mut command: Command) -> Command {
+    let digit_flag = command
+        .get_arguments()
+        .any(|arg| arg.get_short().is_some_and(|short| short.is_ascii_digit()));
+    if !digit_flag {
+        let ids: Vec<String> = command
+            .get_arguments()
+            .filter(|arg| arg.get_action().takes_values())
+            .map(|arg| arg.get_id().to_string())
+            .collect();
+        for id in ids {
+            command = command.mut_arg(id, |arg| arg.allow_negative_numbers(true));
+        }
+    }
+    let names: Vec<String> = command
+        .get_subcommands()
+        .map(|subcommand| subcommand.get_name().to_string())
+        .collect();
+    for name in names {
+        command = command.mut_subcommand(name, allow_negative_numbers);
+    }
+    command
+}
+
+/// The invalid value and the possible values of an `InvalidValue` error
+fn choices(error: &clap::Error) -> Option<(&str, &[String])> {
+    match (
+        error.get(ContextKind::InvalidValue)?,
+        error.get(ContextKind::ValidValue)?,
+    ) {
+        (ContextValue::String(value), ContextValue::Strings(choices)) => Some((value, choices)),
+        _ => None,
+    }
+}
+
+/// Replaces an invalid value that is the prefix of exactly one choice with that choice
+fn complete_choice(argv: &mut [String], error: &clap::Error) -> bool {
+    let (value, choices) = match choices(error) {
+        Some((value, choices)) if !value.is_empty() => (value, choices),
+        _ => return false,
+    };
+    let mut matching = choices.iter().filter(|choice| choice.starts_with(value));
+    let choice = match (matching.next(), matching.next()) {
+        (Some(choice), None) => choice,
+        _ => return false,
+    };
+    replace_value(argv, value, choice)
+}
+
+/// Replaces the value a `ValueValidation` error complains about with its result if it is an
+/// arithmetic expression
+fn evaluate_invalid_value(argv: &mut [String], error: &clap::Error) -> bool {
+    let value = match error.get(ContextKind::InvalidValue) {
+        Some(ContextValue::String(value)) => value,
+        _ => return false,
+    };
+    match evaluate_value(value) {
+        Some(result) => replace_value(argv, value, &result),
+        None => false,
+    }
+}
+
+/// Replaces the first word of `argv` that is `value`, alone or as in `--name=value`
+fn replace_value(argv: &mut [String], value: &str, replacement: &str) -> bool {
+    for arg in argv.iter_mut().skip(1) {
+        if arg.as_str() == value {
+            *arg = replacement.to_string();
+            return true;
+        }
+        if let Some(option) = arg.strip_suffix(value).filter(|o| o.ends_with('=')) {
+            *arg = format!("{}{}", option, replacement);
+            return true;
+        }
+    }
+    false
+}
+
+/// Adds the closest choice to an `InvalidValue` error unless clap already suggests one
+fn suggest_choice(error: &mut clap::Error) {
+    if error.get(ContextKind::SuggestedValue).is_some() {
+        return;
+    }
+    let suggestion = match choices(error) {
+        Some((value, choices)) => did_you_mean(value, choices.iter().map(String::as_str))
+            .into_iter()
+            .next(),
+        None => None,
+    };
+    if let Some(suggestion) = suggestion {
+        error.insert(
+            ContextKind::SuggestedValue,
+            ContextValue::String(suggestion),
+        );
+    }
+}
+
+/// Prompts for the first required argument a clap error reports missing and adds the
+/// answer to `argv`. Returns false when the argument is unknown or the answer is empty.
+fn ask_missing(command: &Command, error: &clap::Error, argv: &mut Vec<String>) -> bool {
+    let arg = match error.get(ContextKind::InvalidArg) {
+        Some(ContextValue::Strings(missing)) => missing
+            .first()
+            .and_then(|missing| find_arg(command, missing)),
+        _ => None,
+    };
+    let arg = match arg {
+        Some(arg) => arg,
+        None => return false,
+    };
+    match input(&format!("{}: ", value_name(arg))) {
+        Ok(value) if !value.is_empty() => {
+            push_value(arg, value, argv);
+            true
         }
+        _ => false,
+    }
+}
+
+/// Finds an argument by how clap displays it in `command` or one of its subcommands
+fn find_arg<'a>(command: &'a Command, name: &str) -> Option<&'a clap::Arg> {
+    command
+        .get_arguments()
+        .find(|arg| arg.to_string() == name)
+        .or_else(|| {
+            command
+                .get_subcommands()
+                .find_map(|subcommand| find_arg(subcommand, name))
+        })
+}
+
+/// The name to ask for the value of an argument by, its value name or else its id
+fn value_name(arg: &clap::Arg) -> String {
+    arg.get_value_names()
+        .and_then(|names| names.first())
+        .map(|name| name.to_string())
+        .unwrap_or_else(|| arg.get_id().to_string())
+}
+
+/// Adds a value for an argument to `argv`, after its flag unless it is positional
+fn push_value(arg: &clap::Arg, value: String, argv: &mut Vec<String>) {
+    if let Some(long) = arg.get_long() {
+        argv.push(format!("--{}", long));
+    } else if let Some(short) = arg.get_short() {
+        argv.push(format!("-{}", short));
+    }
+    argv.push(value);
+}
+
+/// Whether the error names a non-empty invalid value
+fn has_invalid_value(error: &clap::Error) -> bool {
+    matches!(
+        error.get(ContextKind::InvalidValue),
+        Some(ContextValue::String(value)) if !value.is_empty()
+    )
+}
+
+/// Finds the id of the argument a clap error complains about
+fn invalid_arg_id(command: &Command, error: &clap::Error) -> Option<String> {
+    match error.get(ContextKind::InvalidArg)? {
+        ContextValue::String(invalid) => command
+            .get_arguments()
+            .find(|arg| arg.to_string() == *invalid)
+            .map(|arg| arg.get_id().to_string()),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Arg;
+
+    fn greet() -> ReplCommand<(), String, String> {
+        let command = Command::new("greet")
+            .arg(Arg::new("name").long("name"))
+            .arg(
+                Arg::new("times")
+                    .long("times")
+                    .value_parser(clap::value_parser!(u8)),
+            )
+            .arg(
+                Arg::new("count")
+                    .long("count")
+                    .value_parser(clap::value_parser!(u8)),
+            );
+        ReplCommand::new("greet", command, |_, _| Ok(None))
+    }
+
+    fn error_count(argv: &[&str]) -> usize {
+        match greet().get_matches(argv, false, false, false, false) {
+            Ok(_) => 0,
+            Err(errors) => errors.len(),
+        }
+    }
+
+    #[test]
+    fn option_without_value_is_one_error() {
+        assert_eq!(error_count(&["greet", "--name"]), 1);
+        assert_eq!(error_count(&["greet", "--times"]), 1);
+    }
+
+    #[test]
+    fn every_invalid_value_is_reported() {
+        assert_eq!(error_count(&["greet", "--times", "x", "--count", "y"]), 2);
+        assert_eq!(error_count(&["greet", "--times", "x", "--name"]), 2);
+        assert_eq!(error_count(&["greet", "--times", "3", "--name", "ann"]), 0);
+    }
+
+    #[test]
+    fn numeric_values_may_be_expressions() {
+        let matches = greet()
+            .get_matches(
+                &["greet", "--times", "2*3", "--count=(1+1)*4"],
+                false,
+                false,
+                true,
+                false,
+            )
+            .unwrap();
+        assert_eq!(matches.get_one::<u8>("times"), Some(&6));
+        assert_eq!(matches.get_one::<u8>("count"), Some(&8));
+        assert_eq!(error_count(&["greet", "--times", "2*3"]), 1);
+        assert!(greet()
+            .get_matches(&["greet", "--times", "1/4"], false, false, true, false)
+            .is_err());
+    }
+
+    #[test]
+    fn asked_values_are_added_after_their_flag() {
+        let mut command = Command::new("copy")
+            .arg(Arg::new("source").value_name("FILE").required(true))
+            .arg(Arg::new("mode").long("mode").required(true));
+        // parsing builds the command before clap reports a missing argument
+        command.build();
+        let mut argv = vec!["copy".to_string()];
+        let source = find_arg(&command, "<FILE>").unwrap();
+        assert_eq!(value_name(source), "FILE");
+        push_value(source, "a.txt".to_string(), &mut argv);
+        let mode = find_arg(&command, "--mode <mode>").unwrap();
+        assert_eq!(value_name(mode), "mode");
+        push_value(mode, "fast".to_string(), &mut argv);
+        assert_eq!(argv, ["copy", "a.txt", "--mode", "fast"]);
+    }
+
+    #[test]
+    fn unknown_sensitive_argument_is_ignored() {
+        let mut command = greet();
+        command.apply(&CommandSetting::SensitiveArg("pasword".to_string()));
+        command.apply(&CommandSetting::SensitiveArg("name".to_string()));
+        assert_eq!(command.sensitive, vec!["name".to_string()]);
     }
 }
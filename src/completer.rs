@@ -1,11 +1,153 @@
-use crate::command::ReplCommand;
+use crate::expand::expand_home;
+use crate::tokenizer::is_flag;
 use clap::builder::StyledStr;
-use clap::Command;
+use clap::{Arg, Command, ValueHint};
 use reedline::{Completer, Span, Suggestion};
 use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+/// How completions are matched against the word being completed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompletionMatching {
+    /// Completions starting with the word
+    Prefix,
+    /// Completions containing the word, those containing it earlier first
+    Substring,
+    /// Completions containing the characters of the word in order, ignoring case, so `tbl`
+    /// completes to `show-tables`. Closer matches come first.
+    Fuzzy,
+}
+
+/// The index of the commands of the current prompt, shared with the help menu
+pub(crate) type SharedIndex = Arc<Mutex<Option<Arc<CompletionIndex>>>>;
+
+/// The commands to complete with their names indexed, built once and shared by the
+/// completers of the following prompts while the commands stay the same
+pub(crate) struct CompletionIndex {
+    commands: Vec<Command>,
+    /// Names and visible aliases, sorted, with the index of their command and their
+    /// position among its names to list them in the order of the commands
+    names: Vec<(String, usize, usize)>,
+    /// Command index by name or alias, hidden aliases included
+    lookup: HashMap<String, usize>,
+}
+
+impl CompletionIndex {
+    pub(crate) fn new(commands: Vec<Command>) -> Self {
+        let mut names = vec![];
+        let mut lookup = HashMap::new();
+        for (index, command) in commands.iter().enumerate() {
+            let visible = std::iter::once(command.get_name()).chain(command.get_visible_aliases());
+            for (position, name) in visible.enumerate() {
+                names.push((name.to_string(), index, position));
+            }
+            for alias in command.get_all_aliases() {
+                lookup.entry(alias.to_string()).or_insert(index);
+            }
+        }
+        // names win over aliases of other commands
+        for (index, command) in commands.iter().enumerate() {
+            lookup.insert(command.get_name().to_string(), index);
+        }
+        names.sort();
+        CompletionIndex {
+            commands,
+            names,
+            lookup,
+        }
+    }
+
+    /// Whether the index was built from commands with the same names as `commands`, down
+    /// to their subcommands
+    pub(crate) fn indexes(&self, commands: &[Command]) -> bool {
+        same_names(self.commands.iter(), commands.iter())
+    }
+
+    /// The command with the name or alias `name`
+    fn find(&self, name: &str) -> Option<&Command> {
+        self.commands.get(*self.lookup.get(name)?)
+    }
+
+    /// Positions in `names` of the names starting with `prefix`
+    fn starting_with(&self, prefix: &str) -> std::ops::Range<usize> {
+        let start = self
+            .names
+            .partition_point(|(name, _, _)| name.as_str() < prefix);
+        let end = start
+            + self.names[start..]
+                .iter()
+                .take_while(|(name, _, _)| name.starts_with(prefix))
+                .count();
+        start..end
+    }
+}
+
+/// Completer of the help menu, offering the line unchanged with the help of the command on
+/// it, down to the subcommand given, so it's shown without losing the input. On an empty
+/// line it offers every command with its description.
+pub(crate) struct HelpCompleter {
+    index: SharedIndex,
+}
+
+impl HelpCompleter {
+    pub(crate) fn new(index: SharedIndex) -> Self {
+        HelpCompleter { index }
+    }
+}
+
+impl Completer for HelpCompleter {
+    fn complete(&mut self, line: &str, pos: usize) -> Vec<Suggestion> {
+        let Some(index) = self.index.lock().ok().and_then(|index| index.clone()) else {
+            return vec![];
+        };
+        let span = Span::new(0, line.len());
+        let mut words = line.split_whitespace();
+        let Some(mut command) = words.next().map(|name| index.find(name)) else {
+            return index
+                .commands
+                .iter()
+                .map(|command| Suggestion {
+                    value: command.get_name().to_string(),
+                    description: command.get_about().map(|about| about.to_string()),
+                    extra: None,
+                    span,
+                    append_whitespace: true,
+                })
+                .collect();
+        };
+        for word in words {
+            match command.and_then(|command| command.find_subcommand(word)) {
+                Some(subcommand) => command = Some(subcommand),
+                None => break,
+            }
+        }
+        // the menu shows a description on a single row, so the help is split into rows that
+        // leave the line unchanged when selected
+        let Some(command) = command else {
+            return vec![];
+        };
+        let help = command.clone().render_help().to_string();
+        help.lines()
+            .map(|row| Suggestion {
+                value: String::new(),
+                description: Some(row.to_string()),
+                extra: None,
+                span: Span::new(pos, pos),
+                append_whitespace: false,
+            })
+            .collect()
+    }
+}
 
 pub(crate) struct ReplCompleter {
-    commands: HashMap<String, Command>,
+    index: Arc<CompletionIndex>,
+    values: HashMap<(String, String), Vec<String>>,
+    help_name: String,
+    help_about: String,
+    matching: CompletionMatching,
+    /// The last command name searched and the positions in the index of its matches,
+    /// narrowed down instead of searching all names again while the name is typed
+    last_search: Option<(String, Vec<usize>)>,
 }
 
 impl Completer for ReplCompleter {
@@ -26,7 +168,7 @@ impl Completer for ReplCompleter {
                     }
                 } else {
                     // If no command is found, look for a top-level one
-                    deepest_command = self.commands.get(*word);
+                    deepest_command = self.find_command(word);
                     deepest_command_idx = i;
                 }
             }
@@ -38,7 +180,33 @@ impl Completer for ReplCompleter {
                 let last_word = words_rev.next().unwrap();
                 let last_word_start_pos = line.len() - last_word.len();
                 let span = Span::new(last_word_start_pos, pos);
-                self.parameter_values_starting_with(command, words_rev.count(), last_word, span)
+                let argument = if words_left.len() > 1 {
+                    argument_at(command, &words_left[1..words_left.len() - 1])
+                } else {
+                    None
+                };
+                let values = argument.and_then(|arg| {
+                    let key = (command.get_name().to_string(), arg.get_id().to_string());
+                    self.values.get(&key)
+                });
+                match (values, argument.map(Arg::get_value_hint)) {
+                    (Some(values), _) => values
+                        .iter()
+                        .filter(|value| self.matches(value, last_word))
+                        .map(|value| self.build_suggestion(value, None, span))
+                        .collect(),
+                    (None, Some(ValueHint::DirPath)) => paths_starting_with(last_word, true, span),
+                    (
+                        None,
+                        Some(ValueHint::AnyPath | ValueHint::FilePath | ValueHint::ExecutablePath),
+                    ) => paths_starting_with(last_word, false, span),
+                    _ => self.parameter_values_starting_with(
+                        command,
+                        words_rev.count(),
+                        last_word,
+                        span,
+                    ),
+                }
             } else {
                 vec![]
             }
@@ -47,17 +215,57 @@ impl Completer for ReplCompleter {
             self.commands_starting_with(line, span)
         });
         completions.dedup();
+        if self.matching != CompletionMatching::Prefix {
+            completions.sort_by_cached_key(|suggestion| {
+                let search = line
+                    .get(suggestion.span.start..suggestion.span.end)
+                    .unwrap_or_default();
+                match_score(self.matching, &suggestion.value, search).unwrap_or(usize::MAX)
+            });
+        }
         completions
     }
 }
 
 impl ReplCompleter {
-    pub fn new<Context, E>(repl_commands: &HashMap<String, ReplCommand<Context, E>>) -> Self {
-        let mut commands = HashMap::new();
-        for (name, repl_command) in repl_commands.iter() {
-            commands.insert(name.clone(), repl_command.command.clone());
+    pub fn new(index: Arc<CompletionIndex>) -> Self {
+        ReplCompleter {
+            index,
+            values: HashMap::new(),
+            help_name: "help".to_string(),
+            help_about: "show help".to_string(),
+            matching: CompletionMatching::Prefix,
+            last_search: None,
         }
-        ReplCompleter { commands }
+    }
+
+    /// Name and description of the help command
+    pub fn with_help(mut self, name: &str, about: &str) -> Self {
+        self.help_name = name.to_string();
+        self.help_about = about.to_string();
+        self
+    }
+
+    /// How completions are matched against the word being completed
+    pub fn with_matching(mut self, matching: CompletionMatching) -> Self {
+        self.matching = matching;
+        self
+    }
+
+    /// Values of arguments computed from the context, by command name and argument id
+    pub fn with_values(mut self, values: HashMap<(String, String), Vec<String>>) -> Self {
+        self.values = values;
+        self
+    }
+
+    /// Finds a top-level command by its name or one of its aliases
+    fn find_command(&self, name: &str) -> Option<&Command> {
+        self.index.find(name)
+    }
+
+    /// Whether `candidate` completes `search`
+    fn matches(&self, candidate: &str, search: &str) -> bool {
+        match_score(self.matching, candidate, search).is_some()
     }
 
     fn build_suggestion(&self, value: &str, help: Option<&StyledStr>, span: Span) -> Suggestion {
@@ -87,27 +295,27 @@ impl ReplCompleter {
             completions.extend(
                 arg.get_possible_values()
                     .iter()
-                    .filter(|value| value.get_name().starts_with(search))
+                    .filter(|value| self.matches(value.get_name(), search))
                     .map(|value| self.build_suggestion(value.get_name(), value.get_help(), span)),
             );
 
             if let Some(long) = arg.get_long() {
                 let value = "--".to_string() + long;
-                if value.starts_with(search) {
+                if self.matches(&value, search) {
                     completions.push(self.build_suggestion(&value, arg.get_help(), span));
                 }
             }
 
             if let Some(short) = arg.get_short() {
                 let value = "-".to_string() + &short.to_string();
-                if value.starts_with(search) {
+                if self.matches(&value, search) {
                     completions.push(self.build_suggestion(&value, arg.get_help(), span));
                 }
             }
         }
 
         for subcommand in command.get_subcommands() {
-            if subcommand.get_name().starts_with(search) {
+            if self.matches(subcommand.get_name(), search) {
                 completions.push(self.build_suggestion(
                     subcommand.get_name(),
                     subcommand.get_after_help(),
@@ -119,21 +327,162 @@ impl ReplCompleter {
         completions
     }
 
-    fn commands_starting_with(&self, search: &str, span: Span) -> Vec<Suggestion> {
-        let mut result: Vec<Suggestion> = self
-            .commands
+    fn commands_starting_with(&mut self, search: &str, span: Span) -> Vec<Suggestion> {
+        let index = Arc::clone(&self.index);
+        // every strategy only drops candidates as the searched name gets longer
+        let candidates: Vec<usize> = match self.last_search.take() {
+            Some((last, matched)) if search.starts_with(&last) => matched,
+            _ if self.matching == CompletionMatching::Prefix => {
+                index.starting_with(search).collect()
+            }
+            _ => (0..index.names.len()).collect(),
+        };
+        let matched: Vec<usize> = candidates
+            .into_iter()
+            .filter(|&position| self.matches(&index.names[position].0, search))
+            .collect();
+        self.last_search = Some((search.to_string(), matched.clone()));
+
+        let mut names: Vec<&(String, usize, usize)> = matched
             .iter()
-            .filter(|(key, _)| key.starts_with(search))
-            .map(|(_, command)| {
-                self.build_suggestion(command.get_name(), command.get_about(), span)
+            .map(|&position| &index.names[position])
+            .collect();
+        names.sort_by_key(|(_, command, position)| (*command, *position));
+        let mut result: Vec<Suggestion> = names
+            .into_iter()
+            .map(|(name, command, _)| {
+                self.build_suggestion(name, index.commands[*command].get_about(), span)
             })
             .collect();
 
-        if "help".starts_with(search) {
-            let help: StyledStr = "show help".into();
-            result.push(self.build_suggestion("help", Some(&help), span));
+        if self.matches(&self.help_name, search) {
+            let help: StyledStr = self.help_about.clone().into();
+            result.push(self.build_suggestion(&self.help_name, Some(&help), span));
         }
 
         result
     }
 }
+
+/// Whether both lists of commands have the same names, down to their subcommands
+fn same_names<'a>(
+    mut left: impl Iterator<Item = &'a Command>,
+    mut right: impl Iterator<Item = &'a Command>,
+) -> bool {
+    loop {
+        match (left.next(), right.next()) {
+            (None, None) => return true,
+            (Some(left), Some(right)) => {
+                if left.get_name() != right.get_name()
+                    || !same_names(left.get_subcommands(), right.get_subcommands())
+                {
+                    return false;
+                }
+            }
+            _ => return false,
+        }
+    }
+}
+
+/// How well `candidate` matches `search`, lower is better, `None` if it doesn't match
+fn match_score(matching: CompletionMatching, candidate: &str, search: &str) -> Option<usize> {
+    match matching {
+        CompletionMatching::Prefix => candidate.starts_with(search).then_some(0),
+        CompletionMatching::Substring => candidate.find(search),
+        CompletionMatching::Fuzzy => {
+            // characters skipped before and between the characters of `search`
+            let mut score = 0;
+            let mut chars = candidate.chars().map(|c| c.to_ascii_lowercase());
+            for wanted in search.chars().map(|c| c.to_ascii_lowercase()) {
+                score += chars.by_ref().position(|c| c == wanted)?;
+            }
+            Some(score)
+        }
+    }
+}
+
+/// The argument the word after `args` is a value of, either the argument of a preceding
+/// flag taking a value or the positional argument at that position, the last positional
+/// argument if it takes several values
+pub(crate) fn argument_at<'a>(command: &'a Command, args: &[&str]) -> Option<&'a Arg> {
+    let mut positional = 0;
+    let mut options_ended = false;
+    let mut words = args.iter().filter(|word| !word.is_empty()).peekable();
+    while let Some(word) = words.next() {
+        if options_ended || !is_flag(word) {
+            positional += 1;
+        } else if *word == "--" {
+            options_ended = true;
+        } else if !word.contains('=') {
+            let flag = find_flag(command, word);
+            if flag.is_some_and(|arg| arg.get_action().takes_values()) {
+                if words.peek().is_none() {
+                    return flag;
+                }
+                words.next();
+            }
+        }
+    }
+    command.get_positionals().nth(positional).or_else(|| {
+        command.get_positionals().last().filter(|arg| {
+            arg.get_num_args()
+                .is_some_and(|range| range.max_values() > 1)
+        })
+    })
+}
+
+fn find_flag<'a>(command: &'a Command, flag: &str) -> Option<&'a Arg> {
+    match flag.strip_prefix("--") {
+        Some(long) => command
+            .get_arguments()
+            .find(|arg| arg.get_long() == Some(long)),
+        None => {
+            let short = flag.chars().nth(1)?;
+            command
+                .get_arguments()
+                .find(|arg| arg.get_short() == Some(short))
+        }
+    }
+}
+
+/// Completes file and directory names, `~` stands for the home directory and names with
+/// spaces are quoted
+fn paths_starting_with(search: &str, dirs_only: bool, span: Span) -> Vec<Suggestion> {
+    let search = search.trim_start_matches(['"', '\'']);
+    let (dir, prefix) = match search.rfind('/') {
+        Some(index) => (&search[..=index], &search[index + 1..]),
+        None => ("", search),
+    };
+    let Ok(entries) = std::fs::read_dir(expand_home(if dir.is_empty() { "." } else { dir })) else {
+        return vec![];
+    };
+    let mut completions: Vec<Suggestion> = entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().to_string();
+            let is_dir = entry.path().is_dir();
+            if !name.starts_with(prefix)
+                || (name.starts_with('.') && !prefix.starts_with('.'))
+                || (dirs_only && !is_dir)
+            {
+                return None;
+            }
+            let mut value = format!("{}{}", dir, name);
+            if is_dir {
+                value.push('/');
+            }
+            if value.contains(' ') {
+                value = format!("\"{}\"", value);
+            }
+            Some(Suggestion {
+                value,
+                description: None,
+                extra: None,
+                span,
+                append_whitespace: !is_dir,
+            })
+        })
+        .collect();
+    completions.sort_by(|a, b| a.value.cmp(&b.value));
+    completions
+}
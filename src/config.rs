@@ -0,0 +1,238 @@
+use crate::error::*;
+use crate::expand::expand_home;
+use crate::pager::PagerMode;
+use crate::theme::Theme;
+use nu_ansi_term::Color;
+use reedline::{KeyCode, KeyModifiers};
+use std::path::{Path, PathBuf};
+use toml::{Table, Value};
+
+/// Number of history entries kept if the config file sets a history file but no size
+const DEFAULT_HISTORY_SIZE: usize = 1000;
+
+/// Settings read from a config file set with
+/// [Repl::with_config_file](struct.Repl.html#method.with_config_file)
+#[derive(Default)]
+pub(crate) struct Config {
+    pub(crate) prompt: Option<String>,
+    pub(crate) history: Option<(PathBuf, usize)>,
+    pub(crate) pager: Option<Option<PagerMode>>,
+    pub(crate) color: Option<bool>,
+    pub(crate) timing: Option<bool>,
+    pub(crate) confirm: Option<bool>,
+    theme: Vec<(String, Color)>,
+    /// Key combinations executing a command line
+    pub(crate) keybindings: Vec<(KeyModifiers, KeyCode, String)>,
+}
+
+impl Config {
+    /// Reads the config file at `path`, which may start with `~`, `None` if there is none
+    pub(crate) fn load(path: &Path) -> Result<Option<Config>> {
+        let path = expand_home(&path.to_string_lossy());
+        let text = match std::fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err.into()),
+        };
+        let invalid = |message: String| Error::InvalidConfig(path.display().to_string(), message);
+        let table: Table = text
+            .parse()
+            .map_err(|err: toml::de::Error| invalid(err.to_string()))?;
+        Config::from_table(table).map(Some).map_err(invalid)
+    }
+
+    fn from_table(table: Table) -> std::result::Result<Config, String> {
+        let mut config = Config::default();
+        let mut history_size = None;
+        let mut history = None;
+        for (key, value) in table {
+            match key.as_str() {
+                "prompt" => config.prompt = Some(string(&key, value)?),
+                "history" => history = Some(expand_home(&string(&key, value)?)),
+                "history_size" => match value {
+                    Value::Integer(size) if size > 0 => history_size = Some(size as usize),
+                    _ => return Err("'history_size' must be a positive number".to_string()),
+                },
+                "pager" => {
+                    config.pager = Some(match string(&key, value)?.as_str() {
+                        "off" => None,
+                        "internal" => Some(PagerMode::Internal),
+                        program => Some(PagerMode::External(program.to_string())),
+                    })
+                }
+                "color" => config.color = Some(boolean(&key, value)?),
+                "timing" => config.timing = Some(boolean(&key, value)?),
+                "confirm" => config.confirm = Some(boolean(&key, value)?),
+                "theme" => {
+                    for (field, color) in table_of(&key, value)? {
+                        if !THEME_FIELDS.contains(&field.as_str()) {
+                            return Err(format!(
+                                "unknown theme entry '{}', use one of {}",
+                                field,
+                                THEME_FIELDS.join(", ")
+                            ));
+                        }
+                        let color = parse_color(&string(&field, color)?)?;
+                        config.theme.push((field, color));
+                    }
+                }
+                "keybindings" => {
+                    for (keys, command) in table_of(&key, value)? {
+                        let (modifiers, key_code) = parse_keys(&keys)?;
+                        config
+                            .keybindings
+                            .push((modifiers, key_code, string(&keys, command)?));
+                    }
+                }
+                _ => return Err(format!("unknown setting '{}'", key)),
+            }
+        }
+        config.history = history.map(|path| (path, history_size.unwrap_or(DEFAULT_HISTORY_SIZE)));
+        Ok(config)
+    }
+
+    /// `theme` with the colors of the config file
+    pub(crate) fn theme(&self, mut theme: Theme) -> Theme {
+        for (field, color) in &self.theme {
+            match field.as_str() {
+                "prompt" => theme.prompt_style = theme.prompt_style.fg(*color),
+                "hint" => theme.hint_style = theme.hint_style.fg(*color),
+                "command" => theme.command_color = *color,
+                "unknown_command" => theme.unknown_command_color = *color,
+                "argument" => theme.argument_color = *color,
+                "flag" => theme.flag_color = *color,
+                "string" => theme.string_color = *color,
+                "error" => theme.error_style = theme.error_style.fg(*color),
+                "warning" => theme.warning_style = theme.warning_style.fg(*color),
+                "help_heading" => theme.help_heading_style = theme.help_heading_style.fg(*color),
+                "status_success" => {
+                    theme.status_success_style = theme.status_success_style.fg(*color)
+                }
+                "status_failure" => {
+                    theme.status_failure_style = theme.status_failure_style.fg(*color)
+                }
+                "menu" => theme.menu_style = theme.menu_style.fg(*color),
+                "menu_selected" => theme.menu_selected_style = theme.menu_selected_style.on(*color),
+                "menu_description" => {
+                    theme.menu_description_style = theme.menu_description_style.fg(*color)
+                }
+                _ => {}
+            }
+        }
+        theme
+    }
+}
+
+/// Entries of the `[theme]` table
+const THEME_FIELDS: &[&str] = &[
+    "prompt",
+    "hint",
+    "command",
+    "unknown_command",
+    "argument",
+    "flag",
+    "string",
+    "error",
+    "warning",
+    "help_heading",
+    "status_success",
+    "status_failure",
+    "menu",
+    "menu_selected",
+    "menu_description",
+];
+
+fn string(key: &str, value: Value) -> std::result::Result<String, String> {
+    match value {
+        Value::String(text) => Ok(text),
+        _ => Err(format!("'{}' must be a string", key)),
+    }
+}
+
+fn boolean(key: &str, value: Value) -> std::result::Result<bool, String> {
+    match value {
+        Value::Boolean(value) => Ok(value),
+        _ => Err(format!("'{}' must be true or false", key)),
+    }
+}
+
+fn table_of(key: &str, value: Value) -> std::result::Result<Table, String> {
+    match value {
+        Value::Table(table) => Ok(table),
+        _ => Err(format!("'{}' must be a table", key)),
+    }
+}
+
+/// Parses a color name like `light_blue` or `#rrggbb`
+fn parse_color(name: &str) -> std::result::Result<Color, String> {
+    let color = match name {
+        "default" => Color::Default,
+        "black" => Color::Black,
+        "red" => Color::Red,
+        "green" => Color::Green,
+        "yellow" => Color::Yellow,
+        "blue" => Color::Blue,
+        "purple" => Color::Purple,
+        "magenta" => Color::Magenta,
+        "cyan" => Color::Cyan,
+        "white" => Color::White,
+        "dark_gray" => Color::DarkGray,
+        "light_red" => Color::LightRed,
+        "light_green" => Color::LightGreen,
+        "light_yellow" => Color::LightYellow,
+        "light_blue" => Color::LightBlue,
+        "light_purple" => Color::LightPurple,
+        "light_magenta" => Color::LightMagenta,
+        "light_cyan" => Color::LightCyan,
+        "light_gray" => Color::LightGray,
+        _ => {
+            let hex = name
+                .strip_prefix('#')
+                .filter(|hex| hex.len() == 6)
+                .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                .ok_or_else(|| format!("unknown color '{}'", name))?;
+            Color::Rgb((hex >> 16) as u8, (hex >> 8) as u8, hex as u8)
+        }
+    };
+    Ok(color)
+}
+
+/// Parses a key combination like `ctrl-s`, `alt-shift-x` or `f5`
+fn parse_keys(keys: &str) -> std::result::Result<(KeyModifiers, KeyCode), String> {
+    let invalid = || format!("unknown key combination '{}'", keys);
+    let (modifier_names, key) = match keys.rsplit_once('-') {
+        Some((modifiers, "")) => (modifiers.strip_suffix('-').unwrap_or(modifiers), "-"),
+        Some((modifiers, key)) => (modifiers, key),
+        None => ("", keys),
+    };
+    let mut modifiers = KeyModifiers::NONE;
+    for name in modifier_names.split('-').filter(|name| !name.is_empty()) {
+        modifiers |= match name {
+            "ctrl" => KeyModifiers::CONTROL,
+            "alt" => KeyModifiers::ALT,
+            "shift" => KeyModifiers::SHIFT,
+            _ => return Err(invalid()),
+        };
+    }
+    let key_code = match key {
+        "enter" => KeyCode::Enter,
+        "tab" => KeyCode::Tab,
+        "esc" => KeyCode::Esc,
+        "backspace" => KeyCode::Backspace,
+        "delete" => KeyCode::Delete,
+        "up" => KeyCode::Up,
+        "down" => KeyCode::Down,
+        "left" => KeyCode::Left,
+        "right" => KeyCode::Right,
+        "home" => KeyCode::Home,
+        "end" => KeyCode::End,
+        "pageup" => KeyCode::PageUp,
+        "pagedown" => KeyCode::PageDown,
+        _ => match (key.strip_prefix('f').map(str::parse), key.chars().count()) {
+            (Some(Ok(number)), _) if (1..=12).contains(&number) => KeyCode::F(number),
+            (_, 1) => KeyCode::Char(key.chars().next().unwrap_or_default()),
+            _ => return Err(invalid()),
+        },
+    };
+    Ok((modifiers, key_code))
+}
@@ -0,0 +1,209 @@
+use crate::error::*;
+use crate::parsers::parse_bool;
+use clap::ArgMatches;
+use serde::de::{
+    self, DeserializeOwned, DeserializeSeed, Deserializer, IntoDeserializer, MapAccess, SeqAccess,
+    Visitor,
+};
+use serde::forward_to_deserialize_any;
+use std::fmt;
+use std::vec::IntoIter;
+
+/// Deserializes the arguments of a command into a struct whose fields are named like the
+/// arguments, e.g. `let args: AddArgs = args.deserialize()?`. Values are converted from the
+/// words typed, optional arguments go into `Option` fields and arguments taking several
+/// values into `Vec` fields, with `#[serde(default)]` if they may be left out.
+pub trait DeserializeArgs {
+    fn deserialize<T: DeserializeOwned>(&self) -> Result<T>;
+}
+
+impl DeserializeArgs for ArgMatches {
+    fn deserialize<T: DeserializeOwned>(&self) -> Result<T> {
+        T::deserialize(MatchesDeserializer(self)).map_err(|error| Error::InvalidArguments(error.0))
+    }
+}
+
+type DeResult<T> = std::result::Result<T, DeError>;
+
+#[derive(Debug)]
+struct DeError(String);
+
+impl fmt::Display for DeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for DeError {}
+
+impl de::Error for DeError {
+    fn custom<T: fmt::Display>(message: T) -> Self {
+        DeError(message.to_string())
+    }
+}
+
+/// The whole argument map, as a map from argument ids to their raw values
+struct MatchesDeserializer<'a>(&'a ArgMatches);
+
+impl<'de> Deserializer<'de> for MatchesDeserializer<'_> {
+    type Error = DeError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> DeResult<V::Value> {
+        let entries: Vec<(String, Vec<String>)> = self
+            .0
+            .ids()
+            .filter_map(|id| {
+                let values = self.0.try_get_raw(id.as_str()).ok()??;
+                let values = values
+                    .map(|value| value.to_string_lossy().to_string())
+                    .collect();
+                Some((id.to_string(), values))
+            })
+            .collect();
+        visitor.visit_map(ArgsMap {
+            entries: entries.into_iter(),
+            values: None,
+        })
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes byte_buf
+        option unit unit_struct newtype_struct seq tuple tuple_struct map struct enum
+        identifier ignored_any
+    }
+}
+
+struct ArgsMap {
+    entries: IntoIter<(String, Vec<String>)>,
+    values: Option<Vec<String>>,
+}
+
+impl<'de> MapAccess<'de> for ArgsMap {
+    type Error = DeError;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(&mut self, seed: K) -> DeResult<Option<K::Value>> {
+        match self.entries.next() {
+            Some((id, values)) => {
+                self.values = Some(values);
+                seed.deserialize(id.into_deserializer()).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> DeResult<V::Value> {
+        let values = self.values.take().unwrap_or_default();
+        seed.deserialize(ValuesDeserializer(values))
+    }
+}
+
+/// The raw values of one argument, parsed into the type of the field
+struct ValuesDeserializer(Vec<String>);
+
+impl ValuesDeserializer {
+    fn single(&self) -> DeResult<&str> {
+        match self.0.as_slice() {
+            [value] => Ok(value),
+            values => Err(DeError(format!("expected one value, got {}", values.len()))),
+        }
+    }
+
+    fn parse<T: std::str::FromStr>(&self, kind: &str) -> DeResult<T> {
+        let value = self.single()?;
+        value
+            .parse()
+            .map_err(|_| DeError(format!("invalid {} '{}'", kind, value)))
+    }
+}
+
+macro_rules! deserialize_parsed {
+    ($($method:ident => $visit:ident: $kind:literal,)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> DeResult<V::Value> {
+                visitor.$visit(self.parse($kind)?)
+            }
+        )*
+    };
+}
+
+impl<'de> Deserializer<'de> for ValuesDeserializer {
+    type Error = DeError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> DeResult<V::Value> {
+        match self.0.len() {
+            1 => visitor.visit_string(self.single()?.to_string()),
+            _ => self.deserialize_seq(visitor),
+        }
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> DeResult<V::Value> {
+        let value = parse_bool(self.single()?).map_err(|error| DeError(error.to_string()))?;
+        visitor.visit_bool(value)
+    }
+
+    deserialize_parsed! {
+        deserialize_i8 => visit_i8: "integer",
+        deserialize_i16 => visit_i16: "integer",
+        deserialize_i32 => visit_i32: "integer",
+        deserialize_i64 => visit_i64: "integer",
+        deserialize_i128 => visit_i128: "integer",
+        deserialize_u8 => visit_u8: "integer",
+        deserialize_u16 => visit_u16: "integer",
+        deserialize_u32 => visit_u32: "integer",
+        deserialize_u64 => visit_u64: "integer",
+        deserialize_u128 => visit_u128: "integer",
+        deserialize_f32 => visit_f32: "number",
+        deserialize_f64 => visit_f64: "number",
+        deserialize_char => visit_char: "character",
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> DeResult<V::Value> {
+        if self.0.is_empty() {
+            visitor.visit_none()
+        } else {
+            visitor.visit_some(self)
+        }
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> DeResult<V::Value> {
+        visitor.visit_seq(ValuesSeq(self.0.into_iter()))
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> DeResult<V::Value> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> DeResult<V::Value> {
+        visitor.visit_enum(self.single()?.to_string().into_deserializer())
+    }
+
+    forward_to_deserialize_any! {
+        str string bytes byte_buf unit unit_struct tuple tuple_struct map struct identifier
+        ignored_any
+    }
+}
+
+struct ValuesSeq(IntoIter<String>);
+
+impl<'de> SeqAccess<'de> for ValuesSeq {
+    type Error = DeError;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> DeResult<Option<T::Value>> {
+        match self.0.next() {
+            Some(value) => seed.deserialize(ValuesDeserializer(vec![value])).map(Some),
+            None => Ok(None),
+        }
+    }
+}
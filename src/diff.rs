@@ -0,0 +1,365 @@
+use std::ops::Range;
+use yansi::Paint;
+
+/// Lines of context shown around each change
+const CONTEXT: usize = 3;
+
+#[derive(Clone, Copy)]
+enum Op {
+    Equal(usize, usize),
+    Delete(usize),
+    Insert(usize),
+}
+
+/// Renders a colored unified diff between two texts, or `None` if they are equal
+pub(crate) fn unified_diff(old_name: &str, old: &str, new_name: &str, new: &str) -> Option<String> {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+    let ops = diff_ops(&old, &new);
+    if ops.iter().all(|op| matches!(op, Op::Equal(..))) {
+        return None;
+    }
+
+    let mut lines = vec![
+        Paint::red(format!("--- {}", old_name)).bold().to_string(),
+        Paint::green(format!("+++ {}", new_name)).bold().to_string(),
+    ];
+    for hunk in hunks(&ops) {
+        let hunk = &ops[hunk.0..hunk.1];
+        let old_start = hunk.iter().find_map(|op| match op {
+            Op::Equal(i, _) | Op::Delete(i) => Some(*i),
+            Op::Insert(_) => None,
+        });
+        let new_start = hunk.iter().find_map(|op| match op {
+            Op::Equal(_, j) | Op::Insert(j) => Some(*j),
+            Op::Delete(_) => None,
+        });
+        let old_len = hunk
+            .iter()
+            .filter(|op| !matches!(op, Op::Insert(_)))
+            .count();
+        let new_len = hunk
+            .iter()
+            .filter(|op| !matches!(op, Op::Delete(_)))
+            .count();
+        lines.push(
+            Paint::cyan(format!(
+                "@@ -{},{} +{},{} @@",
+                old_start.map_or(0, |i| i + 1),
+                old_len,
+                new_start.map_or(0, |j| j + 1),
+                new_len
+            ))
+            .to_string(),
+        );
+        for op in hunk {
+            lines.push(match *op {
+                Op::Equal(i, _) => format!(" {}", old[i]),
+                Op::Delete(i) => Paint::red(format!("-{}", old[i])).to_string(),
+                Op::Insert(j) => Paint::green(format!("+{}", new[j])).to_string(),
+            });
+        }
+    }
+
+    Some(lines.join("\n"))
+}
+
+/// Computes the shortest edit script between two lists of lines with the linear space
+/// variant of Myers' algorithm, so large outputs take O(N + M) memory
+fn diff_ops(old: &[&str], new: &[&str]) -> Vec<Op> {
+    let max_d = (old.len() + new.len()).div_ceil(2) + 1;
+    let mut forward = Diagonals::new(max_d);
+    let mut backward = Diagonals::new(max_d);
+    let mut ops = Vec::with_capacity(old.len().max(new.len()));
+    conquer(
+        old,
+        0..old.len(),
+        new,
+        0..new.len(),
+        &mut forward,
+        &mut backward,
+        &mut ops,
+    );
+    // list the deletions of a change before its insertions, like diff does
+    for change in ops.split_mut(|op| matches!(op, Op::Equal(..))) {
+        change.sort_by_key(|op| matches!(op, Op::Insert(_)));
+    }
+    ops
+}
+
+/// Furthest reaching x per diagonal `k = x - y`, indexed from `-max_d` to `max_d`
+struct Diagonals {
+    x: Vec<usize>,
+    offset: isize,
+}
+
+impl Diagonals {
+    fn new(max_d: usize) -> Self {
+        Diagonals {
+            x: vec![0; 2 * max_d],
+            offset: max_d as isize,
+        }
+    }
+}
+
+impl std::ops::Index<isize> for Diagonals {
+    type Output = usize;
+
+    fn index(&self, k: isize) -> &usize {
+        &self.x[(k + self.offset) as usize]
+    }
+}
+
+impl std::ops::IndexMut<isize> for Diagonals {
+    fn index_mut(&mut self, k: isize) -> &mut usize {
+        &mut self.x[(k + self.offset) as usize]
+    }
+}
+
+/// Strips the common prefix and suffix of the ranges and diffs the rest by splitting it
+/// at the middle snake of its shortest edit script
+fn conquer(
+    old: &[&str],
+    mut old_range: Range<usize>,
+    new: &[&str],
+    mut new_range: Range<usize>,
+    forward: &mut Diagonals,
+    backward: &mut Diagonals,
+    ops: &mut Vec<Op>,
+) {
+    while !old_range.is_empty()
+        && !new_range.is_empty()
+        && old[old_range.start] == new[new_range.start]
+    {
+        ops.push(Op::Equal(old_range.start, new_range.start));
+        old_range.start += 1;
+        new_range.start += 1;
+    }
+    let (old_end, new_end) = (old_range.end, new_range.end);
+    while !old_range.is_empty()
+        && !new_range.is_empty()
+        && old[old_range.end - 1] == new[new_range.end - 1]
+    {
+        old_range.end -= 1;
+        new_range.end -= 1;
+    }
+
+    if old_range.is_empty() {
+        ops.extend(new_range.clone().map(Op::Insert));
+    } else if new_range.is_empty() {
+        ops.extend(old_range.clone().map(Op::Delete));
+    } else {
+        let (x, y) = middle_snake(old, &old_range, new, &new_range, forward, backward);
+        conquer(
+            old,
+            old_range.start..x,
+            new,
+            new_range.start..y,
+            forward,
+            backward,
+            ops,
+        );
+        conquer(
+            old,
+            x..old_range.end,
+            new,
+            y..new_range.end,
+            forward,
+            backward,
+            ops,
+        );
+    }
+    ops.extend(
+        (old_range.end..old_end)
+            .zip(new_range.end..new_end)
+            .map(|(i, j)| Op::Equal(i, j)),
+    );
+}
+
+/// Searches the shortest edit script from both ends at once and returns a point on it
+/// where the searches meet, splitting the ranges into two smaller problems
+fn middle_snake(
+    old: &[&str],
+    old_range: &Range<usize>,
+    new: &[&str],
+    new_range: &Range<usize>,
+    forward: &mut Diagonals,
+    backward: &mut Diagonals,
+) -> (usize, usize) {
+    let (n, m) = (old_range.len(), new_range.len());
+    let old = &old[old_range.clone()];
+    let new = &new[new_range.clone()];
+    let delta = n as isize - m as isize;
+    let odd = delta % 2 != 0;
+    forward[1] = 0;
+    backward[1] = 0;
+    for d in 0..=((n + m).div_ceil(2) as isize) {
+        for k in (-d..=d).rev().step_by(2) {
+            let mut x = if k == -d || (k != d && forward[k - 1] < forward[k + 1]) {
+                forward[k + 1]
+            } else {
+                forward[k - 1] + 1
+            };
+            // negative on diagonals outside the grid, which wrap and fail the bounds check
+            let y = (x as isize - k) as usize;
+            let (start_x, start_y) = (x, y);
+            if x < n && y < m {
+                x += old[x..]
+                    .iter()
+                    .zip(&new[y..])
+                    .take_while(|(a, b)| a == b)
+                    .count();
+            }
+            forward[k] = x;
+            if odd && (k - delta).abs() < d && forward[k] + backward[delta - k] >= n {
+                return (old_range.start + start_x, new_range.start + start_y);
+            }
+        }
+        for k in (-d..=d).rev().step_by(2) {
+            let mut x = if k == -d || (k != d && backward[k - 1] < backward[k + 1]) {
+                backward[k + 1]
+            } else {
+                backward[k - 1] + 1
+            };
+            let mut y = (x as isize - k) as usize;
+            if x < n && y < m {
+                let common = old[..n - x]
+                    .iter()
+                    .rev()
+                    .zip(new[..m - y].iter().rev())
+                    .take_while(|(a, b)| a == b)
+                    .count();
+                x += common;
+                y += common;
+            }
+            backward[k] = x;
+            if !odd && (k - delta).abs() <= d && backward[k] + forward[delta - k] >= n {
+                return (old_range.start + n - x, new_range.start + m - y);
+            }
+        }
+    }
+    unreachable!("the searches meet after at most (n + m) / 2 edits")
+}
+
+/// Groups changes with their surrounding context into `(start, end)` ranges of `ops`
+fn hunks(ops: &[Op]) -> Vec<(usize, usize)> {
+    let mut hunks: Vec<(usize, usize)> = vec![];
+    for (index, op) in ops.iter().enumerate() {
+        if matches!(op, Op::Equal(..)) {
+            continue;
+        }
+        let start = index.saturating_sub(CONTEXT);
+        let end = (index + CONTEXT + 1).min(ops.len());
+        match hunks.last_mut() {
+            Some(last) if start <= last.1 => last.1 = end,
+            _ => hunks.push((start, end)),
+        }
+    }
+    hunks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transcript::strip_ansi;
+
+    fn diff(old: &str, new: &str) -> Option<String> {
+        unified_diff("old", old, "new", new).map(|diff| strip_ansi(&diff))
+    }
+
+    #[test]
+    fn equal_texts_have_no_diff() {
+        assert_eq!(diff("a\nb", "a\nb"), None);
+        assert_eq!(diff("", ""), None);
+    }
+
+    #[test]
+    fn changed_line() {
+        assert_eq!(
+            diff("a\nb\nc", "a\nB\nc").unwrap(),
+            "--- old\n+++ new\n@@ -1,3 +1,3 @@\n a\n-b\n+B\n c"
+        );
+    }
+
+    #[test]
+    fn distant_changes_get_separate_hunks() {
+        let old: Vec<String> = (1..=20).map(|n| n.to_string()).collect();
+        let mut new = old.clone();
+        new[1] = "two".to_string();
+        new[17] = "eighteen".to_string();
+        let diff = diff(&old.join("\n"), &new.join("\n")).unwrap();
+        let headers: Vec<&str> = diff.lines().filter(|line| line.starts_with("@@")).collect();
+        assert_eq!(headers, ["@@ -1,5 +1,5 @@", "@@ -15,6 +15,6 @@"]);
+    }
+
+    #[test]
+    fn insertion_into_empty_text() {
+        assert_eq!(
+            diff("", "a").unwrap(),
+            "--- old\n+++ new\n@@ -0,0 +1,1 @@\n+a"
+        );
+    }
+
+    /// Length of the longest common subsequence, the quadratic way
+    fn lcs_len(old: &[&str], new: &[&str]) -> usize {
+        let mut lcs = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+        for i in (0..old.len()).rev() {
+            for j in (0..new.len()).rev() {
+                lcs[i][j] = if old[i] == new[j] {
+                    lcs[i + 1][j + 1] + 1
+                } else {
+                    lcs[i + 1][j].max(lcs[i][j + 1])
+                };
+            }
+        }
+        lcs[0][0]
+    }
+
+    #[test]
+    fn edit_scripts_are_shortest_and_complete() {
+        let mut seed = 0x2545_f491_u32;
+        let mut random = move |limit: u32| {
+            seed ^= seed << 13;
+            seed ^= seed >> 17;
+            seed ^= seed << 5;
+            seed % limit
+        };
+        let words = ["a", "b", "c"];
+        for _ in 0..500 {
+            let old: Vec<&str> = (0..random(12)).map(|_| words[random(3) as usize]).collect();
+            let new: Vec<&str> = (0..random(12)).map(|_| words[random(3) as usize]).collect();
+            let ops = diff_ops(&old, &new);
+            let (mut i, mut j) = (0, 0);
+            for op in &ops {
+                match *op {
+                    Op::Equal(a, b) => {
+                        assert_eq!((a, b), (i, j));
+                        assert_eq!(old[a], new[b]);
+                        i += 1;
+                        j += 1;
+                    }
+                    Op::Delete(a) => {
+                        assert_eq!(a, i);
+                        i += 1;
+                    }
+                    Op::Insert(b) => {
+                        assert_eq!(b, j);
+                        j += 1;
+                    }
+                }
+            }
+            assert_eq!((i, j), (old.len(), new.len()), "{:?} {:?}", old, new);
+            let equal = ops.iter().filter(|op| matches!(op, Op::Equal(..))).count();
+            assert_eq!(equal, lcs_len(&old, &new), "{:?} {:?}", old, new);
+        }
+    }
+
+    #[test]
+    fn large_texts_with_few_changes() {
+        let old: Vec<String> = (0..200_000).map(|n| n.to_string()).collect();
+        let mut new = old.clone();
+        new[100_000] = "changed".to_string();
+        let diff = diff(&old.join("\n"), &new.join("\n")).unwrap();
+        assert!(diff.contains("@@ -99998,7 +99998,7 @@"), "{}", diff);
+    }
+}
@@ -0,0 +1,52 @@
+use crate::prompt::ReplPrompt;
+use crate::transcript::strip_ansi;
+use reedline::{Prompt, PromptEditMode, Reedline, Signal, ValidationResult, Validator};
+use std::io::{self, Write};
+
+/// Line editing backend of `Repl::run`
+pub(crate) enum LineEditor {
+    /// reedline, with completion, highlighting, hints and history
+    Reedline(Box<Reedline>),
+    /// Lines read from stdin without editing, colors or raw mode, for dumb terminals,
+    /// continued while the validator finds them incomplete
+    Plain(Box<dyn Validator>),
+}
+
+impl LineEditor {
+    /// Reads the next line, continued over several lines while it is incomplete
+    pub(crate) fn read_line(&mut self, prompt: &ReplPrompt) -> io::Result<Signal> {
+        match self {
+            LineEditor::Reedline(line_editor) => line_editor.read_line(prompt),
+            LineEditor::Plain(validator) => read_plain_line(prompt, validator.as_ref()),
+        }
+    }
+}
+
+/// Prints the prompt without colors and reads a line from stdin, the end of the input
+/// counts as Ctrl-D
+fn read_plain_line(prompt: &ReplPrompt, validator: &dyn Validator) -> io::Result<Signal> {
+    let mut stdout = io::stdout();
+    let mut text = format!(
+        "{}{}",
+        prompt.render_prompt_left(),
+        prompt.render_prompt_indicator(PromptEditMode::Default)
+    );
+    let mut line = String::new();
+    loop {
+        write!(stdout, "{}", strip_ansi(&text))?;
+        stdout.flush()?;
+        let mut input = String::new();
+        if io::stdin().read_line(&mut input)? == 0 {
+            if line.is_empty() {
+                return Ok(Signal::CtrlD);
+            }
+            return Ok(Signal::Success(line));
+        }
+        line.push_str(input.trim_end_matches(['\n', '\r']));
+        if matches!(validator.validate(&line), ValidationResult::Complete) {
+            return Ok(Signal::Success(line));
+        }
+        line.push('\n');
+        text = prompt.render_prompt_multiline_indicator().to_string();
+    }
+}
@@ -29,8 +29,95 @@ pub enum Error {
     /// Error parsing a float value
     ParseFloatError(num::ParseFloatError),
 
-    /// Command not found
-    UnknownCommand(String),
+    /// Command not found, with the closest known commands as suggestions
+    UnknownCommand(String, Vec<String>),
+
+    /// Invalid arithmetic expression in an argument
+    ArithmeticError(String),
+
+    /// Reading input or writing output failed
+    IoError(String),
+
+    /// Invalid regular expression
+    InvalidPattern(String),
+
+    /// No history entry matches a history expansion like `!42`
+    HistoryEntryNotFound(String),
+
+    /// The command output with the given number is not retained
+    OutputNotFound(usize),
+
+    /// Startup health checks failed
+    HealthCheckFailed(Vec<String>),
+
+    /// A quote in the input line is never closed
+    UnterminatedQuote(String),
+
+    /// No invocation with the given number is kept
+    InvocationNotFound(usize),
+
+    /// Scripts run with `source` are nested deeper than the given limit
+    SourceDepthExceeded(String, usize),
+
+    /// Invalid duration like `2s` or `500ms`
+    InvalidDuration(String),
+
+    /// Invalid boolean like `yes` or `off`
+    InvalidBool(String),
+
+    /// The arguments couldn't be deserialized into the arguments struct
+    InvalidArguments(String),
+
+    /// A value was rejected by a validator, with its message
+    InvalidValue(String),
+
+    /// A `wait-for` command didn't succeed before its timeout
+    WaitTimeout(String),
+
+    /// No background job with the given number exists
+    JobNotFound(usize),
+
+    /// The command can't run as a background job
+    BackgroundUnsupported(String),
+
+    /// The background job with the given number panicked
+    JobPanicked(usize),
+
+    /// A command, alias or built-in with the given name already exists
+    DuplicateCommand(String),
+
+    /// The command prefix matches several commands, which are listed
+    AmbiguousCommand(String, Vec<String>),
+
+    /// A `|` or `>` isn't followed by a program or file
+    MissingRedirectTarget(String),
+
+    /// The context couldn't be saved to the given path, with the reason
+    ContextNotSaved(String, String),
+
+    /// The config file with the given path is invalid, with the problem
+    InvalidConfig(String, String),
+
+    /// A shell command run with the shell escape failed, with its exit status
+    ShellCommandFailed(String, String),
+
+    /// The guard of the command rejected it, with the reason
+    CommandRejected(String, String),
+
+    /// The command panicked, with the panic message
+    CommandPanicked(String, String),
+
+    /// The history file with the given path can't be used, with the reason
+    HistoryUnavailable(String, String),
+
+    /// An `if` or `repeat` block of a script is invalid, with the problem
+    ScriptSyntax(String),
+
+    /// No context was registered with the given name, with the registered ones
+    UnknownContext(String, Vec<String>),
+
+    /// The command run with `run_command` failed, its problems have been printed
+    CommandFailed(String),
 }
 
 impl std::error::Error for Error {}
@@ -57,11 +144,136 @@ impl fmt::Display for Error {
             Error::ParseBoolError(error) => write!(f, "Error: {}", error,),
             Error::ParseFloatError(error) => write!(f, "Error: {}", error,),
             Error::ParseIntError(error) => write!(f, "Error: {}", error,),
-            Error::UnknownCommand(command) => write!(f, "Error: Unknown command '{}'", command),
+            Error::UnknownCommand(command, suggestions) => {
+                write!(f, "Error: Unknown command '{}'", command)?;
+                match suggestions.split_last() {
+                    Some((last, [])) => write!(f, ". Did you mean '{}'?", last),
+                    Some((last, rest)) => {
+                        write!(f, ". Did you mean '{}' or '{}'?", rest.join("', '"), last)
+                    }
+                    None => Ok(()),
+                }
+            }
+            Error::ArithmeticError(expression) => {
+                write!(f, "Error: Invalid arithmetic expression '{}'", expression)
+            }
+            Error::IoError(error) => write!(f, "Error: {}", error),
+            Error::InvalidPattern(pattern) => write!(f, "Error: Invalid pattern '{}'", pattern),
+            Error::HistoryEntryNotFound(event) => {
+                write!(f, "Error: History entry '{}' not found", event)
+            }
+            Error::OutputNotFound(number) => write!(f, "Error: Output {} not found", number),
+            Error::HealthCheckFailed(checks) => {
+                write!(f, "Error: Health checks failed: {}", checks.join(", "))
+            }
+            Error::UnterminatedQuote(line) => write!(f, "Error: Unterminated quote in '{}'", line),
+            Error::InvocationNotFound(number) => {
+                write!(f, "Error: Invocation {} not found", number)
+            }
+            Error::SourceDepthExceeded(path, limit) => write!(
+                f,
+                "Error: Sourcing '{}' exceeds the maximum nesting depth of {}",
+                path, limit
+            ),
+            Error::InvalidDuration(duration) => write!(f, "Error: Invalid duration '{}'", duration),
+            Error::InvalidBool(value) => write!(f, "Error: Invalid boolean '{}'", value),
+            Error::InvalidArguments(error) => write!(f, "Error: Invalid arguments: {}", error),
+            Error::InvalidValue(message) => write!(f, "{}", message),
+            Error::MissingRedirectTarget(line) => {
+                write!(
+                    f,
+                    "Error: Missing program or file to redirect to in '{}'",
+                    line
+                )
+            }
+            Error::ContextNotSaved(path, message) => {
+                write!(
+                    f,
+                    "Error: Failed to save the context to '{}': {}",
+                    path, message
+                )
+            }
+            Error::InvalidConfig(path, message) => {
+                write!(f, "Error: Invalid config file '{}': {}", path, message)
+            }
+            Error::ShellCommandFailed(command, status) => {
+                write!(f, "Error: Shell command '{}' failed, {}", command, status)
+            }
+            Error::CommandRejected(command, reason) => {
+                write!(f, "Error: Can't run '{}': {}", command, reason)
+            }
+            Error::CommandPanicked(command, message) => {
+                write!(f, "Error: Command '{}' panicked: {}", command, message)
+            }
+            Error::HistoryUnavailable(path, reason) => {
+                write!(
+                    f,
+                    "Error: Can't use the history file '{}': {}",
+                    path, reason
+                )
+            }
+            Error::ScriptSyntax(problem) => write!(f, "Error: Invalid block: {}", problem),
+            Error::UnknownContext(name, known) => write!(
+                f,
+                "Error: Unknown context '{}', known contexts: {}",
+                name,
+                known.join(", ")
+            ),
+            Error::CommandFailed(command) => write!(f, "Error: Command '{}' failed", command),
+            Error::WaitTimeout(command) => {
+                write!(f, "Error: Timed out waiting for '{}' to succeed", command)
+            }
+            Error::JobNotFound(id) => write!(f, "Error: Job {} not found", id),
+            Error::BackgroundUnsupported(command) => {
+                write!(
+                    f,
+                    "Error: Command '{}' can't run in the background",
+                    command
+                )
+            }
+            Error::JobPanicked(id) => write!(f, "Error: Job {} panicked", id),
+            Error::DuplicateCommand(name) => {
+                write!(f, "Error: Command '{}' already exists", name)
+            }
+            Error::AmbiguousCommand(prefix, candidates) => write!(
+                f,
+                "Error: Ambiguous command '{}', could be '{}'",
+                prefix,
+                candidates.join("', '")
+            ),
         }
     }
 }
 
+/// Error type any error converts into with `?`, for commands without an error type of their
+/// own. `anyhow::Error` and `eyre::Report` work as the error type of the REPL as well.
+#[derive(Debug)]
+pub struct ReplError(Box<dyn std::error::Error + Send + Sync>);
+
+impl ReplError {
+    /// Error with just a message
+    pub fn msg(message: impl fmt::Display) -> Self {
+        ReplError(message.to_string().into())
+    }
+
+    /// The wrapped error
+    pub fn inner(&self) -> &(dyn std::error::Error + Send + Sync + 'static) {
+        self.0.as_ref()
+    }
+}
+
+impl fmt::Display for ReplError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> std::result::Result<(), fmt::Error> {
+        self.0.fmt(f)
+    }
+}
+
+impl<T: std::error::Error + Send + Sync + 'static> From<T> for ReplError {
+    fn from(error: T) -> Self {
+        ReplError(Box::new(error))
+    }
+}
+
 impl From<num::ParseIntError> for Error {
     fn from(error: num::ParseIntError) -> Self {
         Error::ParseIntError(error)
@@ -74,6 +286,12 @@ impl From<num::ParseFloatError> for Error {
     }
 }
 
+impl From<std::io::Error> for Error {
+    fn from(error: std::io::Error) -> Self {
+        Error::IoError(error.to_string())
+    }
+}
+
 impl From<std::str::ParseBoolError> for Error {
     fn from(error: std::str::ParseBoolError) -> Self {
         Error::ParseBoolError(error)
@@ -0,0 +1,31 @@
+use std::time::Duration;
+
+/// Event of a REPL session, passed to the listener set with
+/// [Repl::with_event_listener](struct.Repl.html#method.with_event_listener) e.g. for
+/// telemetry or an audit log
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReplEvent {
+    /// The REPL started, after the on start hook
+    SessionStarted,
+    /// The REPL stopped, after the on exit hook
+    SessionEnded,
+    /// A command is about to run
+    CommandStarted {
+        /// Name of the command
+        command: String,
+    },
+    /// A command finished
+    CommandFinished {
+        /// Name of the command
+        command: String,
+        /// How long the command ran
+        duration: Duration,
+        /// Whether the command succeeded
+        ok: bool,
+    },
+    /// A line started with a word that is no known command
+    UnknownCommand {
+        /// The unknown word
+        command: String,
+    },
+}
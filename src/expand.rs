@@ -0,0 +1,245 @@
+use regex::Regex;
+use std::path::{Path, PathBuf};
+
+/// Expands `$name` and `${name}` with the values `lookup` finds, `$$` is a literal `$`.
+/// Unknown variables are kept as they are, so later expansions can still see them.
+pub(crate) fn expand_session_variables(
+    text: &str,
+    lookup: impl Fn(&str) -> Option<String>,
+) -> String {
+    let mut expanded = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '$' {
+            expanded.push(c);
+            continue;
+        }
+        if chars.next_if_eq(&'$').is_some() {
+            expanded.push(c);
+            continue;
+        }
+        let braced = chars.next_if_eq(&'{').is_some();
+        let mut name = String::new();
+        while let Some(c) = chars.next_if(|c| c.is_ascii_alphanumeric() || *c == '_') {
+            name.push(c);
+        }
+        let closed = braced && chars.next_if_eq(&'}').is_some();
+        match lookup(&name) {
+            Some(value) if !name.is_empty() && braced == closed => expanded.push_str(&value),
+            _ => {
+                expanded.push(c);
+                if braced {
+                    expanded.push('{');
+                }
+                expanded.push_str(&name);
+                if closed {
+                    expanded.push('}');
+                }
+            }
+        }
+    }
+    expanded
+}
+
+/// Expands the escapes `\n`, `\t`, `\r`, `\e` and `\\`, other backslashes are kept
+pub(crate) fn expand_escapes(text: &str) -> String {
+    let mut expanded = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            expanded.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => expanded.push('\n'),
+            Some('t') => expanded.push('\t'),
+            Some('r') => expanded.push('\r'),
+            Some('e') => expanded.push('\u{1b}'),
+            Some('\\') => expanded.push('\\'),
+            Some(other) => {
+                expanded.push(c);
+                expanded.push(other);
+            }
+            None => expanded.push(c),
+        }
+    }
+    expanded
+}
+
+/// Replaces each `{}` in `format` with the next argument, `{{` and `}}` are literal braces
+pub(crate) fn format_placeholders(format: &str, args: &[&str]) -> String {
+    let mut formatted = String::with_capacity(format.len());
+    let mut args = args.iter();
+    let mut chars = format.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.next_if_eq(&'{').is_some() => formatted.push('{'),
+            '}' if chars.next_if_eq(&'}').is_some() => formatted.push('}'),
+            '{' if chars.next_if_eq(&'}').is_some() => {
+                formatted.push_str(args.next().copied().unwrap_or_default())
+            }
+            _ => formatted.push(c),
+        }
+    }
+    formatted
+}
+
+/// Replaces a leading `~` with the home directory
+pub(crate) fn expand_home(path: &str) -> PathBuf {
+    let home = std::env::var_os("HOME").or_else(|| std::env::var_os("USERPROFILE"));
+    match (path.strip_prefix('~'), home) {
+        (Some(rest), Some(home)) => match rest.trim_start_matches('/') {
+            "" => PathBuf::from(home),
+            rest => Path::new(&home).join(rest),
+        },
+        _ => PathBuf::from(path),
+    }
+}
+
+/// Expands a leading `~` and the globs `*`, `?` and `[...]` in a path like a shell does,
+/// a pattern without matches is kept as it is
+pub(crate) fn expand_path(word: &str) -> Vec<String> {
+    let path = match word.strip_prefix('~') {
+        Some(_) => expand_home(word).to_string_lossy().to_string(),
+        None => word.to_string(),
+    };
+    if !path.contains(['*', '?', '[']) {
+        return vec![path];
+    }
+    let (mut matches, components) = match path.strip_prefix('/') {
+        Some(rest) => (vec![PathBuf::from("/")], rest),
+        None => (vec![PathBuf::new()], path.as_str()),
+    };
+    for component in components
+        .split('/')
+        .filter(|component| !component.is_empty())
+    {
+        if !component.contains(['*', '?', '[']) {
+            matches = matches
+                .into_iter()
+                .map(|dir| dir.join(component))
+                .filter(|path| path.exists())
+                .collect();
+            continue;
+        }
+        let Some(pattern) = glob_regex(component) else {
+            return vec![path];
+        };
+        matches = matches
+            .into_iter()
+            .flat_map(|dir| {
+                let entries = std::fs::read_dir(if dir.as_os_str().is_empty() {
+                    Path::new(".")
+                } else {
+                    &dir
+                });
+                entries
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|entry| entry.ok())
+                    .map(|entry| entry.file_name().to_string_lossy().to_string())
+                    .filter(|name| !name.starts_with('.') || component.starts_with('.'))
+                    .filter(|name| pattern.is_match(name))
+                    .map(|name| dir.join(name))
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+    }
+    let mut matches: Vec<String> = matches
+        .into_iter()
+        .map(|path| path.to_string_lossy().to_string())
+        .collect();
+    if matches.is_empty() {
+        return vec![path];
+    }
+    matches.sort();
+    matches
+}
+
+/// Translates one path component with globs into an anchored regex
+fn glob_regex(glob: &str) -> Option<Regex> {
+    let mut pattern = String::from("^");
+    let mut chars = glob.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' => pattern.push_str(".*"),
+            '?' => pattern.push('.'),
+            '[' => {
+                let class: String = chars.by_ref().take_while(|c| *c != ']').collect();
+                let class = match class.strip_prefix('!') {
+                    Some(negated) => format!("^{}", negated),
+                    None => class,
+                };
+                pattern.push_str(&format!("[{}]", class.replace('\\', "\\\\")));
+            }
+            c => pattern.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    pattern.push('$');
+    Regex::new(&pattern).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lookup(name: &str) -> Option<String> {
+        match name {
+            "user" => Some("ada".to_string()),
+            "empty" => Some(String::new()),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn session_variables() {
+        assert_eq!(expand_session_variables("hi $user!", lookup), "hi ada!");
+        assert_eq!(expand_session_variables("${user}s", lookup), "adas");
+        assert_eq!(expand_session_variables("[$empty]", lookup), "[]");
+        assert_eq!(expand_session_variables("cost $$5", lookup), "cost $5");
+    }
+
+    #[test]
+    fn unknown_session_variables_are_kept() {
+        assert_eq!(
+            expand_session_variables("$HOME ${other}", lookup),
+            "$HOME ${other}"
+        );
+        assert_eq!(expand_session_variables("${user", lookup), "${user");
+        assert_eq!(expand_session_variables("$ $", lookup), "$ $");
+    }
+
+    #[test]
+    fn escapes() {
+        assert_eq!(expand_escapes(r"a\tb\nc\\d\q\"), "a\tb\nc\\d\\q\\");
+        assert_eq!(expand_escapes(r"\e[1m"), "\u{1b}[1m");
+    }
+
+    #[test]
+    fn placeholders() {
+        assert_eq!(format_placeholders("{} + {} = {}", &["1", "2"]), "1 + 2 = ");
+        assert_eq!(format_placeholders("{{}} {}", &["x"]), "{} x");
+    }
+
+    #[test]
+    fn globs_match_file_names() {
+        let dir = std::env::temp_dir().join("reedline-repl-rs-expand-glob");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        for name in ["a1.txt", "a2.txt", "b.txt", ".hidden.txt"] {
+            std::fs::write(dir.join(name), "").unwrap();
+        }
+        let dir = dir.to_string_lossy();
+        let names = |pattern: &str| -> Vec<String> {
+            expand_path(&format!("{}/{}", dir, pattern))
+                .into_iter()
+                .map(|path| path.rsplit('/').next().unwrap().to_string())
+                .collect()
+        };
+        assert_eq!(names("*.txt"), ["a1.txt", "a2.txt", "b.txt"]);
+        assert_eq!(names("a?.txt"), ["a1.txt", "a2.txt"]);
+        assert_eq!(names("[!a]*"), ["b.txt"]);
+        assert_eq!(names(".*"), [".hidden.txt"]);
+        assert_eq!(names("c*"), ["c*"]);
+    }
+}
@@ -0,0 +1,67 @@
+use std::fmt;
+use yansi::Paint;
+
+/// Outcome of a single startup health check
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HealthStatus {
+    /// Check passed
+    Pass,
+    /// Check found a problem that doesn't prevent the REPL from working
+    Warn,
+    /// Check failed
+    Fail,
+}
+
+/// Result of a single startup health check, rendered beneath the banner
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HealthItem {
+    /// Name of the check, e.g. "database connection"
+    pub name: String,
+    /// Outcome of the check
+    pub status: HealthStatus,
+    /// Optional details, e.g. the reason of a failure
+    pub message: Option<String>,
+}
+
+impl HealthItem {
+    /// Create a passed check
+    pub fn pass(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            status: HealthStatus::Pass,
+            message: None,
+        }
+    }
+
+    /// Create a check with a warning
+    pub fn warn(name: &str, message: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            status: HealthStatus::Warn,
+            message: Some(message.to_string()),
+        }
+    }
+
+    /// Create a failed check
+    pub fn fail(name: &str, message: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            status: HealthStatus::Fail,
+            message: Some(message.to_string()),
+        }
+    }
+}
+
+impl fmt::Display for HealthItem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let status = match self.status {
+            HealthStatus::Pass => Paint::green("[ OK ]").bold(),
+            HealthStatus::Warn => Paint::yellow("[WARN]").bold(),
+            HealthStatus::Fail => Paint::red("[FAIL]").bold(),
+        };
+        match &self.message {
+            Some(message) => write!(f, "{} {}: {}", status, self.name, message),
+            None => write!(f, "{} {}", status, self.name),
+        }
+    }
+}
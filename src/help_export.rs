@@ -0,0 +1,185 @@
+use clap::{Arg, Command};
+use std::io::{self, Write};
+
+/// Documentation format of [Repl::export_help](struct.Repl.html#method.export_help)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HelpFormat {
+    /// Markdown with a section per command
+    Markdown,
+    /// Man page in roff
+    Man,
+}
+
+/// Writes the documentation of `app`, whose subcommands are the commands of the REPL
+pub(crate) fn export(app: &Command, format: HelpFormat, buf: &mut impl Write) -> io::Result<()> {
+    let commands = collect_commands(app, "");
+    match format {
+        HelpFormat::Markdown => markdown(app, &commands, buf),
+        HelpFormat::Man => man(app, &commands, buf),
+    }
+}
+
+/// All visible commands with their full names, mounted commands as `prefix command`
+fn collect_commands<'a>(app: &'a Command, prefix: &str) -> Vec<(String, &'a Command)> {
+    let mut commands = vec![];
+    for command in app
+        .get_subcommands()
+        .filter(|command| !command.is_hide_set())
+    {
+        let name = format!("{}{}", prefix, command.get_name());
+        commands.push((name.clone(), command));
+        commands.extend(collect_commands(command, &format!("{} ", name)));
+    }
+    commands
+}
+
+fn markdown(
+    app: &Command,
+    commands: &[(String, &Command)],
+    buf: &mut impl Write,
+) -> io::Result<()> {
+    writeln!(buf, "# {}", app.get_name())?;
+    writeln!(buf)?;
+    if let Some(about) = app.get_about() {
+        writeln!(buf, "{}", about)?;
+        writeln!(buf)?;
+    }
+    if let Some(version) = app.get_version() {
+        writeln!(buf, "Version {}", version)?;
+        writeln!(buf)?;
+    }
+    writeln!(buf, "## Commands")?;
+    for (name, command) in commands {
+        writeln!(buf)?;
+        writeln!(buf, "### `{}`", name)?;
+        writeln!(buf)?;
+        if let Some(about) = command.get_long_about().or(command.get_about()) {
+            writeln!(buf, "{}", about)?;
+            writeln!(buf)?;
+        }
+        writeln!(buf, "Usage: `{}`", usage(name, command))?;
+        let args: Vec<&Arg> = visible_args(command).collect();
+        if !args.is_empty() {
+            writeln!(buf)?;
+            for arg in args {
+                write!(buf, "- `{}`", arg_name(arg))?;
+                if let Some(help) = arg.get_help() {
+                    write!(buf, ": {}", help)?;
+                }
+                if let Some(default) = default_value(arg) {
+                    write!(buf, " (default: `{}`)", default)?;
+                }
+                writeln!(buf)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn man(app: &Command, commands: &[(String, &Command)], buf: &mut impl Write) -> io::Result<()> {
+    writeln!(
+        buf,
+        ".TH {} 1 \"\" \"{}\"",
+        roff(&app.get_name().to_uppercase()),
+        roff(app.get_version().unwrap_or_default())
+    )?;
+    writeln!(buf, ".SH NAME")?;
+    match app.get_about() {
+        Some(about) => writeln!(
+            buf,
+            "{} \\- {}",
+            roff(app.get_name()),
+            roff(&about.to_string())
+        )?,
+        None => writeln!(buf, "{}", roff(app.get_name()))?,
+    }
+    writeln!(buf, ".SH COMMANDS")?;
+    for (name, command) in commands {
+        writeln!(buf, ".SS {}", roff(name))?;
+        if let Some(about) = command.get_long_about().or(command.get_about()) {
+            writeln!(buf, "{}", roff(&about.to_string()))?;
+        }
+        writeln!(buf, ".PP")?;
+        writeln!(buf, "\\fBUsage:\\fR {}", roff(&usage(name, command)))?;
+        for arg in visible_args(command) {
+            writeln!(buf, ".TP")?;
+            writeln!(buf, "\\fB{}\\fR", roff(&arg_name(arg)))?;
+            let mut help = arg
+                .get_help()
+                .map(|help| help.to_string())
+                .unwrap_or_default();
+            if let Some(default) = default_value(arg) {
+                help = format!("{} [default: {}]", help, default)
+                    .trim()
+                    .to_string();
+            }
+            writeln!(buf, "{}", roff(&help))?;
+        }
+    }
+    Ok(())
+}
+
+fn visible_args(command: &Command) -> impl Iterator<Item = &Arg> {
+    command
+        .get_arguments()
+        .filter(|arg| !arg.is_hide_set() && !arg.is_global_set())
+}
+
+/// Usage line of a command under its full name, like `db query [OPTIONS] <sql>`
+pub(crate) fn usage(name: &str, command: &Command) -> String {
+    let mut command = command.clone().name(name.to_string());
+    let usage = command.render_usage().to_string();
+    usage.trim_start_matches("Usage: ").trim().to_string()
+}
+
+/// Name of an argument like `<file>` or `-n, --count <count>`
+fn arg_name(arg: &Arg) -> String {
+    let value = arg
+        .get_value_names()
+        .and_then(|names| names.first())
+        .map(|name| name.to_string())
+        .unwrap_or_else(|| arg.get_id().to_string());
+    if arg.is_positional() {
+        return format!("<{}>", value);
+    }
+    let mut flags = vec![];
+    if let Some(short) = arg.get_short() {
+        flags.push(format!("-{}", short));
+    }
+    if let Some(long) = arg.get_long() {
+        flags.push(format!("--{}", long));
+    }
+    let mut name = flags.join(", ");
+    if arg.get_action().takes_values() {
+        name += &format!(" <{}>", value);
+    }
+    name
+}
+
+fn default_value(arg: &Arg) -> Option<String> {
+    let defaults: Vec<String> = arg
+        .get_default_values()
+        .iter()
+        .map(|value| value.to_string_lossy().to_string())
+        .collect();
+    if defaults.is_empty() {
+        None
+    } else {
+        Some(defaults.join(", "))
+    }
+}
+
+/// Escapes text for roff, a leading `.` or `'` would start a request
+fn roff(text: &str) -> String {
+    text.lines()
+        .map(|line| {
+            let line = line.replace('\\', "\\\\").replace('-', "\\-");
+            if line.starts_with(['.', '\'']) {
+                format!("\\&{}", line)
+            } else {
+                line
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
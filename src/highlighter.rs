@@ -0,0 +1,231 @@
+use crate::theme::Theme;
+use crate::tokenizer::is_flag;
+use clap::{ArgAction, Command};
+use nu_ansi_term::{Color, Style};
+use reedline::{Highlighter, StyledText};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+/// Highlighter coloring the line with the metadata of the registered commands: known and
+/// unknown commands, flags, quoted strings and arguments the command doesn't take
+pub(crate) struct ReplHighlighter {
+    commands: HashMap<String, Command>,
+    theme: Theme,
+    shell_escape: Option<char>,
+}
+
+impl ReplHighlighter {
+    pub(crate) fn new(commands: Vec<Command>, theme: Theme) -> Self {
+        let commands = commands
+            .into_iter()
+            .flat_map(|command| {
+                std::iter::once(command.get_name().to_string())
+                    .chain(command.get_visible_aliases().map(|alias| alias.to_string()))
+                    .map(|name| (name, command.clone()))
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+        ReplHighlighter {
+            commands,
+            theme,
+            shell_escape: None,
+        }
+    }
+
+    /// Lines starting with `shell_escape` go to the system shell and are left uncolored
+    pub(crate) fn with_shell_escape(mut self, shell_escape: Option<char>) -> Self {
+        self.shell_escape = shell_escape;
+        self
+    }
+
+    /// Color of every word of the line, in order
+    fn word_colors(&self, words: &[&str]) -> Vec<Color> {
+        let mut colors = Vec::with_capacity(words.len());
+        let mut words = words.iter();
+        let mut command = match words.next() {
+            Some(name) => match self.commands.get(*name) {
+                Some(command) => {
+                    colors.push(self.theme.command_color);
+                    command
+                }
+                None => {
+                    colors.push(self.theme.unknown_command_color);
+                    colors.extend(words.map(|_| self.theme.argument_color));
+                    return colors;
+                }
+            },
+            None => return colors,
+        };
+        let mut positionals = 0;
+        let mut expects_value = false;
+        let mut options_ended = false;
+        for word in words {
+            if expects_value {
+                expects_value = false;
+                colors.push(self.value_color(word, true));
+            } else if !options_ended && *word == "--" {
+                options_ended = true;
+                colors.push(self.theme.flag_color);
+            } else if !options_ended && is_flag(word) {
+                let (flag, value) = match word.split_once('=') {
+                    Some((flag, _)) => (flag, true),
+                    None => (*word, false),
+                };
+                match find_flag(command, flag) {
+                    Some(takes_value) => {
+                        expects_value = takes_value && !value && !is_short_with_value(word);
+                        colors.push(self.theme.flag_color);
+                    }
+                    None => colors.push(self.theme.unknown_command_color),
+                }
+            } else if !options_ended && positionals == 0 && command.has_subcommands() {
+                match command.find_subcommand(word) {
+                    Some(subcommand) => {
+                        command = subcommand;
+                        colors.push(self.theme.command_color);
+                    }
+                    None => colors.push(self.theme.unknown_command_color),
+                }
+            } else {
+                let valid = positionals < max_positionals(command);
+                positionals += 1;
+                colors.push(self.value_color(word, valid));
+            }
+        }
+        colors
+    }
+
+    fn value_color(&self, word: &str, valid: bool) -> Color {
+        if !valid {
+            self.theme.unknown_command_color
+        } else if word.starts_with(['"', '\'']) {
+            self.theme.string_color
+        } else {
+            self.theme.argument_color
+        }
+    }
+}
+
+impl Highlighter for ReplHighlighter {
+    fn highlight(&self, line: &str, _cursor: usize) -> StyledText {
+        if let Some(escape) = self.shell_escape {
+            if line.trim_start().starts_with(escape) {
+                let mut styled = StyledText::new();
+                styled.push((Style::new(), line.to_string()));
+                return styled;
+            }
+        }
+        let segments = segments(line);
+        let words: Vec<&str> = segments
+            .iter()
+            .filter(|segment| !segment.trim().is_empty())
+            .copied()
+            .collect();
+        let mut colors = self.word_colors(&words).into_iter();
+        let mut styled = StyledText::new();
+        for segment in segments {
+            let style = if segment.trim().is_empty() {
+                Style::new()
+            } else {
+                Style::new().fg(colors.next().unwrap_or(self.theme.argument_color))
+            };
+            styled.push((style, segment.to_string()));
+        }
+        styled
+    }
+}
+
+/// Highlighter set with [Repl::with_highlighter](struct.Repl.html#method.with_highlighter),
+/// shared so it can be handed to the line editor again before every prompt
+#[derive(Clone)]
+pub(crate) struct SharedHighlighter(Arc<Mutex<Box<dyn Highlighter>>>);
+
+impl SharedHighlighter {
+    pub(crate) fn new(highlighter: Box<dyn Highlighter>) -> Self {
+        SharedHighlighter(Arc::new(Mutex::new(highlighter)))
+    }
+}
+
+impl Highlighter for SharedHighlighter {
+    fn highlight(&self, line: &str, cursor: usize) -> StyledText {
+        match self.0.lock() {
+            Ok(highlighter) => highlighter.highlight(line, cursor),
+            Err(_) => {
+                let mut styled = StyledText::new();
+                styled.push((Style::new(), line.to_string()));
+                styled
+            }
+        }
+    }
+}
+
+/// Splits the line into alternating runs of whitespace and words, keeping quoted strings
+/// with their whitespace in one word
+fn segments(line: &str) -> Vec<&str> {
+    let mut segments = vec![];
+    let mut start = 0;
+    let mut quote: Option<char> = None;
+    let mut in_space = None;
+    for (index, c) in line.char_indices() {
+        if let Some(open) = quote {
+            if c == open {
+                quote = None;
+            }
+            continue;
+        }
+        let space = c.is_whitespace();
+        if in_space != Some(space) {
+            if index > start {
+                segments.push(&line[start..index]);
+            }
+            start = index;
+            in_space = Some(space);
+        }
+        if c == '"' || c == '\'' {
+            quote = Some(c);
+        }
+    }
+    if start < line.len() {
+        segments.push(&line[start..]);
+    }
+    segments
+}
+
+/// Looks up a `--long` or `-s` flag of the command, returns whether it takes a value
+fn find_flag(command: &Command, flag: &str) -> Option<bool> {
+    let arg = match flag.strip_prefix("--") {
+        Some(long) => command
+            .get_arguments()
+            .find(|arg| arg.get_long() == Some(long)),
+        None => {
+            let short = flag.chars().nth(1)?;
+            command
+                .get_arguments()
+                .find(|arg| arg.get_short() == Some(short))
+        }
+    };
+    match arg {
+        Some(arg) => Some(arg.get_action().takes_values()),
+        None if flag == "--help" || flag == "-h" => Some(false),
+        None => None,
+    }
+}
+
+/// Whether a short flag carries its value in the same word, like `-n5`
+fn is_short_with_value(word: &str) -> bool {
+    !word.starts_with("--") && word.chars().count() > 2
+}
+
+/// Number of positional arguments the command takes, `usize::MAX` if the last one takes
+/// any number of values
+fn max_positionals(command: &Command) -> usize {
+    command
+        .get_positionals()
+        .map(|arg| match arg.get_num_args() {
+            Some(range) => range.max_values(),
+            None if matches!(arg.get_action(), ArgAction::Append) => usize::MAX,
+            None if arg.get_action().takes_values() => 1,
+            None => 0,
+        })
+        .fold(0, usize::saturating_add)
+}
@@ -0,0 +1,70 @@
+use nu_ansi_term::Style;
+use reedline::{Hinter, History, SearchDirection, SearchQuery};
+
+/// Hinter suggesting the most recent invocation of the command being typed, skipping
+/// history entries that don't start with one of the known commands
+pub(crate) struct CommandHinter {
+    commands: Vec<String>,
+    style: Style,
+    hint: String,
+}
+
+impl CommandHinter {
+    pub(crate) fn new(commands: Vec<String>, style: Style) -> Self {
+        Self {
+            commands,
+            style,
+            hint: String::new(),
+        }
+    }
+
+    fn is_invocation(&self, entry: &str) -> bool {
+        entry
+            .split_whitespace()
+            .next()
+            .is_some_and(|name| self.commands.iter().any(|command| command == name))
+    }
+}
+
+impl Hinter for CommandHinter {
+    fn handle(
+        &mut self,
+        line: &str,
+        _pos: usize,
+        history: &dyn History,
+        use_ansi_coloring: bool,
+    ) -> String {
+        self.hint = if line.trim().is_empty() {
+            String::new()
+        } else {
+            history
+                .search(SearchQuery::everything(SearchDirection::Backward, None))
+                .unwrap_or_default()
+                .into_iter()
+                .map(|item| item.command_line)
+                .find(|entry| {
+                    entry.len() > line.len() && entry.starts_with(line) && self.is_invocation(entry)
+                })
+                .map(|entry| entry[line.len()..].to_string())
+                .unwrap_or_default()
+        };
+        if use_ansi_coloring && !self.hint.is_empty() {
+            self.style.paint(&self.hint).to_string()
+        } else {
+            self.hint.clone()
+        }
+    }
+
+    fn complete_hint(&self) -> String {
+        self.hint.clone()
+    }
+
+    fn next_hint_token(&self) -> String {
+        let start = self.hint.len() - self.hint.trim_start().len();
+        let end = self.hint[start..]
+            .find(char::is_whitespace)
+            .map(|index| start + index)
+            .unwrap_or(self.hint.len());
+        self.hint[..end].to_string()
+    }
+}
@@ -0,0 +1,292 @@
+use crate::error::*;
+use crate::repl::{find_command, HistoryMode, Repl};
+use crate::tokenizer::split_chain;
+use reedline::{
+    FileBackedHistory, History, HistoryItem, HistoryItemId, HistorySessionId, SearchDirection,
+    SearchQuery, HISTORY_SIZE,
+};
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+/// Number of entries the `history` builtin lists without a pattern
+const HISTORY_LISTING: usize = 20;
+
+/// The history file of a REPL, shared with the line editor. Reedline doesn't add submitted
+/// lines itself, the REPL records them once it knows a line may be kept, so lines excluded
+/// from the history never reach the file.
+#[derive(Clone)]
+pub(crate) struct HistoryStore {
+    entries: Arc<Mutex<FileBackedHistory>>,
+    path: Option<PathBuf>,
+    shared: bool,
+}
+
+impl HistoryStore {
+    /// Opens the history file at `path`, creating its directory if it is missing. A shared
+    /// history is written after every recorded line, merging the lines of other sessions.
+    pub(crate) fn open(capacity: usize, path: &Path, shared: bool) -> Result<Self> {
+        let unavailable =
+            |reason: String| Error::HistoryUnavailable(path.display().to_string(), reason);
+        if let Some(directory) = path
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+        {
+            std::fs::create_dir_all(directory).map_err(|err| unavailable(err.to_string()))?;
+        }
+        let entries = FileBackedHistory::with_file(capacity, path.to_path_buf())
+            .map_err(|err| unavailable(err.to_string()))?;
+        Ok(HistoryStore {
+            entries: Arc::new(Mutex::new(entries)),
+            path: Some(path.to_path_buf()),
+            shared,
+        })
+    }
+
+    /// A history kept in memory only
+    pub(crate) fn in_memory(capacity: usize) -> Self {
+        HistoryStore {
+            entries: Arc::new(Mutex::new(FileBackedHistory::new(capacity))),
+            path: None,
+            shared: false,
+        }
+    }
+
+    fn entries(&self) -> MutexGuard<'_, FileBackedHistory> {
+        self.entries.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// All lines, oldest first
+    pub(crate) fn lines(&self) -> Vec<String> {
+        self.entries()
+            .search(SearchQuery::everything(SearchDirection::Forward, None))
+            .unwrap_or_default()
+            .into_iter()
+            .map(|item| item.command_line)
+            .collect()
+    }
+
+    /// Adds a line that ran, a shared history writes it right away
+    pub(crate) fn record(&self, line: &str) -> Result<()> {
+        let mut entries = self.entries();
+        entries
+            .save(HistoryItem::from_command_line(line))
+            .map_err(|err| Error::IoError(err.to_string()))?;
+        if self.shared {
+            entries.sync()?;
+        }
+        Ok(())
+    }
+
+    /// Removes every line, from the file too
+    pub(crate) fn clear(&self) -> Result<()> {
+        match self.entries().clear() {
+            // the file isn't written before the first line is
+            Err(_) if self.path.as_ref().is_some_and(|path| !path.exists()) => Ok(()),
+            result => result.map_err(|err| Error::IoError(err.to_string())),
+        }
+    }
+
+    /// Writes the lines not written yet to the file, under its lock
+    pub(crate) fn sync(&self) -> Result<()> {
+        self.entries().sync()?;
+        Ok(())
+    }
+
+    /// The history handed to the line editor
+    pub(crate) fn editor_history(&self) -> Box<dyn History> {
+        Box::new(EditorHistory(self.clone()))
+    }
+}
+
+/// The line editor's view of a [HistoryStore]: it searches the recorded lines, and the lines
+/// reedline saves on submit are dropped
+struct EditorHistory(HistoryStore);
+
+impl History for EditorHistory {
+    fn save(&mut self, h: HistoryItem) -> reedline::Result<HistoryItem> {
+        Ok(h)
+    }
+
+    fn load(&self, id: HistoryItemId) -> reedline::Result<HistoryItem> {
+        self.0.entries().load(id)
+    }
+
+    fn count(&self, query: SearchQuery) -> reedline::Result<i64> {
+        self.0.entries().count(query)
+    }
+
+    fn search(&self, query: SearchQuery) -> reedline::Result<Vec<HistoryItem>> {
+        self.0.entries().search(query)
+    }
+
+    fn update(
+        &mut self,
+        id: HistoryItemId,
+        updater: &dyn Fn(HistoryItem) -> HistoryItem,
+    ) -> reedline::Result<()> {
+        self.0.entries().update(id, updater)
+    }
+
+    fn clear(&mut self) -> reedline::Result<()> {
+        self.0.entries().clear()
+    }
+
+    fn delete(&mut self, h: HistoryItemId) -> reedline::Result<()> {
+        self.0.entries().delete(h)
+    }
+
+    fn sync(&mut self) -> std::io::Result<()> {
+        self.0.entries().sync()
+    }
+
+    fn session(&self) -> Option<HistorySessionId> {
+        self.0.entries().session()
+    }
+}
+
+impl<Context, E, Output> Repl<Context, E, Output>
+where
+    E: Display + From<Error> + std::fmt::Debug,
+{
+    /// Prints all history entries matching the regex `pattern` with their number for `!n`
+    pub(crate) fn search_history(&self, pattern: &str) -> Result<()> {
+        let regex =
+            regex::Regex::new(pattern).map_err(|_| Error::InvalidPattern(pattern.to_string()))?;
+        for (i, line) in self.history_lines.iter().enumerate() {
+            if regex.is_match(line) {
+                self.print_output(format!("{:>5}  {}", i + 1, line));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Prints the last entries of the history, or all entries containing `pattern`
+    pub(crate) fn show_history(&self, pattern: Option<&str>) {
+        let skip = match pattern {
+            Some(_) => 0,
+            None => self.history_lines.len().saturating_sub(HISTORY_LISTING),
+        };
+        for (i, line) in self.history_lines.iter().enumerate().skip(skip) {
+            if pattern
+                .map(|pattern| line.contains(pattern))
+                .unwrap_or(true)
+            {
+                self.print_output(format!("{:>5}  {}", i + 1, line));
+            }
+        }
+    }
+
+    /// Replaces a `!!` line with the last history entry, `!n` with the n-th history entry
+    /// and `!prefix` with the most recent entry starting with `prefix`, echoing the result.
+    /// The whole line is replaced, so an entry chaining several commands runs all of them.
+    pub(crate) fn expand_history(&self, line: String) -> Result<String> {
+        let event = match line.trim().strip_prefix('!') {
+            Some(event) if !event.is_empty() => event,
+            _ => return Ok(line),
+        };
+        let entry = match event.parse::<usize>() {
+            Ok(number) => number
+                .checked_sub(1)
+                .and_then(|index| self.history_lines.get(index)),
+            // the most recent line starting with the prefix, skipping history expansions
+            // like the line being expanded itself, `!!` matches any line
+            Err(_) => self.history_lines.iter().rev().find(|entry| {
+                (event == "!" || entry.starts_with(event)) && !entry.starts_with('!')
+            }),
+        }
+        .ok_or_else(|| Error::HistoryEntryNotFound(line.trim().to_string()))?;
+        self.print_output(entry);
+
+        Ok(entry.clone())
+    }
+
+    /// Keeps track of a submitted line the same way the history file keeps it, which skips
+    /// a line repeating the previous one and drops the oldest line when full. Returns
+    /// whether the line is added to the history.
+    pub(crate) fn record_history(&mut self, line: &str) -> Result<bool> {
+        // the lines of the file come first, e.g. lines of other sessions sharing it
+        self.history_store()?;
+        if line.trim().is_empty() || self.excluded_from_history(line) {
+            return Ok(false);
+        }
+        if self.history_lines.back().is_some_and(|last| last == line) {
+            return Ok(false);
+        }
+        if self.history_lines.len() == self.history_capacity.unwrap_or(HISTORY_SIZE) {
+            self.history_lines.pop_front();
+        }
+        self.history_lines.push_back(line.to_string());
+
+        Ok(true)
+    }
+
+    /// Writes a recorded line to the history file once it ran, unless the history filter
+    /// rejects it
+    pub(crate) fn store_history(&mut self, line: &str, success: bool) -> Result<()> {
+        // `history clear` removed the line with the others
+        if self.history_lines.back().map(String::as_str) != Some(line) {
+            return Ok(());
+        }
+        if let Some(filter) = self.history_filter {
+            if !filter(line, success) {
+                self.history_lines.pop_back();
+                return Ok(());
+            }
+        }
+        self.history_store()?.record(line)
+    }
+
+    /// The history the lines are recorded in, the history file is opened on first use
+    /// and its lines are loaded for `history` and `!n`
+    pub(crate) fn history_store(&mut self) -> Result<HistoryStore> {
+        if let Some(store) = &self.history_store {
+            return Ok(store.clone());
+        }
+        let capacity = self.history_capacity.unwrap_or(HISTORY_SIZE);
+        let store = match self.history.clone() {
+            Some(path) => match HistoryStore::open(capacity, &path, self.shared_history) {
+                Ok(store) => store,
+                Err(err) if self.history_mode == HistoryMode::Strict => return Err(err),
+                Err(err) => {
+                    self.handle_error(err.into())?;
+                    self.history = None;
+                    HistoryStore::in_memory(capacity)
+                }
+            },
+            None => HistoryStore::in_memory(capacity),
+        };
+        self.history_lines = store.lines().into();
+        self.history_store = Some(store.clone());
+        Ok(store)
+    }
+
+    /// Records an entered line in the transcript, lines kept out of the history because
+    /// they may contain secrets are only noted
+    pub(crate) fn record_input(&self, line: &str) {
+        if self.excluded_from_history(line) {
+            self.record_transcript('>', "(line not recorded)");
+        } else {
+            self.record_transcript('>', line);
+        }
+    }
+
+    /// Whether `line` must not be kept in the history: lines starting with a space and
+    /// lines running a command with sensitive arguments or excluded from the history
+    /// anywhere in their chain
+    fn excluded_from_history(&self, line: &str) -> bool {
+        if line.starts_with(' ') {
+            return true;
+        }
+        let scope = self.scope();
+        line.lines()
+            .flat_map(split_chain)
+            .filter_map(|(command, _)| command.split_whitespace().next().map(str::to_string))
+            .any(|command| {
+                find_command(&self.commands, &command, &scope).is_some_and(|definition| {
+                    !definition.history || !definition.sensitive.is_empty()
+                })
+            })
+    }
+}
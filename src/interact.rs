@@ -0,0 +1,166 @@
+use crossterm::cursor::MoveToPreviousLine;
+use crossterm::event::{read, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crossterm::terminal::{disable_raw_mode, enable_raw_mode, Clear, ClearType};
+use crossterm::{execute, queue};
+use std::io::{self, stdin, stdout, Write};
+
+/// Ask the user for a line of input from within a command callback
+///
+/// ```rust,no_run
+/// let token = reedline_repl_rs::input("token: ")?;
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub fn input(prompt: &str) -> io::Result<String> {
+    let mut stdout = stdout();
+    write!(stdout, "{}", prompt)?;
+    stdout.flush()?;
+    let mut line = String::new();
+    stdin().read_line(&mut line)?;
+    Ok(line.trim_end_matches(['\r', '\n']).to_string())
+}
+
+/// Ask a yes/no question, anything but `y` or `yes` is a no
+///
+/// ```rust,no_run
+/// if reedline_repl_rs::confirm("Delete 300 rows?")? {
+///     // delete them
+/// }
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub fn confirm(question: &str) -> io::Result<bool> {
+    let answer = input(&format!("{} [y/N] ", question))?;
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+}
+
+/// Ask for a secret like a password, typed characters are shown as `*`. Ctrl-C returns
+/// an error of kind `Interrupted`.
+pub fn password(prompt: &str) -> io::Result<String> {
+    let mut stdout = stdout();
+    write!(stdout, "{}", prompt)?;
+    stdout.flush()?;
+    let _raw = RawMode::enable()?;
+    let mut secret = String::new();
+    loop {
+        match read_key()? {
+            KeyEvent {
+                code: KeyCode::Char('c'),
+                modifiers: KeyModifiers::CONTROL,
+                ..
+            } => {
+                write!(stdout, "\r\n")?;
+                return Err(io::Error::from(io::ErrorKind::Interrupted));
+            }
+            KeyEvent {
+                code: KeyCode::Enter,
+                ..
+            } => break,
+            KeyEvent {
+                code: KeyCode::Backspace,
+                ..
+            } if !secret.is_empty() => {
+                secret.pop();
+                write!(stdout, "\u{8} \u{8}")?;
+            }
+            KeyEvent {
+                code: KeyCode::Char(c),
+                ..
+            } => {
+                secret.push(c);
+                write!(stdout, "*")?;
+            }
+            _ => {}
+        }
+        stdout.flush()?;
+    }
+    write!(stdout, "\r\n")?;
+    stdout.flush()?;
+    Ok(secret)
+}
+
+/// Let the user pick one of `items` with the arrow keys and Enter. Returns the index of
+/// the chosen item, or `None` if the selection was cancelled with Esc, `q` or Ctrl-C.
+pub fn select<T: AsRef<str>>(prompt: &str, items: &[T]) -> io::Result<Option<usize>> {
+    if items.is_empty() {
+        return Ok(None);
+    }
+    let mut stdout = stdout();
+    writeln!(stdout, "{}", prompt)?;
+    let _raw = RawMode::enable()?;
+    let mut selected = 0;
+    draw_items(&mut stdout, items, selected)?;
+    let choice = loop {
+        match read_key()? {
+            KeyEvent {
+                code: KeyCode::Up | KeyCode::Char('k'),
+                ..
+            } => selected = selected.checked_sub(1).unwrap_or(items.len() - 1),
+            KeyEvent {
+                code: KeyCode::Down | KeyCode::Char('j'),
+                ..
+            } => selected = (selected + 1) % items.len(),
+            KeyEvent {
+                code: KeyCode::Enter,
+                ..
+            } => break Some(selected),
+            KeyEvent {
+                code: KeyCode::Char('c'),
+                modifiers: KeyModifiers::CONTROL,
+                ..
+            }
+            | KeyEvent {
+                code: KeyCode::Esc | KeyCode::Char('q'),
+                ..
+            } => break None,
+            _ => continue,
+        }
+        queue!(stdout, MoveToPreviousLine(items.len() as u16))?;
+        draw_items(&mut stdout, items, selected)?;
+    };
+    // remove the list, the prompt stays
+    execute!(
+        stdout,
+        MoveToPreviousLine(items.len() as u16),
+        Clear(ClearType::FromCursorDown)
+    )?;
+    Ok(choice)
+}
+
+fn draw_items<T: AsRef<str>>(
+    stdout: &mut impl Write,
+    items: &[T],
+    selected: usize,
+) -> io::Result<()> {
+    for (index, item) in items.iter().enumerate() {
+        let marker = if index == selected { ">" } else { " " };
+        queue!(stdout, Clear(ClearType::CurrentLine))?;
+        write!(stdout, "{} {}\r\n", marker, item.as_ref())?;
+    }
+    stdout.flush()
+}
+
+/// Reads the next key press, ignoring other events and key releases
+fn read_key() -> io::Result<KeyEvent> {
+    loop {
+        if let Event::Key(key) = read()? {
+            if key.kind != KeyEventKind::Release {
+                return Ok(key);
+            }
+        }
+    }
+}
+
+/// Keeps the terminal in raw mode until dropped
+struct RawMode;
+
+impl RawMode {
+    fn enable() -> io::Result<Self> {
+        enable_raw_mode()?;
+        Ok(Self)
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        let _ = disable_raw_mode();
+    }
+}
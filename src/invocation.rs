@@ -0,0 +1,15 @@
+/// A command executed by the REPL, kept in the session so it can be inspected and run again
+/// with [Repl::rerun](struct.Repl.html#method.rerun)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Invocation {
+    /// Number of the invocation in this session, starting at 1
+    pub number: usize,
+    /// Command line after history expansion
+    pub line: String,
+    /// Name of the command
+    pub command: String,
+    /// Parsed arguments of the command
+    pub args: Vec<String>,
+    /// Whether the command succeeded
+    pub success: bool,
+}
@@ -0,0 +1,254 @@
+use crate::error::Error;
+use crate::repl::{find_command, Repl};
+#[cfg(feature = "async")]
+use crate::timer::delay;
+use crate::Callback;
+use clap::ArgMatches;
+use std::fmt::Display;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// How often `fg` checks whether its job finished or the wait was interrupted
+const JOB_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Called on the job's thread when its command finished
+pub(crate) type Notifier = Box<dyn FnOnce() + Send>;
+
+/// Starts a command callback on a background thread, instantiated by
+/// `Repl::with_background_jobs` where the context can be cloned and sent to it
+pub(crate) type Spawner<Context, E, Output> = fn(
+    Callback<Context, E, Output>,
+    ArgMatches,
+    &Context,
+    Option<Notifier>,
+) -> JoinHandle<Result<Option<Output>, E>>;
+
+/// Runs `callback` with a clone of the context on a new thread
+pub(crate) fn spawn<Context, E, Output>(
+    callback: Callback<Context, E, Output>,
+    matches: ArgMatches,
+    context: &Context,
+    notify: Option<Notifier>,
+) -> JoinHandle<Result<Option<Output>, E>>
+where
+    Context: Clone + Send + 'static,
+    E: Send + 'static,
+    Output: Send + 'static,
+{
+    let mut context = context.clone();
+    thread::spawn(move || {
+        let result = callback(matches, &mut context);
+        if let Some(notify) = notify {
+            notify();
+        }
+        result
+    })
+}
+
+/// A command started with a trailing `&`
+pub(crate) struct Job<E, Output> {
+    pub(crate) id: usize,
+    pub(crate) command: String,
+    pub(crate) line: String,
+    pub(crate) handle: JoinHandle<Result<Option<Output>, E>>,
+    pub(crate) started: Instant,
+    pub(crate) reported: bool,
+}
+
+impl<E, Output> Job<E, Output> {
+    pub(crate) fn status(&self) -> &'static str {
+        if self.handle.is_finished() {
+            "Done"
+        } else {
+            "Running"
+        }
+    }
+}
+
+impl<Context, E, Output> Repl<Context, E, Output>
+where
+    Context: Clone + Send + 'static,
+    E: Display + Send + 'static,
+    Output: Send + 'static,
+{
+    /// Run commands ending with `&` on a background thread and add the `jobs` and `fg`
+    /// builtins. A job runs with a clone of the context, so changes it makes to the
+    /// context are not seen by the REPL. Its output is kept until `fg <id>` prints it.
+    /// Jobs are threads rather than processes, so they work the same on Windows, where
+    /// Ctrl+Break stops waiting in `fg` like Ctrl-C does.
+    pub fn with_background_jobs(mut self) -> Self {
+        self.spawner = Some(spawn::<Context, E, Output>);
+
+        self
+    }
+}
+
+impl<Context, E, Output> Repl<Context, E, Output>
+where
+    E: Display + From<Error> + std::fmt::Debug,
+{
+    /// The line without its trailing `&` if it should run as a background job
+    pub(crate) fn background_line(&self, line: &str) -> Option<String> {
+        self.spawner?;
+        let line = line.trim_end();
+        if line.ends_with("\\&") {
+            return None;
+        }
+        line.strip_suffix('&').map(str::to_string)
+    }
+
+    /// Starts a command on a background thread with a clone of the context
+    pub(crate) fn start_job(&mut self, line: String) -> core::result::Result<(), E> {
+        let invocation = match self.prepare_line(line)? {
+            Some(invocation) => invocation,
+            None => return Ok(()),
+        };
+        let definition = find_command(&self.commands, &invocation.command, &self.scope())
+            .filter(|definition| definition.callback.is_some())
+            .ok_or_else(|| Error::BackgroundUnsupported(invocation.command.clone()))?;
+        self.check_guard(&invocation.command, definition.guard)?;
+        let mut argv: Vec<&str> = vec![&invocation.command];
+        argv.extend(invocation.args.iter().map(String::as_str));
+        let matches = match definition.get_matches(
+            &argv,
+            self.infer_choices,
+            self.expand_paths,
+            self.arithmetic,
+            self.prompt_missing(),
+        ) {
+            Ok(matches) => matches,
+            Err(errors) => {
+                self.print_clap_errors(&invocation.command, &errors);
+                return Ok(());
+            }
+        };
+        if !self.confirmed(
+            &invocation.command,
+            definition.confirmation.as_deref(),
+            &matches,
+        ) {
+            return Ok(());
+        }
+        let callback = definition
+            .callback
+            .expect("Must be filled for sync commands");
+        let spawn = self.spawner.expect("Must be set for background jobs");
+        self.job_count += 1;
+        let id = self.job_count;
+        let handle = spawn(
+            callback,
+            matches,
+            &self.context,
+            self.job_notifier(id, &invocation.line),
+        );
+        self.print_output(format!("[{}] {}", id, invocation.line));
+        self.jobs.push(Job {
+            id,
+            command: invocation.command.clone(),
+            line: invocation.line.clone(),
+            handle,
+            started: Instant::now(),
+            reported: false,
+        });
+        self.record_invocation(invocation, true);
+
+        Ok(())
+    }
+
+    /// Announces a finished job above the prompt as soon as it is done
+    #[cfg(feature = "external_printer")]
+    fn job_notifier(&self, id: usize, line: &str) -> Option<Notifier> {
+        let printer = self.printer.clone();
+        let message = format!("[{}] Done     {}", id, line);
+        Some(Box::new(move || {
+            let _ = printer.sender().send(message);
+        }))
+    }
+
+    /// Finished jobs are announced before the next prompt instead
+    #[cfg(not(feature = "external_printer"))]
+    fn job_notifier(&self, _id: usize, _line: &str) -> Option<Notifier> {
+        None
+    }
+
+    /// Announces the jobs that finished since the last prompt
+    pub(crate) fn report_finished_jobs(&mut self) {
+        if cfg!(feature = "external_printer") {
+            return;
+        }
+        let mut finished = vec![];
+        for job in self.jobs.iter_mut() {
+            if !job.reported && job.handle.is_finished() {
+                job.reported = true;
+                finished.push(format!("[{}] Done     {}", job.id, job.line));
+            }
+        }
+        for message in finished {
+            self.print_output(message);
+        }
+    }
+
+    /// Waits for the job `id`, or the last one, and handles its result like a command's.
+    /// Ctrl-C, or Ctrl+Break on Windows, returns to the prompt and leaves the job running.
+    pub(crate) fn foreground_job(&mut self, args: &[&str]) -> core::result::Result<(), E> {
+        let (index, job) = self.take_job(args)?;
+        let guard = self.cancellation.watch();
+        while !job.handle.is_finished() && !self.cancellation.is_cancelled() {
+            std::thread::sleep(JOB_POLL_INTERVAL);
+        }
+        drop(guard);
+        self.finish_foreground_job(index, job)
+    }
+
+    #[cfg(feature = "async")]
+    pub(crate) async fn foreground_job_async(
+        &mut self,
+        args: &[&str],
+    ) -> core::result::Result<(), E> {
+        let (index, job) = self.take_job(args)?;
+        let guard = self.cancellation.watch();
+        while !job.handle.is_finished() && !self.cancellation.is_cancelled() {
+            delay(JOB_POLL_INTERVAL).await;
+        }
+        drop(guard);
+        self.finish_foreground_job(index, job)
+    }
+
+    /// Removes the job `id`, or the last one, from the jobs with its position
+    fn take_job(&mut self, args: &[&str]) -> core::result::Result<(usize, Job<E, Output>), E> {
+        let index = match args {
+            [] => self.jobs.len().checked_sub(1),
+            [id] => {
+                let id = id.parse::<usize>().map_err(Error::from)?;
+                self.jobs.iter().position(|job| job.id == id)
+            }
+            _ => return Err(Error::TooManyArguments("fg".to_string(), 1).into()),
+        };
+        let index = index.ok_or_else(|| {
+            Error::JobNotFound(args.first().and_then(|id| id.parse().ok()).unwrap_or(0))
+        })?;
+        Ok((index, self.jobs.remove(index)))
+    }
+
+    /// Handles the result of a job waited for by `fg`, or puts it back if it still runs
+    fn finish_foreground_job(
+        &mut self,
+        index: usize,
+        job: Job<E, Output>,
+    ) -> core::result::Result<(), E> {
+        if !job.handle.is_finished() {
+            self.print_output(format!("[{}] {:<8} {}", job.id, job.status(), job.line));
+            self.jobs.insert(index, job);
+            return Ok(());
+        }
+        let result = job.handle.join().map_err(|_| Error::JobPanicked(job.id))?;
+        self.handle_command_result(&job.command, result, job.started.elapsed())
+    }
+
+    /// Lists the background jobs for the `jobs` builtin
+    pub(crate) fn list_jobs(&self) {
+        for job in &self.jobs {
+            self.print_output(format!("[{}] {:<8} {}", job.id, job.status(), job.line));
+        }
+    }
+}
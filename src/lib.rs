@@ -27,6 +27,9 @@
 //! - If the return is `Ok(None)`, it prints nothing
 //! - If the return is an error, it prints the error message to stderr
 //!
+//! Output and errors can be redirected with [Repl::with_output](struct.Repl.html#method.with_output)
+//! and [Repl::with_error_output](struct.Repl.html#method.with_error_output), e.g. to capture them in tests.
+//!
 //! # Context
 //!
 //! The `Context` type is used to keep state between REPL calls. Here's an example:
@@ -39,6 +42,62 @@
 //! - the context is passed to your command callback functions as a mutable reference
 //! - the prompt can be changed after each executed commmand using with_on_after_command as shown
 //!
+//! # Typed Arguments
+//!
+//! Arguments are parsed by clap, so a [value_parser](https://docs.rs/clap/latest/clap/macro.value_parser.html)
+//! rejects invalid input (e.g. `add one two`) with a clear error before your callback runs:
+//! ```rust,no_run
+#![doc = include_str!("../examples/typed_arguments.rs")]
+//! ```
+//! A few things to note:
+//! - read typed values with `args.get_one::<i32>(..)` instead of parsing strings yourself
+//! - use `value_name` to show the expected type in `help <command>`
+//! - `num_args(1..)` collects all remaining words into one argument, read them with `args.get_many(..)`
+//! - `value_parser!` handles any type implementing `FromStr`, like `IpAddr` or `char`, and
+//!   [parse_duration](fn.parse_duration.html) and [parse_bool](fn.parse_bool.html) accept
+//!   durations like `30s` and booleans like `yes` or `off`
+//! - `value_parser(["on", "off", "auto"])` allows only the given values, help lists them and
+//!   the completer offers them for the argument
+//! - `value_parser!(u16).range(1..)` limits numbers to a range,
+//!   [matches_regex](fn.matches_regex.html) values to a pattern and
+//!   [validated](fn.validated.html) runs your own check with its error message
+//! - `get_one` returns `None` for an optional argument that wasn't given, `try_get_one` also
+//!   returns an error instead of panicking for an unknown argument or the wrong type
+//!
+//! # Structured Output
+//!
+//! Commands can return any type instead of `String`, the REPL renders it with its
+//! [OutputRenderer](trait.OutputRenderer.html). By default that is the `Display`
+//! implementation, [Repl::with_output_renderer](struct.Repl.html#method.with_output_renderer)
+//! switches to another format like JSON:
+//! ```rust,no_run
+#![doc = include_str!("../examples/structured_output.rs")]
+//! ```
+//! Use [Repl::new_with_renderer](struct.Repl.html#method.new_with_renderer) for output
+//! types that don't implement `Display`.
+//!
+//! Commands returning an [OutputStream](struct.OutputStream.html) print every line as soon
+//! as it is produced, e.g. to follow a log:
+//! ```rust,no_run
+#![doc = include_str!("../examples/streaming.rs")]
+//! ```
+//!
+//! # Commands from Functions
+//!
+//! With the `derive` feature the `repl_command` attribute generates the clap command and the
+//! callback from a function, its parameters become typed arguments:
+//! ```rust,ignore
+#![doc = include_str!("../examples/derive.rs")]
+//! ```
+//!
+//! # Arguments Structs
+//!
+//! With the `serde` feature [DeserializeArgs](trait.DeserializeArgs.html) deserializes the
+//! arguments into a struct, converting every value to the type of its field:
+//! ```rust,ignore
+#![doc = include_str!("../examples/deserialize.rs")]
+//! ```
+//!
 //! # Async Support
 //!
 //! The `async` feature allows you to write async REPL code:
@@ -48,6 +107,49 @@
 //! A few things to note:
 //! - The ugly Pin::Box workaround is required because of unstable rust async Fn's
 //!
+//! # Other Frontends
+//!
+//! `run` is the terminal frontend of the REPL. To drive it from elsewhere, e.g. a GUI or
+//! tests, feed it lines with [feed_line](struct.Repl.html#method.feed_line), which returns
+//! the output and errors as a [ReplResponse](struct.ReplResponse.html) instead of printing
+//! them:
+//! ```rust
+//! # use reedline_repl_rs::clap::{ArgMatches, Command};
+//! # use reedline_repl_rs::{Repl, Result};
+//! # fn hello<T>(_args: ArgMatches, _context: &mut T) -> Result<Option<String>> {
+//! #     Ok(Some("Hello!".to_string()))
+//! # }
+//! let mut repl = Repl::new(()).with_command(Command::new("hello"), hello);
+//! let response = repl.feed_line("hello");
+//! assert_eq!(response.output, "Hello!\n");
+//! assert!(response.success);
+//! ```
+//!
+//! [ReplTester](struct.ReplTester.html) builds on it to test the commands of a REPL:
+//! ```rust
+//! # use reedline_repl_rs::clap::{ArgMatches, Command};
+//! # use reedline_repl_rs::{Repl, ReplTester, Result};
+//! # fn hello<T>(_args: ArgMatches, _context: &mut T) -> Result<Option<String>> {
+//! #     Ok(Some("Hello!".to_string()))
+//! # }
+//! let repl = Repl::new(()).with_command(Command::new("hello"), hello);
+//! ReplTester::new(repl)
+//!     .send("hello")
+//!     .expect_output("Hello!")
+//!     .send("hello extra")
+//!     .expect_error("unexpected argument 'extra'")
+//!     .send("bogus")
+//!     .expect_error("Unknown command 'bogus'");
+//! ```
+//!
+//! # Remote Access
+//!
+//! The `serve` feature lets clients run the commands over a TCP or Unix socket with a plain
+//! line protocol, e.g. to attach to a REPL embedded in a daemon with `nc localhost 7000`:
+//! ```rust,ignore
+#![doc = include_str!("../examples/serve.rs")]
+//! ```
+//!
 //! # Keybindings
 //!
 //! Per default Emacs-style keybindings are used
@@ -78,13 +180,16 @@
 //!     append <name>
 //!
 //! ARGS:
-//!     <name>
+//!     <name>    Name to add to the end
 //!
 //! OPTIONS:
 //!     -h, --help    Print help information
 //! MyApp> 〉
 //! ```
 //!
+//! Describe each argument with clap's `Arg::help`, `help <command>` shows the description
+//! next to the argument together with its default value if it has one.
+//!
 //! # Errors
 //!
 //! Your command functions don't need to return `reedline_repl_rs::Error`; you can return any error from
@@ -96,37 +201,182 @@
 //! ```rust,no_run
 #![doc = include_str!("../examples/custom_error.rs")]
 //! ```
+//!
+//! Without an error type of your own, return [ReplError](struct.ReplError.html), which any
+//! error converts into with `?`. `anyhow::Error` and `eyre::Report` satisfy the bounds of
+//! the REPL's error type as they are.
+//!
+//! ```rust,no_run
+#![doc = include_str!("../examples/any_error.rs")]
+//! ```
 
+mod arithmetic;
+mod cancel;
 mod command;
 mod completer;
+#[cfg(feature = "config")]
+mod config;
+#[cfg(feature = "serde")]
+mod deserialize;
+mod diff;
+mod editor;
 mod error;
+mod event;
+mod expand;
+mod health;
+mod help_export;
+mod highlighter;
+mod hinter;
+mod history;
+mod interact;
+mod invocation;
+mod jobs;
+mod messages;
+mod mode;
+mod output;
+mod pager;
+mod parsers;
+mod progress;
 mod prompt;
+mod redirect;
+mod registry;
 mod repl;
+mod response;
+mod screen;
+mod script;
+#[cfg(feature = "serve")]
+mod serve;
+mod settings;
+mod stats;
+mod suggestions;
+mod terminal;
+mod tester;
+mod theme;
+#[cfg(feature = "async")]
+mod timer;
+mod tokenizer;
+mod transcript;
+mod validator;
+mod variables;
 
+pub use cancel::CancellationToken;
 pub use clap;
 use clap::ArgMatches;
+#[cfg(feature = "shell_completions")]
+pub use clap_complete::Shell;
+pub use completer::CompletionMatching;
 pub use crossterm;
-pub use error::{Error, Result};
+#[cfg(feature = "serde")]
+pub use deserialize::DeserializeArgs;
+pub use error::{Error, ReplError, Result};
+pub use event::ReplEvent;
+pub use health::{HealthItem, HealthStatus};
+pub use help_export::HelpFormat;
+pub use interact::{confirm, input, password, select};
+pub use invocation::Invocation;
+pub use messages::Messages;
+pub use mode::Modes;
 pub use nu_ansi_term;
+pub use output::{OutputRenderer, OutputStream, PlainRenderer};
+pub use pager::PagerMode;
+pub use parsers::{matches_regex, parse_bool, parse_duration, validated};
+pub use progress::Progress;
 pub use reedline;
+#[cfg(feature = "derive")]
+pub use reedline_repl_rs_macros::repl_command;
 #[doc(inline)]
-pub use repl::Repl;
+pub use registry::CommandRegistry;
+pub use repl::{CtrlCAction, CtrlDAction, HelpOrdering, HistoryMode, MenuKind, PastePolicy, Repl};
+pub use response::ReplResponse;
+pub use screen::{with_alternate_screen, AlternateScreen};
+#[cfg(feature = "serve")]
+pub use serve::Listener;
+pub use stats::CommandStats;
+use std::collections::HashMap;
 #[cfg(feature = "async")]
 use std::{future::Future, pin::Pin};
+pub use tester::ReplTester;
+pub use theme::Theme;
+pub use variables::Variables;
 pub use yansi;
 use yansi::Paint;
 
-/// Command callback function signature
-pub type Callback<Context, Error> =
-    fn(ArgMatches, &mut Context) -> std::result::Result<Option<String>, Error>;
+/// Command callback function signature, the output is rendered by the REPL's
+/// [OutputRenderer](trait.OutputRenderer.html)
+pub type Callback<Context, Error, Output = String> =
+    fn(ArgMatches, &mut Context) -> std::result::Result<Option<Output>, Error>;
 
 /// Async Command callback function signature
 #[cfg(feature = "async")]
-pub type AsyncCallback<Context, Error> =
+pub type AsyncCallback<Context, Error, Output = String> =
     fn(
         ArgMatches,
         &'_ mut Context,
-    ) -> Pin<Box<dyn Future<Output = std::result::Result<Option<String>, Error>> + '_>>;
+    ) -> Pin<Box<dyn Future<Output = std::result::Result<Option<Output>, Error>> + '_>>;
+
+/// Argument parser signature, turns the raw arguments of a command into named values
+/// instead of the default tokenizer and clap
+pub type ParserCallback<Context, Error> =
+    fn(&str, &Context) -> std::result::Result<HashMap<String, String>, Error>;
+
+/// Health check signature, called once before the first prompt
+pub type HealthCheckCallback<Context, Error> =
+    fn(&mut Context) -> std::result::Result<Vec<HealthItem>, Error>;
+
+/// Command error handler signature, gets the error of a failed command, returning `Ok`
+/// marks the error as handled
+pub type CommandErrorHandler<Context, Error> =
+    fn(Error, &mut Context) -> std::result::Result<(), Error>;
+
+/// Visibility predicate signature, decides whether a command is shown in help and completion
+pub type VisibleWhenCallback<Context> = fn(&Context) -> bool;
+
+/// Guard signature, returns why a command can't run in the current context, e.g.
+/// `"not connected"`
+pub type GuardCallback<Context> = fn(&Context) -> std::result::Result<(), String>;
+
+/// Right prompt signature, returns the text shown on the right edge of the prompt line
+pub type RightPromptCallback<Context> = fn(&Context) -> String;
+
+/// Argument completer signature, returns the values to complete an argument with
+pub type ArgCompleterCallback<Context> = fn(&Context) -> Vec<String>;
+
+/// Modes accessor signature, returns the mode stack kept in the context
+pub type ModesCallback<Context> = fn(&Context) -> &Modes;
+
+/// Line preprocessor signature, rewrites each input line before it is tokenized
+pub type LinePreprocessorCallback<Context> = fn(String, &Context) -> String;
+
+/// History filter signature, gets an entered line and whether it succeeded, returning
+/// false keeps the line out of the history file
+pub type HistoryFilterCallback = fn(&str, bool) -> bool;
+
+/// BeforeCommand hook signature, called with the command name before the command runs
+pub type BeforeCommandCallback<Context> = fn(&str, &mut Context);
+
+/// AfterCommand hook signature, called with the command name, its output and its run time after
+/// the command ran
+pub type AfterCommandHookCallback<Context> =
+    fn(&str, &Option<String>, std::time::Duration, &mut Context);
+
+/// Session message signature, returns the banner or goodbye message for the context
+pub type SessionMessageCallback<Context> = fn(&Context) -> String;
+
+/// Session hook signature, used for the on start and on exit hooks
+pub type SessionCallback<Context> = fn(&mut Context);
+
+/// Context saver signature, returns the snapshot of the context written on exit
+pub type SaveContextCallback<Context> = fn(&Context) -> std::result::Result<String, ReplError>;
+
+/// Context loader signature, restores the context from the snapshot of the last session
+pub type LoadContextCallback<Context> =
+    fn(&mut Context, &str) -> std::result::Result<(), ReplError>;
+
+/// Event listener signature, called with every session and command event
+pub type EventListenerCallback = fn(ReplEvent);
+
+/// Line editor configurator signature, gets the configured reedline instance before the REPL starts
+pub type ReedlineConfigurator = fn(reedline::Reedline) -> reedline::Reedline;
 
 /// AfterCommand callback function signature
 pub type AfterCommandCallback<Context, Error> =
@@ -0,0 +1,95 @@
+/// Texts of the built-in messages, set with
+/// [Repl::with_messages](struct.Repl.html#method.with_messages) to translate the REPL.
+/// Every `{}` is replaced by a value, in order. Errors of this crate reach the REPL as your
+/// error type, translate them in its `Display` implementation
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Messages {
+    /// Description of the help command
+    pub help_about: String,
+    /// Hint after the help listing, `{}` is the help command
+    pub help_verbose_hint: String,
+    /// `{}` is the command or topic without help
+    pub help_not_found: String,
+    /// Pointer to the help of a command after errors in its arguments, `{}` is the help
+    /// command with the command
+    pub help_more_information: String,
+    /// Heading of the commands in the help listing
+    pub commands_heading: String,
+    /// Heading of the help topics in the help listing
+    pub topics_heading: String,
+    /// Tag in front of errors
+    pub error_tag: String,
+    /// `{}` is the number of problems and `{}` the command
+    pub argument_problems: String,
+    /// Usage line after the problems with the arguments of a command, `{}` is the usage
+    pub usage: String,
+    /// `{}` is the run time of a command
+    pub took: String,
+    /// Result of comparing two identical outputs
+    pub outputs_identical: String,
+    /// `{}` is the line finishing a paste
+    pub paste_start: String,
+    /// `{}` is the number of pasted commands
+    pub paste_confirm: String,
+    /// Shown when the pasted commands are not executed
+    pub paste_cancelled: String,
+    /// `{}` is the line of the failed command
+    pub paste_stopped: String,
+    /// `{}` is the error of the after command callback
+    pub after_command_failed: String,
+    /// Warning before a deprecated command runs, `{}` is the command and `{}` its note
+    pub deprecated_warning: String,
+    /// Added to the description of a deprecated command, `{}` is its note
+    pub deprecated_marker: String,
+    /// `{}` is the file of the saved context and `{}` why it couldn't be loaded
+    pub context_restore_failed: String,
+    /// Refusal of a command needing confirmation without a terminal, `{}` is the command
+    pub confirmation_required: String,
+    /// Answer to a confirmation question other than yes
+    pub confirmation_cancelled: String,
+    /// Output of the `stats` builtin before any command ran
+    pub no_stats: String,
+}
+
+impl Messages {
+    /// Replaces the `{}` of `template` with `values`, in order
+    pub(crate) fn fill(template: &str, values: &[&str]) -> String {
+        let mut values = values.iter();
+        let mut parts = template.split("{}");
+        let mut filled = parts.next().unwrap_or_default().to_string();
+        for part in parts {
+            filled += values.next().copied().unwrap_or_default();
+            filled += part;
+        }
+        filled
+    }
+}
+
+impl Default for Messages {
+    fn default() -> Self {
+        Self {
+            help_about: "Show help, -v adds the arguments of all commands".to_string(),
+            help_verbose_hint: "Use '{} -v' to show the arguments of all commands".to_string(),
+            help_not_found: "Help not found for command '{}'".to_string(),
+            help_more_information: "For more information, try '{}'.".to_string(),
+            commands_heading: "COMMANDS:".to_string(),
+            topics_heading: "TOPICS:".to_string(),
+            error_tag: "error:".to_string(),
+            argument_problems: "error: {} problems with '{}':".to_string(),
+            usage: "Usage: {}".to_string(),
+            took: "took {}".to_string(),
+            outputs_identical: "Outputs are identical".to_string(),
+            paste_start: "Enter commands, finish with '{}'".to_string(),
+            paste_confirm: "Execute {} command(s)? [y/N]".to_string(),
+            paste_cancelled: "paste cancelled".to_string(),
+            paste_stopped: "paste stopped at line {}".to_string(),
+            after_command_failed: "failed to execute after_command_callback {}".to_string(),
+            deprecated_warning: "warning: '{}' is deprecated, {}".to_string(),
+            deprecated_marker: "[deprecated: {}]".to_string(),
+            context_restore_failed: "failed to restore the context from {}: {}".to_string(),
+            confirmation_required: "'{}' needs confirmation, pass --yes to run it".to_string(),
+            confirmation_cancelled: "cancelled".to_string(),
+            no_stats: "No commands have run yet".to_string(),
+        }
+    }
+}
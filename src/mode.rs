@@ -0,0 +1,42 @@
+/// Stack of active modes for REPLs whose commands depend on their state, like a device
+/// CLI with `configure` and `interface` modes. Keep it in your context and push or pop
+/// modes from command callbacks. Commands added with
+/// [Repl::with_mode_command](struct.Repl.html#method.with_mode_command) are only
+/// available while their mode is the active one.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Modes {
+    stack: Vec<String>,
+}
+
+impl Modes {
+    /// Create an empty stack, i.e. no mode is active
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Enter a mode, it stays active until it is popped
+    pub fn push(&mut self, mode: &str) {
+        self.stack.push(mode.to_string());
+    }
+
+    /// Leave the active mode and return its name
+    pub fn pop(&mut self) -> Option<String> {
+        self.stack.pop()
+    }
+
+    /// The active mode, `None` at the top level
+    pub fn current(&self) -> Option<&str> {
+        self.stack.last().map(String::as_str)
+    }
+
+    /// All entered modes, outermost first
+    pub fn path(&self) -> &[String] {
+        &self.stack
+    }
+}
+
+/// The active mode and whether it is a sub-REPL hiding the commands outside of it
+pub(crate) struct Scope {
+    pub(crate) mode: Option<String>,
+    pub(crate) exclusive: bool,
+}
@@ -0,0 +1,67 @@
+use std::fmt::Display;
+
+/// Renders the value returned by a command into the text the REPL prints
+pub trait OutputRenderer<Output> {
+    /// Render a command's output
+    fn render(&self, output: &Output) -> String;
+
+    /// Render a command's output piece by piece, each piece is printed as soon as it is
+    /// ready. `None`, the default, renders the output at once with `render`.
+    fn stream<'a>(
+        &'a self,
+        _output: &'a mut Output,
+    ) -> Option<Box<dyn Iterator<Item = String> + 'a>> {
+        None
+    }
+}
+
+/// Renders any output with its `Display` implementation, the default renderer
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PlainRenderer;
+
+impl<Output: Display> OutputRenderer<Output> for PlainRenderer {
+    fn render(&self, output: &Output) -> String {
+        output.to_string()
+    }
+}
+
+impl<Output, F> OutputRenderer<Output> for F
+where
+    F: Fn(&Output) -> String,
+{
+    fn render(&self, output: &Output) -> String {
+        self(output)
+    }
+}
+
+/// Output produced line by line, e.g. by a command following a log. Every line is printed
+/// as soon as the iterator yields it instead of after the whole output is ready. Create the
+/// REPL with `Repl::new_with_renderer(context, PlainRenderer)` to return it from commands.
+pub struct OutputStream(Box<dyn Iterator<Item = String> + Send>);
+
+impl OutputStream {
+    /// Stream the lines yielded by `lines`
+    pub fn new(lines: impl Iterator<Item = String> + Send + 'static) -> Self {
+        OutputStream(Box::new(lines))
+    }
+}
+
+impl From<String> for OutputStream {
+    fn from(text: String) -> Self {
+        OutputStream::new(std::iter::once(text))
+    }
+}
+
+impl OutputRenderer<OutputStream> for PlainRenderer {
+    fn render(&self, _output: &OutputStream) -> String {
+        // never called, streams are rendered by `stream`
+        String::new()
+    }
+
+    fn stream<'a>(
+        &'a self,
+        output: &'a mut OutputStream,
+    ) -> Option<Box<dyn Iterator<Item = String> + 'a>> {
+        Some(Box::new(&mut output.0))
+    }
+}
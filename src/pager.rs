@@ -0,0 +1,128 @@
+use crate::screen::AlternateScreen;
+use crossterm::cursor::MoveTo;
+use crossterm::event::{read, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crossterm::style::{Attribute, SetAttribute};
+use crossterm::terminal::{size, Clear, ClearType};
+use crossterm::{execute, queue};
+use std::io::{self, stdout, Write};
+use std::process::{Command, Stdio};
+
+/// Pager for outputs longer than the terminal, set with
+/// [Repl::with_pager](struct.Repl.html#method.with_pager)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PagerMode {
+    /// Built-in pager: Space and `f` page down, `b` pages up, Enter, arrows, `j` and `k`
+    /// scroll by a line, `g` and `G` jump to the start and end, `q` quits
+    Internal,
+    /// External program reading the output from stdin, like `less -R`
+    External(String),
+}
+
+/// Whether `text` has more lines than fit on the terminal with the prompt
+pub(crate) fn exceeds_screen(text: &str) -> bool {
+    match size() {
+        Ok((_, rows)) => text.lines().count() >= rows as usize,
+        Err(_) => false,
+    }
+}
+
+/// Shows `text` in the pager
+pub(crate) fn page(text: &str, mode: &PagerMode) -> io::Result<()> {
+    match mode {
+        PagerMode::Internal => page_internal(text),
+        PagerMode::External(command) => page_external(text, command),
+    }
+}
+
+fn page_external(text: &str, command: &str) -> io::Result<()> {
+    let mut words = command.split_whitespace();
+    let program = words
+        .next()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "empty pager command"))?;
+    let mut child = Command::new(program)
+        .args(words)
+        .stdin(Stdio::piped())
+        .spawn()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        // the pager may quit before reading everything
+        let _ = stdin.write_all(text.as_bytes());
+    }
+    child.wait()?;
+    Ok(())
+}
+
+fn page_internal(text: &str) -> io::Result<()> {
+    let lines: Vec<&str> = text.lines().collect();
+    let _screen = AlternateScreen::enter_raw()?;
+    let mut stdout = stdout();
+    let mut top = 0;
+    loop {
+        let (_, rows) = size()?;
+        let height = (rows as usize).saturating_sub(1).max(1);
+        let last_top = lines.len().saturating_sub(height);
+        top = top.min(last_top);
+        queue!(stdout, Clear(ClearType::All), MoveTo(0, 0))?;
+        for line in lines.iter().skip(top).take(height) {
+            write!(stdout, "{}\r\n", line)?;
+        }
+        queue!(stdout, MoveTo(0, rows.saturating_sub(1)))?;
+        queue!(stdout, SetAttribute(Attribute::Reverse))?;
+        write!(
+            stdout,
+            " lines {}-{} of {} (q to quit) ",
+            top + 1,
+            (top + height).min(lines.len()),
+            lines.len()
+        )?;
+        execute!(stdout, SetAttribute(Attribute::Reset))?;
+
+        match read_key()? {
+            KeyEvent {
+                code: KeyCode::Char('c'),
+                modifiers: KeyModifiers::CONTROL,
+                ..
+            }
+            | KeyEvent {
+                code: KeyCode::Char('q') | KeyCode::Esc,
+                ..
+            } => break,
+            KeyEvent {
+                code: KeyCode::Char(' ' | 'f') | KeyCode::PageDown,
+                ..
+            } => top += height,
+            KeyEvent {
+                code: KeyCode::Char('b') | KeyCode::PageUp,
+                ..
+            } => top = top.saturating_sub(height),
+            KeyEvent {
+                code: KeyCode::Enter | KeyCode::Down | KeyCode::Char('j'),
+                ..
+            } => top += 1,
+            KeyEvent {
+                code: KeyCode::Up | KeyCode::Char('k'),
+                ..
+            } => top = top.saturating_sub(1),
+            KeyEvent {
+                code: KeyCode::Char('g') | KeyCode::Home,
+                ..
+            } => top = 0,
+            KeyEvent {
+                code: KeyCode::Char('G') | KeyCode::End,
+                ..
+            } => top = last_top,
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Reads the next key press, ignoring other events and key releases
+fn read_key() -> io::Result<KeyEvent> {
+    loop {
+        if let Event::Key(key) = read()? {
+            if key.kind != KeyEventKind::Release {
+                return Ok(key);
+            }
+        }
+    }
+}
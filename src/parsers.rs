@@ -0,0 +1,120 @@
+use crate::error::*;
+use regex::Regex;
+use std::time::Duration;
+
+/// Parses a duration like `500ms`, `2s`, `1.5m` or `1h`, plain numbers are seconds
+pub fn parse_duration(value: &str) -> Result<Duration> {
+    let value = value.trim();
+    let split = value
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let seconds = match unit {
+        "ms" => 0.001,
+        "" | "s" => 1.0,
+        "m" => 60.0,
+        "h" => 3600.0,
+        _ => return Err(Error::InvalidDuration(value.to_string())),
+    };
+    match number.parse::<f64>() {
+        Ok(number) if number.is_finite() => Ok(Duration::from_secs_f64(number * seconds)),
+        _ => Err(Error::InvalidDuration(value.to_string())),
+    }
+}
+
+/// Parses a boolean, accepting `true`/`false`, `yes`/`no`, `on`/`off`, `y`/`n` and `1`/`0`
+/// in any case
+pub fn parse_bool(value: &str) -> Result<bool> {
+    match value.trim().to_lowercase().as_str() {
+        "true" | "yes" | "y" | "on" | "1" => Ok(true),
+        "false" | "no" | "n" | "off" | "0" => Ok(false),
+        _ => Err(Error::InvalidBool(value.to_string())),
+    }
+}
+
+/// Value parser accepting values that match `pattern`, e.g.
+/// `Arg::new("name").value_parser(matches_regex("^[a-z]+$"))`
+pub fn matches_regex(
+    pattern: &str,
+) -> impl Fn(&str) -> Result<String> + Clone + Send + Sync + 'static {
+    let pattern = pattern.to_string();
+    let regex = Regex::new(&pattern);
+    move |value: &str| match &regex {
+        Ok(regex) if regex.is_match(value) => Ok(value.to_string()),
+        Ok(_) => Err(Error::InvalidValue(format!(
+            "'{}' doesn't match '{}'",
+            value, pattern
+        ))),
+        Err(_) => Err(Error::InvalidPattern(pattern.clone())),
+    }
+}
+
+/// Value parser accepting values `validator` returns `Ok` for, its error message is shown
+/// otherwise, e.g. `Arg::new("port").value_parser(validated(check_port))`
+pub fn validated(
+    validator: fn(&str) -> std::result::Result<(), String>,
+) -> impl Fn(&str) -> Result<String> + Clone + Send + Sync + 'static {
+    move |value: &str| match validator(value) {
+        Ok(()) => Ok(value.to_string()),
+        Err(message) => Err(Error::InvalidValue(message)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn durations() {
+        assert_eq!(parse_duration("500ms").unwrap(), Duration::from_millis(500));
+        assert_eq!(parse_duration(" 2 ").unwrap(), Duration::from_secs(2));
+        assert_eq!(parse_duration("1.5m").unwrap(), Duration::from_secs(90));
+        assert_eq!(parse_duration("1h").unwrap(), Duration::from_secs(3600));
+        for value in ["", "ms", "5d", "1.2.3s", "-1s"] {
+            assert_eq!(
+                parse_duration(value),
+                Err(Error::InvalidDuration(value.to_string()))
+            );
+        }
+    }
+
+    #[test]
+    fn bools() {
+        for value in ["true", "Yes", "y", "ON", "1"] {
+            assert_eq!(parse_bool(value), Ok(true));
+        }
+        for value in ["false", "No", "n", "off", "0"] {
+            assert_eq!(parse_bool(value), Ok(false));
+        }
+        assert_eq!(
+            parse_bool("maybe"),
+            Err(Error::InvalidBool("maybe".to_string()))
+        );
+    }
+
+    #[test]
+    fn regex_values() {
+        let lowercase = matches_regex("^[a-z]+$");
+        assert_eq!(lowercase("abc"), Ok("abc".to_string()));
+        assert!(matches!(lowercase("Abc"), Err(Error::InvalidValue(_))));
+        assert_eq!(
+            matches_regex("(")("a"),
+            Err(Error::InvalidPattern("(".to_string()))
+        );
+    }
+
+    #[test]
+    fn validated_values() {
+        fn even(value: &str) -> std::result::Result<(), String> {
+            match value.parse::<u32>() {
+                Ok(number) if number % 2 == 0 => Ok(()),
+                _ => Err(format!("{} isn't even", value)),
+            }
+        }
+        assert_eq!(validated(even)("4"), Ok("4".to_string()));
+        assert_eq!(
+            validated(even)("3"),
+            Err(Error::InvalidValue("3 isn't even".to_string()))
+        );
+    }
+}
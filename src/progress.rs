@@ -0,0 +1,153 @@
+use crossterm::cursor::MoveToColumn;
+use crossterm::queue;
+use crossterm::terminal::{Clear, ClearType};
+use std::io::{self, stderr, IsTerminal, Write};
+
+const BAR_WIDTH: usize = 30;
+const SPINNER: [char; 4] = ['|', '/', '-', '\\'];
+
+/// Progress bar or spinner for long-running commands, drawn on a single line of stderr
+/// while the command runs. The line is cleared when the progress is finished or dropped,
+/// so the prompt is drawn as usual afterwards. Nothing is drawn if stderr is not a
+/// terminal, e.g. when the output is piped into a file.
+///
+/// ```rust,no_run
+/// use reedline_repl_rs::Progress;
+///
+/// let mut progress = Progress::bar(300, "importing");
+/// for _ in 0..300 {
+///     // import a row
+///     progress.inc(1);
+/// }
+/// progress.finish();
+/// ```
+pub struct Progress {
+    message: String,
+    position: u64,
+    total: Option<u64>,
+    enabled: bool,
+}
+
+impl Progress {
+    /// Create a progress bar going from 0 to `total`
+    pub fn bar(total: u64, message: &str) -> Self {
+        Self::new(Some(total), message)
+    }
+
+    /// Create a spinner for work of unknown length, advance it with [tick](#method.tick)
+    pub fn spinner(message: &str) -> Self {
+        Self::new(None, message)
+    }
+
+    fn new(total: Option<u64>, message: &str) -> Self {
+        let progress = Self {
+            message: message.to_string(),
+            position: 0,
+            total,
+            enabled: stderr().is_terminal(),
+        };
+        progress.draw();
+        progress
+    }
+
+    /// Set the position of the bar
+    pub fn set(&mut self, position: u64) {
+        self.position = position;
+        self.draw();
+    }
+
+    /// Advance the bar by `delta`
+    pub fn inc(&mut self, delta: u64) {
+        self.set(self.position.saturating_add(delta));
+    }
+
+    /// Advance the spinner by one step
+    pub fn tick(&mut self) {
+        self.inc(1);
+    }
+
+    /// Change the message shown next to the progress
+    pub fn set_message(&mut self, message: &str) {
+        self.message = message.to_string();
+        self.draw();
+    }
+
+    /// Remove the progress line
+    pub fn finish(self) {}
+
+    fn render(&self) -> String {
+        match self.total {
+            Some(total) => {
+                let position = self.position.min(total);
+                let filled = match total {
+                    0 => BAR_WIDTH,
+                    _ => (position as f64 / total as f64 * BAR_WIDTH as f64) as usize,
+                };
+                format!(
+                    "{} [{}{}] {}/{}",
+                    self.message,
+                    "#".repeat(filled),
+                    "-".repeat(BAR_WIDTH - filled),
+                    position,
+                    total
+                )
+            }
+            None => format!(
+                "{} {}",
+                SPINNER[self.position as usize % SPINNER.len()],
+                self.message
+            ),
+        }
+    }
+
+    fn draw(&self) {
+        if self.enabled {
+            let _ = self.write_line(Some(&self.render()));
+        }
+    }
+
+    fn write_line(&self, text: Option<&str>) -> io::Result<()> {
+        let mut stderr = stderr().lock();
+        queue!(stderr, MoveToColumn(0), Clear(ClearType::CurrentLine))?;
+        if let Some(text) = text {
+            write!(stderr, "{}", text)?;
+        }
+        stderr.flush()
+    }
+}
+
+impl Drop for Progress {
+    fn drop(&mut self) {
+        if self.enabled {
+            let _ = self.write_line(None);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bar_fills_with_the_position() {
+        let mut progress = Progress::bar(4, "copying");
+        progress.set(2);
+        assert_eq!(
+            progress.render(),
+            format!("copying [{}{}] 2/4", "#".repeat(15), "-".repeat(15))
+        );
+        progress.set(9);
+        assert_eq!(
+            progress.render(),
+            format!("copying [{}] 4/4", "#".repeat(BAR_WIDTH))
+        );
+    }
+
+    #[test]
+    fn spinner_turns_with_each_tick() {
+        let mut progress = Progress::spinner("waiting");
+        assert_eq!(progress.render(), "| waiting");
+        progress.tick();
+        assert_eq!(progress.render(), "/ waiting");
+    }
+}
@@ -5,30 +5,53 @@ use std::borrow::Cow;
 pub struct ReplPrompt {
     default: DefaultPrompt,
     prefix: String,
+    multiline_indicator: String,
+    indicator: Option<String>,
+    right: Option<String>,
+    mode: Option<String>,
+    suffix: Option<String>,
+    status: Option<String>,
+    workspace: Option<String>,
 }
 
 impl Prompt for ReplPrompt {
     /// Use prefix as render prompt
-    fn render_prompt_left(&self) -> Cow<str> {
-        {
-            Cow::Borrowed(&self.prefix)
+    fn render_prompt_left(&self) -> Cow<'_, str> {
+        let prompt = match (&self.mode, &self.suffix) {
+            (Some(mode), _) => Cow::Owned(format!("({}) {}", mode, self.prefix)),
+            (None, Some(suffix)) => Cow::Owned(format!("{}{}", self.prefix, suffix)),
+            (None, None) => Cow::Borrowed(self.prefix.as_str()),
+        };
+        let prompt = match &self.workspace {
+            Some(workspace) => Cow::Owned(format!("[{}] {}", workspace, prompt)),
+            None => prompt,
+        };
+        match &self.status {
+            Some(status) => Cow::Owned(format!("{}{}", status, prompt)),
+            None => prompt,
         }
     }
 
-    // call default impl
-    fn render_prompt_right(&self) -> Cow<str> {
-        self.default.render_prompt_right()
+    // call default impl unless overridden
+    fn render_prompt_right(&self) -> Cow<'_, str> {
+        match &self.right {
+            Some(right) => Cow::Borrowed(right),
+            None => self.default.render_prompt_right(),
+        }
     }
-    fn render_prompt_indicator(&self, edit_mode: PromptEditMode) -> Cow<str> {
-        self.default.render_prompt_indicator(edit_mode)
+    fn render_prompt_indicator(&self, edit_mode: PromptEditMode) -> Cow<'_, str> {
+        match &self.indicator {
+            Some(indicator) => Cow::Borrowed(indicator),
+            None => self.default.render_prompt_indicator(edit_mode),
+        }
     }
-    fn render_prompt_multiline_indicator(&self) -> Cow<str> {
-        self.default.render_prompt_multiline_indicator()
+    fn render_prompt_multiline_indicator(&self) -> Cow<'_, str> {
+        Cow::Borrowed(&self.multiline_indicator)
     }
     fn render_prompt_history_search_indicator(
         &self,
         history_search: PromptHistorySearch,
-    ) -> Cow<str> {
+    ) -> Cow<'_, str> {
         self.default
             .render_prompt_history_search_indicator(history_search)
     }
@@ -46,6 +69,13 @@ impl ReplPrompt {
         ReplPrompt {
             prefix: left_prompt.to_string(),
             default: DefaultPrompt::default(),
+            multiline_indicator: "... ".to_string(),
+            indicator: None,
+            right: None,
+            mode: None,
+            suffix: None,
+            status: None,
+            workspace: None,
         }
     }
 
@@ -53,4 +83,37 @@ impl ReplPrompt {
     pub fn update_prefix(&mut self, prefix: &str) {
         self.prefix = prefix.to_string();
     }
+
+    pub fn update_multiline_indicator(&mut self, indicator: &str) {
+        self.multiline_indicator = indicator.to_string();
+    }
+
+    pub fn update_indicator(&mut self, indicator: &str) {
+        self.indicator = Some(indicator.to_string());
+    }
+
+    /// Text on the right edge, `None` shows the default right prompt
+    pub fn update_right(&mut self, right: Option<String>) {
+        self.right = right;
+    }
+
+    /// Show the active mode in front of the prompt
+    pub fn update_mode(&mut self, mode: Option<String>) {
+        self.mode = mode;
+    }
+
+    /// Outcome of the last line in front of the prompt
+    pub fn update_status(&mut self, status: Option<String>) {
+        self.status = status;
+    }
+
+    /// Name of the active context in front of the prompt
+    pub fn update_workspace(&mut self, workspace: Option<String>) {
+        self.workspace = workspace;
+    }
+
+    /// Text right after the prompt, e.g. of a sub-REPL
+    pub fn update_suffix(&mut self, suffix: Option<String>) {
+        self.suffix = suffix;
+    }
 }
@@ -0,0 +1,74 @@
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+
+/// Where the output of a command goes instead of the terminal
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Redirect {
+    /// `| program`, the output is the input of an external program
+    Pipe(String),
+    /// `> path` or `>> path`, the output is written or appended to a file
+    File { path: String, append: bool },
+}
+
+/// Output of a command redirected with `|` or `>`, keeps what to restore once the command ran
+pub(crate) struct Redirection {
+    pub(crate) previous: Box<dyn Write + Send>,
+    pub(crate) terminal_output: bool,
+    pub(crate) child: Option<Child>,
+}
+
+/// Opens the target of `redirect`, returns the writer for the output and the program the
+/// output is piped into
+pub(crate) fn open(redirect: &Redirect) -> io::Result<(Box<dyn Write + Send>, Option<Child>)> {
+    match redirect {
+        Redirect::File { path, append } => {
+            let file = OpenOptions::new()
+                .create(true)
+                .write(true)
+                .append(*append)
+                .truncate(!*append)
+                .open(path)?;
+            Ok((Box::new(file), None))
+        }
+        Redirect::Pipe(program) => {
+            let mut child = shell(program).stdin(Stdio::piped()).spawn()?;
+            let stdin = child.stdin.take().expect("stdin is piped");
+            Ok((Box::new(PipeWriter(stdin)), Some(child)))
+        }
+    }
+}
+
+/// `program` run by the system shell
+#[cfg(unix)]
+pub(crate) fn shell(program: &str) -> Command {
+    let mut command = Command::new("sh");
+    command.arg("-c").arg(program);
+    command
+}
+
+#[cfg(windows)]
+pub(crate) fn shell(program: &str) -> Command {
+    let mut command = Command::new("cmd");
+    command.arg("/C").arg(program);
+    command
+}
+
+/// Input of a piped program, which may quit before reading everything like `head` does
+struct PipeWriter(ChildStdin);
+
+impl Write for PipeWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self.0.write(buf) {
+            Err(err) if err.kind() == io::ErrorKind::BrokenPipe => Ok(buf.len()),
+            result => result,
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self.0.flush() {
+            Err(err) if err.kind() == io::ErrorKind::BrokenPipe => Ok(()),
+            result => result,
+        }
+    }
+}
@@ -0,0 +1,74 @@
+#[cfg(feature = "async")]
+use crate::AsyncCallback;
+use crate::Callback;
+use clap::Command;
+use std::sync::{Arc, Mutex, PoisonError};
+
+/// A change to the registered commands requested through a [CommandRegistry]
+pub(crate) enum Registration<Context, E, Output> {
+    Add(Box<Command>, Callback<Context, E, Output>),
+    #[cfg(feature = "async")]
+    AddAsync(Box<Command>, AsyncCallback<Context, E, Output>),
+    Remove(String),
+}
+
+/// Adds and removes commands from command callbacks, e.g. a `load` command registering
+/// the commands of a plugin. Clones share the same queue, so keep a clone in your context,
+/// pass another one to
+/// [Repl::with_command_registry](struct.Repl.html#method.with_command_registry) and
+/// register commands through it. They are added when the running command returns, so
+/// the next command in a chain can already use them.
+pub struct CommandRegistry<Context, E, Output = String> {
+    pending: Arc<Mutex<Vec<Registration<Context, E, Output>>>>,
+}
+
+impl<Context, E, Output> CommandRegistry<Context, E, Output> {
+    /// Create a registry without pending changes
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a command, replacing one with the same name
+    pub fn register(&self, command: Command, callback: Callback<Context, E, Output>) {
+        self.push(Registration::Add(Box::new(command), callback));
+    }
+
+    /// Add an async command, replacing one with the same name
+    #[cfg(feature = "async")]
+    pub fn register_async(&self, command: Command, callback: AsyncCallback<Context, E, Output>) {
+        self.push(Registration::AddAsync(Box::new(command), callback));
+    }
+
+    /// Remove the command `name`
+    pub fn unregister(&self, name: &str) {
+        self.push(Registration::Remove(name.to_string()));
+    }
+
+    /// The changes requested since the last call, oldest first
+    pub(crate) fn take(&self) -> Vec<Registration<Context, E, Output>> {
+        std::mem::take(&mut *self.pending.lock().unwrap_or_else(PoisonError::into_inner))
+    }
+
+    fn push(&self, registration: Registration<Context, E, Output>) {
+        self.pending
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(registration);
+    }
+}
+
+impl<Context, E, Output> Clone for CommandRegistry<Context, E, Output> {
+    fn clone(&self) -> Self {
+        CommandRegistry {
+            pending: Arc::clone(&self.pending),
+        }
+    }
+}
+
+impl<Context, E, Output> Default for CommandRegistry<Context, E, Output> {
+    fn default() -> Self {
+        CommandRegistry {
+            pending: Arc::default(),
+        }
+    }
+}
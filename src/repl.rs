@@ -1,91 +1,666 @@
-use crate::command::ReplCommand;
-use crate::completer::ReplCompleter;
+use crate::arithmetic::expand_arithmetic;
+use crate::cancel::CancellationToken;
+use crate::command::{CommandSetting, ReplCommand};
+use crate::completer::{
+    CompletionIndex, CompletionMatching, HelpCompleter, ReplCompleter, SharedIndex,
+};
+#[cfg(feature = "config")]
+use crate::config::Config;
+use crate::diff::unified_diff;
+use crate::editor::LineEditor;
 use crate::error::*;
+use crate::event::ReplEvent;
+use crate::expand::{expand_escapes, expand_session_variables, format_placeholders};
+use crate::help_export::{self, HelpFormat};
+use crate::highlighter::{ReplHighlighter, SharedHighlighter};
+use crate::hinter::CommandHinter;
+use crate::history::HistoryStore;
+use crate::interact::confirm;
+use crate::invocation::Invocation;
+use crate::jobs::{Job, Spawner};
+use crate::messages::Messages;
+use crate::mode::Scope;
+use crate::pager::{exceeds_screen, page, PagerMode};
+use crate::parsers::{parse_bool, parse_duration};
 use crate::prompt::ReplPrompt;
-use crate::{paint_green_bold, paint_yellow_bold, AfterCommandCallback, Callback};
+use crate::redirect::{self, Redirection};
+use crate::registry::{CommandRegistry, Registration};
+use crate::response::{Capture, ReplResponse};
+use crate::screen::clear_screen;
+use crate::script::{evaluate, parse_block, repetitions, Block, Body, ScriptLines};
+use crate::settings::{is_setting, on_off, setting_values, SETTINGS};
+use crate::stats::CommandStats;
+use crate::suggestions::did_you_mean;
+use crate::terminal::TerminalGuard;
+use crate::theme::Theme;
+#[cfg(feature = "async")]
+use crate::timer::delay;
+use crate::tokenizer::{
+    join_continuations, split_chain, split_redirect, split_words, strip_comments, Separator,
+};
+use crate::transcript::Transcript;
+use crate::validator::{is_complete, ReplValidator};
+use crate::variables::Variables;
+use crate::{
+    AfterCommandCallback, AfterCommandHookCallback, ArgCompleterCallback, BeforeCommandCallback,
+    Callback, CommandErrorHandler, EventListenerCallback, GuardCallback, HealthCheckCallback,
+    HealthStatus, HistoryFilterCallback, LinePreprocessorCallback, LoadContextCallback,
+    ModesCallback, OutputRenderer, ParserCallback, PlainRenderer, ReedlineConfigurator,
+    RightPromptCallback, SaveContextCallback, SessionCallback, SessionMessageCallback,
+    VisibleWhenCallback,
+};
 #[cfg(feature = "async")]
 use crate::{AsyncAfterCommandCallback, AsyncCallback};
-use clap::Command;
+use clap::{Arg, ArgAction, ArgMatches, Command};
+#[cfg(feature = "shell_completions")]
+use clap_complete::Shell;
 // use crossterm::event::{KeyCode, KeyModifiers};
-use nu_ansi_term::{Color, Style};
+use crossterm::terminal::size;
+use nu_ansi_term::Style;
+#[cfg(feature = "external_printer")]
+use reedline::ExternalPrinter;
 use reedline::{
-    self, default_emacs_keybindings, ColumnarMenu, DefaultHinter, DefaultValidator, Emacs,
-    ExampleHighlighter, FileBackedHistory, KeyCode, KeyModifiers, Keybindings, Reedline,
-    ReedlineEvent, ReedlineMenu, Signal,
+    self, default_emacs_keybindings, ColumnarMenu, Completer, DefaultHinter, EditCommand, Emacs,
+    Highlighter, Hinter, KeyCode, KeyModifiers, Keybindings, ListMenu, Menu, Reedline,
+    ReedlineEvent, ReedlineMenu, Signal, Validator,
 };
+use std::any::Any;
 use std::boxed::Box;
-use std::collections::HashMap;
+use std::cell::{Cell, RefCell};
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashMap, VecDeque};
 use std::fmt::Display;
-use std::path::PathBuf;
+use std::io::{IsTerminal, Write};
+use std::panic::AssertUnwindSafe;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+#[cfg(feature = "async")]
+use std::{future::Future, pin::Pin, task::Poll};
+use yansi::Paint;
 
-type ErrorHandler<Context, E> = fn(error: E, repl: &Repl<Context, E>) -> Result<()>;
+type ErrorHandler<Context, E, Output> =
+    Box<dyn FnMut(E, &Repl<Context, E, Output>) -> Result<()> + Send>;
 
-fn default_error_handler<Context, E: Display>(error: E, _repl: &Repl<Context, E>) -> Result<()> {
-    eprintln!("{}", error);
+fn default_error_handler<Context, E: Display, Output>(
+    error: E,
+    repl: &Repl<Context, E, Output>,
+) -> Result<()> {
+    if repl.error_tag {
+        let message = error.to_string();
+        let message = message.strip_prefix("Error: ").unwrap_or(&message);
+        repl.print_error(format!(
+            "{} {}",
+            repl.active_theme()
+                .error_style
+                .paint(&repl.messages.error_tag),
+            message
+        ));
+    } else {
+        repl.print_error(error);
+    }
     Ok(())
 }
 
+/// Whether colors should be used: `NO_COLOR` is not set and stdout is a terminal
+fn ansi_supported() -> bool {
+    let no_color = std::env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty());
+    !no_color && std::io::stdout().is_terminal()
+}
+
+/// Finds a command available in `mode` by its name or one of its aliases, commands of the
+/// active mode take precedence over global ones
+pub(crate) fn find_command<'a, Context, E, Output>(
+    commands: &'a HashMap<String, ReplCommand<Context, E, Output>>,
+    name: &str,
+    scope: &Scope,
+) -> Option<&'a ReplCommand<Context, E, Output>> {
+    find_command_key(commands, name, scope).map(|key| &commands[key])
+}
+
+/// The key `name` is stored under in `commands`, like [find_command]
+fn find_command_key<'a, Context, E, Output>(
+    commands: &'a HashMap<String, ReplCommand<Context, E, Output>>,
+    name: &str,
+    scope: &Scope,
+) -> Option<&'a String> {
+    let matches = |(_, definition): &(&String, &ReplCommand<Context, E, Output>)| {
+        definition.name == name
+            || definition
+                .command
+                .get_all_aliases()
+                .any(|alias| alias == name)
+    };
+    commands
+        .iter()
+        .filter(|(_, definition)| definition.mode.is_some() && definition.is_available(scope))
+        .find(matches)
+        .or_else(|| {
+            commands
+                .iter()
+                .filter(|(_, definition)| {
+                    definition.mode.is_none() && definition.is_available(scope)
+                })
+                .find(matches)
+        })
+        .map(|(key, _)| key)
+}
+
+/// Formats a `name  description` line of a help listing, wrapping the description into its
+/// column so it fits into `width`
+fn help_entry(name: &str, description: &str, name_width: usize, width: usize) -> String {
+    let indent = 4 + name_width + 2;
+    let column = width.saturating_sub(indent).max(20);
+    let mut lines: Vec<String> = vec![];
+    for word in description.split_whitespace() {
+        match lines.last_mut() {
+            Some(line) if line.len() + 1 + word.len() <= column => {
+                line.push(' ');
+                line.push_str(word);
+            }
+            _ => lines.push(word.to_string()),
+        }
+    }
+    let mut entry = format!("    {:width$}  ", name, width = name_width);
+    entry += &lines.join(&format!("\n{}", " ".repeat(indent)));
+    entry.trim_end().to_string() + "\n"
+}
+
+/// Formats the run time of a command like `350ms` or `1.42s`
+fn format_elapsed(elapsed: Duration) -> String {
+    if elapsed < Duration::from_secs(1) {
+        format!("{}ms", elapsed.as_millis())
+    } else {
+        format!("{:.2}s", elapsed.as_secs_f64())
+    }
+}
+
+/// A REPL mounted under a prefix of another REPL, hiding its context type
+trait Mount<E> {
+    fn description(&self) -> String;
+    fn visible_commands(&self) -> Vec<Command>;
+    fn execute(&mut self, command: &str, args: &[&str]) -> core::result::Result<(), E>;
+    fn help(&self, args: &[&str]) -> Result<()>;
+    fn swap_outputs(
+        &mut self,
+        output: &mut Box<dyn Write + Send>,
+        error_output: &mut Box<dyn Write + Send>,
+    );
+}
+
+impl<Context, E, Output> Mount<E> for Repl<Context, E, Output>
+where
+    E: Display + From<Error> + std::fmt::Debug,
+{
+    fn description(&self) -> String {
+        self.description.clone()
+    }
+
+    fn visible_commands(&self) -> Vec<Command> {
+        self.registered_commands()
+    }
+
+    fn execute(&mut self, command: &str, args: &[&str]) -> core::result::Result<(), E> {
+        self.handle_command(command, args)
+    }
+
+    fn help(&self, args: &[&str]) -> Result<()> {
+        self.show_help(args)
+    }
+
+    fn swap_outputs(
+        &mut self,
+        output: &mut Box<dyn Write + Send>,
+        error_output: &mut Box<dyn Write + Send>,
+    ) {
+        std::mem::swap(self.output.get_mut(), output);
+        std::mem::swap(self.error_output.get_mut(), error_output);
+    }
+}
+
+/// Maximum nesting depth of scripts run with the `source` builtin
+#[cfg(feature = "scripts")]
+const MAX_SOURCE_DEPTH: usize = 16;
+
+/// A script being executed, to attribute errors to their file and line
+struct InputSource {
+    name: String,
+    line: usize,
+}
+
+/// The path argument of the `source` builtin
+#[cfg(feature = "scripts")]
+fn source_path(args: &[&str]) -> Result<String> {
+    match args {
+        [path] => Ok(path.to_string()),
+        [] => Err(Error::MissingRequiredArgument(
+            "source".to_string(),
+            "path".to_string(),
+        )),
+        _ => Err(Error::TooManyArguments("source".to_string(), 1)),
+    }
+}
+
+/// Options of a `wait-for` builtin invocation
+struct WaitFor {
+    command: String,
+    timeout: Duration,
+    interval: Duration,
+}
+
+/// Name of the menu showing help for the command being typed, opened with F1
+const HELP_MENU: &str = "help_menu";
+
+/// Session variable with the outcome of the last command of a script, 0 if it succeeded
+const STATUS_VARIABLE: &str = "status";
+
+/// Sentinel line terminating a `paste` block if none is given
+const DEFAULT_PASTE_SENTINEL: &str = "EOF";
+
+/// State of an in-progress `paste` builtin
+enum PasteState {
+    /// Collecting lines until the sentinel line is entered
+    Collecting {
+        sentinel: String,
+        lines: Vec<String>,
+    },
+    /// Waiting for the user to confirm execution of the collected lines
+    Confirming(Vec<String>),
+}
+
+/// What an entered line runs once its continuations are joined
+enum LineWork {
+    /// Several complete lines pasted for review, run like a script
+    Block(String),
+    /// The lines collected by the `paste` builtin, confirmed to run one by one
+    Pasted(Vec<String>),
+    /// Commands chained with `;` and `&&`
+    Chain(String),
+    /// Nothing, e.g. a line the `paste` builtin collected
+    Nothing,
+}
+
+/// A line fed to the REPL, with the outputs it replaced while capturing its own
+struct FedLine {
+    output: Capture,
+    errors: Capture,
+    replaced: (Box<dyn Write + Send>, Box<dyn Write + Send>),
+    terminal_output: bool,
+    redirects: bool,
+    recorded: bool,
+}
+
+/// A line entered at the prompt, being executed
+struct EnteredLine {
+    recorded: bool,
+    empty: bool,
+    started: Instant,
+}
+
+/// What the REPL does when CTRL+C is pressed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CtrlCAction {
+    /// Discard the current input line and show a new prompt
+    ClearLine,
+    /// Do nothing, the current input line is kept
+    Ignore,
+    /// Stop the REPL
+    Quit,
+}
+
+/// What the REPL does when CTRL+D is pressed on an empty line
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CtrlDAction {
+    /// Do nothing
+    Ignore,
+    /// Stop the REPL
+    Quit,
+}
+
+/// Order of the commands in help and completion
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HelpOrdering {
+    /// In the order the commands were added
+    Insertion,
+    /// Sorted by name
+    Alphabetical,
+}
+
+/// Layout of the completion menu opened with Tab
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MenuKind {
+    /// Completions in columns
+    Columnar,
+    /// One completion per line with its description
+    List,
+}
+
+/// What happens with several lines pasted at once
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PastePolicy {
+    /// Each pasted line runs as it arrives, as if it was typed and submitted
+    Execute,
+    /// The pasted lines land in the buffer as a whole to be reviewed and edited, Enter runs
+    /// them one after another
+    Review,
+}
+
+/// What happens when the history file can't be opened, e.g. in a read-only directory
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HistoryMode {
+    /// The REPL doesn't start, `run` returns the error
+    Strict,
+    /// The error handler reports the problem and the history is kept in memory only
+    BestEffort,
+}
+
+/// Number of executed commands kept for [Repl::rerun](struct.Repl.html#method.rerun)
+const INVOCATION_CAPACITY: usize = 100;
+
+/// How long `serve` waits for the next line of a client before disconnecting it
+#[cfg(feature = "serve")]
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Command output retained for the `diff` builtin
+struct RetainedOutput {
+    number: usize,
+    command: String,
+    output: String,
+}
+
 /// Main REPL struct
-pub struct Repl<Context, E: Display> {
+pub struct Repl<Context, E: Display, Output = String> {
     name: String,
     banner: Option<String>,
+    banner_callback: Option<SessionMessageCallback<Context>>,
+    goodbye_callback: Option<SessionMessageCallback<Context>>,
     version: String,
     description: String,
     prompt: ReplPrompt,
     after_command_callback: Option<AfterCommandCallback<Context, E>>,
     #[cfg(feature = "async")]
     after_command_callback_async: Option<AsyncAfterCommandCallback<Context, E>>,
-    commands: HashMap<String, ReplCommand<Context, E>>,
-    history: Option<PathBuf>,
-    history_capacity: Option<usize>,
-    context: Context,
+    pub(crate) commands: HashMap<String, ReplCommand<Context, E, Output>>,
+    command_order: Vec<String>,
+    command_settings: Vec<(String, CommandSetting<Context, E>)>,
+    help_ordering: HelpOrdering,
+    pub(crate) history: Option<PathBuf>,
+    pub(crate) history_capacity: Option<usize>,
+    pub(crate) history_mode: HistoryMode,
+    pub(crate) context: Context,
+    context_name: String,
+    contexts: BTreeMap<String, Context>,
     keybindings: Keybindings,
-    hinter_style: Style,
+    theme: Theme,
+    pub(crate) ansi: bool,
+    formatted_prompt: Option<String>,
     hinter_enabled: bool,
+    hinter: Option<Box<dyn Hinter>>,
+    highlighter: Option<SharedHighlighter>,
+    validator: Option<Box<dyn Validator>>,
+    command_hints: bool,
     quick_completions: bool,
     partial_completions: bool,
-    stop_on_ctrl_c: bool,
-    stop_on_ctrl_d: bool,
-    error_handler: ErrorHandler<Context, E>,
+    menu: MenuKind,
+    menu_columns: Option<u16>,
+    menu_column_padding: Option<usize>,
+    paste_policy: PastePolicy,
+    catch_panics: bool,
+    completion_matching: CompletionMatching,
+    interactive: Option<bool>,
+    reedline_configurator: Option<ReedlineConfigurator>,
+    ctrl_c_action: CtrlCAction,
+    ctrl_d_action: CtrlDAction,
+    error_handler: Option<ErrorHandler<Context, E, Output>>,
+    paste_confirmation: bool,
+    paste: Option<PasteState>,
+    pub(crate) arithmetic: bool,
+    quit_commands: Vec<String>,
+    pub(crate) quit: bool,
+    clear_command: bool,
+    line_preprocessor: Option<LinePreprocessorCallback<Context>>,
+    before_command_callback: Option<BeforeCommandCallback<Context>>,
+    after_command_hook: Option<AfterCommandHookCallback<Context>>,
+    pub(crate) on_start_callback: Option<SessionCallback<Context>>,
+    pub(crate) on_exit_callback: Option<SessionCallback<Context>>,
+    context_persistence: Option<(
+        SaveContextCallback<Context>,
+        LoadContextCallback<Context>,
+        PathBuf,
+    )>,
+    init_script: Option<PathBuf>,
+    config_error: Option<Error>,
+    init_commands: Vec<String>,
+    output: RefCell<Box<dyn Write + Send>>,
+    pub(crate) error_output: RefCell<Box<dyn Write + Send>>,
+    pub(crate) terminal_output: bool,
+    pager: Option<PagerMode>,
+    timing: bool,
+    transcript_path: Option<PathBuf>,
+    help_topics: Vec<(String, String)>,
+    help_width: Option<usize>,
+    help_command_name: String,
+    messages: Messages,
+    event_listener: Option<EventListenerCallback>,
+    transcript: RefCell<Option<Transcript>>,
+    completion_index: SharedIndex,
+    arguments_rejected: Cell<bool>,
+    /// Whether a command of the current line failed, also when the chain went on
+    line_failed: Cell<bool>,
+    pub(crate) write_error: RefCell<Option<std::io::Error>>,
+    pub(crate) served: bool,
+    #[cfg(feature = "serve")]
+    pub(crate) client_timeout: Option<Duration>,
+    stats: BTreeMap<String, CommandStats>,
+    pub(crate) history_lines: VecDeque<String>,
+    pub(crate) history_store: Option<HistoryStore>,
+    outputs: VecDeque<RetainedOutput>,
+    output_count: usize,
+    output_buffer_capacity: usize,
+    health_check: Option<HealthCheckCallback<Context, E>>,
+    health_check_strict: bool,
+    invocations: VecDeque<Invocation>,
+    invocation_count: usize,
+    output_spacing: usize,
+    pub(crate) infer_choices: bool,
+    pub(crate) expand_paths: bool,
+    prompt_missing_args: bool,
+    shell_escape: Option<char>,
+    pub(crate) redirects: bool,
+    command_prefixes: bool,
+    comments: bool,
+    error_tag: bool,
+    echo_failed_command: bool,
+    confirmations: bool,
+    current_line: String,
+    pub(crate) started: Option<Instant>,
+    sources: Vec<InputSource>,
+    modes: Option<ModesCallback<Context>>,
+    subrepls: HashMap<String, String>,
+    right_prompt: Option<RightPromptCallback<Context>>,
+    status_segment: bool,
+    last_status: Option<(bool, Duration)>,
+    transient_prompt: Option<String>,
+    mounts: HashMap<String, Box<dyn Mount<E> + Send>>,
+    renderer: Box<dyn OutputRenderer<Output> + Send>,
+    variables: Variables,
+    pub(crate) shared_history: bool,
+    pub(crate) history_filter: Option<HistoryFilterCallback>,
+    pub(crate) cancellation: CancellationToken,
+    registry: CommandRegistry<Context, E, Output>,
+    plugin_commands: Vec<String>,
+    pub(crate) spawner: Option<Spawner<Context, E, Output>>,
+    pub(crate) jobs: Vec<Job<E, Output>>,
+    pub(crate) job_count: usize,
+    #[cfg(feature = "external_printer")]
+    pub(crate) printer: ExternalPrinter<String>,
 }
 
-impl<Context, E> Repl<Context, E>
+impl<Context, E, Output> Repl<Context, E, Output>
 where
     E: Display + From<Error> + std::fmt::Debug,
 {
     /// Create a new Repl with the given context's initial value.
-    pub fn new(context: Context) -> Self {
+    pub fn new(context: Context) -> Self
+    where
+        Output: Display + 'static,
+    {
+        Self::new_with_renderer(context, PlainRenderer)
+    }
+
+    /// Create a new Repl with the given context's initial value whose command output is
+    /// rendered by `renderer`, for output types that don't implement `Display`
+    pub fn new_with_renderer(
+        context: Context,
+        renderer: impl OutputRenderer<Output> + Send + 'static,
+    ) -> Self {
         let name = String::from("repl");
-        let style = Style::new().italic().fg(Color::LightGray);
+        let theme = Theme::default();
+        let ansi = ansi_supported();
         let mut keybindings = default_emacs_keybindings();
         keybindings.add_binding(
             KeyModifiers::NONE,
             KeyCode::Tab,
             ReedlineEvent::Menu("completion_menu".to_string()),
         );
-        let prompt = ReplPrompt::new(&paint_green_bold(&format!("{}> ", name)));
+        keybindings.add_binding(
+            KeyModifiers::NONE,
+            KeyCode::F(1),
+            ReedlineEvent::Menu(HELP_MENU.to_string()),
+        );
+        keybindings.add_binding(
+            KeyModifiers::ALT,
+            KeyCode::Enter,
+            ReedlineEvent::Edit(vec![EditCommand::InsertNewline]),
+        );
+        let formatted_prompt = format!("{}> ", name);
+        let prompt_style = if ansi {
+            theme.prompt_style
+        } else {
+            Style::new()
+        };
+        let prompt = ReplPrompt::new(&prompt_style.paint(&formatted_prompt).to_string());
 
         Self {
             name,
             banner: None,
+            banner_callback: None,
+            goodbye_callback: None,
             version: String::new(),
             description: String::new(),
             commands: HashMap::new(),
+            command_order: vec![],
+            command_settings: vec![],
+            help_ordering: HelpOrdering::Alphabetical,
             history: None,
             history_capacity: None,
+            history_mode: HistoryMode::BestEffort,
             after_command_callback: None,
             #[cfg(feature = "async")]
             after_command_callback_async: None,
             quick_completions: true,
             partial_completions: false,
+            menu: MenuKind::Columnar,
+            menu_columns: None,
+            menu_column_padding: None,
+            paste_policy: PastePolicy::Execute,
+            catch_panics: false,
+            completion_matching: CompletionMatching::Prefix,
+            interactive: None,
+            reedline_configurator: None,
             hinter_enabled: true,
-            hinter_style: style,
+            hinter: None,
+            highlighter: None,
+            validator: None,
+            command_hints: false,
+            theme,
+            ansi,
+            formatted_prompt: Some(formatted_prompt),
             prompt,
             context,
+            context_name: "default".to_string(),
+            contexts: BTreeMap::new(),
             keybindings,
-            stop_on_ctrl_c: false,
-            stop_on_ctrl_d: true,
-            error_handler: default_error_handler,
+            ctrl_c_action: CtrlCAction::ClearLine,
+            ctrl_d_action: CtrlDAction::Quit,
+            error_handler: None,
+            paste_confirmation: true,
+            paste: None,
+            arithmetic: false,
+            quit_commands: vec!["quit".to_string(), "exit".to_string()],
+            quit: false,
+            clear_command: true,
+            line_preprocessor: None,
+            before_command_callback: None,
+            after_command_hook: None,
+            on_start_callback: None,
+            on_exit_callback: None,
+            context_persistence: None,
+            init_script: None,
+            config_error: None,
+            init_commands: vec![],
+            output: RefCell::new(Box::new(std::io::stdout())),
+            error_output: RefCell::new(Box::new(std::io::stderr())),
+            terminal_output: true,
+            pager: None,
+            timing: false,
+            transcript_path: None,
+            help_topics: vec![],
+            help_width: None,
+            help_command_name: "help".to_string(),
+            messages: Messages::default(),
+            event_listener: None,
+            transcript: RefCell::new(None),
+            completion_index: Arc::new(Mutex::new(None)),
+            arguments_rejected: Cell::new(false),
+            line_failed: Cell::new(false),
+            write_error: RefCell::new(None),
+            served: false,
+            #[cfg(feature = "serve")]
+            client_timeout: Some(CLIENT_TIMEOUT),
+            stats: BTreeMap::new(),
+            history_lines: VecDeque::new(),
+            history_store: None,
+            outputs: VecDeque::new(),
+            output_count: 0,
+            output_buffer_capacity: 100,
+            health_check: None,
+            health_check_strict: false,
+            invocations: VecDeque::new(),
+            invocation_count: 0,
+            output_spacing: 0,
+            infer_choices: false,
+            expand_paths: true,
+            prompt_missing_args: false,
+            shell_escape: None,
+            redirects: false,
+            command_prefixes: false,
+            comments: true,
+            error_tag: false,
+            echo_failed_command: false,
+            confirmations: true,
+            current_line: String::new(),
+            started: None,
+            sources: vec![],
+            modes: None,
+            subrepls: HashMap::new(),
+            right_prompt: None,
+            status_segment: false,
+            last_status: None,
+            transient_prompt: None,
+            mounts: HashMap::new(),
+            renderer: Box::new(renderer),
+            variables: Variables::new(),
+            shared_history: false,
+            history_filter: None,
+            cancellation: CancellationToken::new(),
+            registry: CommandRegistry::new(),
+            plugin_commands: vec![],
+            spawner: None,
+            jobs: vec![],
+            job_count: 0,
+            #[cfg(feature = "external_printer")]
+            printer: ExternalPrinter::default(),
         }
     }
 
@@ -102,6 +677,23 @@ where
         self
     }
 
+    /// Give your Repl a banner computed from the context when it starts running, e.g. with
+    /// the version of the connected server. It is printed after the banner of `with_banner`,
+    /// once the context is restored and the `with_on_start` callback ran.
+    pub fn with_banner_fn(mut self, callback: SessionMessageCallback<Context>) -> Self {
+        self.banner_callback = Some(callback);
+
+        self
+    }
+
+    /// Give your Repl a message computed from the context which is printed when it stops
+    /// running, e.g. with the number of unsaved changes
+    pub fn with_goodbye_fn(mut self, callback: SessionMessageCallback<Context>) -> Self {
+        self.goodbye_callback = Some(callback);
+
+        self
+    }
+
     /// Give your Repl a version. This is used in the help summary for the Repl.
     pub fn with_version(mut self, version: &str) -> Self {
         self.version = version.to_string();
@@ -134,6 +726,93 @@ where
         self
     }
 
+    /// Give your REPL a callback which is called with the command name before every command
+    pub fn with_before_command(mut self, callback: BeforeCommandCallback<Context>) -> Self {
+        self.before_command_callback = Some(callback);
+
+        self
+    }
+
+    /// Give your REPL a callback which is called with the command name, its output and how
+    /// long it ran after every command. The output is `None` if the command printed nothing
+    /// or failed.
+    pub fn with_after_command(mut self, callback: AfterCommandHookCallback<Context>) -> Self {
+        self.after_command_hook = Some(callback);
+
+        self
+    }
+
+    /// Give your REPL a callback which is called once when it starts running, after the banner
+    pub fn with_on_start(mut self, callback: SessionCallback<Context>) -> Self {
+        self.on_start_callback = Some(callback);
+
+        self
+    }
+
+    /// Give your REPL a callback which is called once when it stops running, e.g. to save
+    /// the context
+    pub fn with_on_exit(mut self, callback: SessionCallback<Context>) -> Self {
+        self.on_exit_callback = Some(callback);
+
+        self
+    }
+
+    /// Save the context to `path` with `save` when the session ends and restore it with
+    /// `load` when the next one starts, so users pick up where they left off. Use serde or
+    /// any other format, e.g. `|context| Ok(serde_json::to_string(context)?)`. A snapshot
+    /// that can't be loaded is reported and the session starts with the initial context.
+    pub fn with_context_persistence<P: AsRef<Path>>(
+        mut self,
+        save: SaveContextCallback<Context>,
+        load: LoadContextCallback<Context>,
+        path: P,
+    ) -> Self {
+        self.context_persistence = Some((save, load, path.as_ref().to_path_buf()));
+
+        self
+    }
+
+    /// Run the lines of the script at `path` before the first prompt, like a shell's
+    /// `.bashrc`, e.g. to preload state each session. A missing script is skipped, errors
+    /// are passed to the error handler with the script name and line.
+    pub fn with_init_script<P: AsRef<Path>>(mut self, path: P) -> Self {
+        self.init_script = Some(path.as_ref().to_path_buf());
+
+        self
+    }
+
+    /// Run `commands` before the first prompt, after the init script
+    pub fn with_init_commands(mut self, commands: Vec<String>) -> Self {
+        self.init_commands = commands;
+
+        self
+    }
+
+    /// Give your REPL health checks which run before the first prompt on the restored
+    /// context, their results are printed beneath the banner
+    pub fn with_health_check(mut self, callback: HealthCheckCallback<Context, E>) -> Self {
+        self.health_check = Some(callback);
+
+        self
+    }
+
+    /// Refuse to start the REPL if a health check fails (Default: false)
+    pub fn with_health_check_strict(mut self, strict: bool) -> Self {
+        self.health_check_strict = strict;
+
+        self
+    }
+
+    /// Give your REPL a callback which rewrites every input line before it is tokenized,
+    /// e.g. to expand application specific shorthands, macros or templates. It sees typed,
+    /// pasted and script lines after comments are stripped and before `!!` history
+    /// references and `$variables` are expanded.
+    pub fn with_line_preprocessor(mut self, callback: LinePreprocessorCallback<Context>) -> Self {
+        self.line_preprocessor = Some(callback);
+
+        self
+    }
+
     /// Give your Repl a file based history saved at history_path
     pub fn with_history(mut self, history_path: PathBuf, capacity: usize) -> Self {
         self.history = Some(history_path);
@@ -142,457 +821,3982 @@ where
         self
     }
 
+    /// Choose what happens if the history file can't be opened (Default:
+    /// `HistoryMode::BestEffort`). Missing directories of the file are created either way.
+    pub fn with_history_mode(mut self, history_mode: HistoryMode) -> Self {
+        self.history_mode = history_mode;
+
+        self
+    }
+
+    /// Write each line to the history file right after it was entered instead of when the
+    /// REPL stops, so concurrent sessions sharing the file merge their commands instead of
+    /// the last one to exit overwriting the others. The file is locked while it is written,
+    /// and lines the other sessions added are picked up for history search.
+    pub fn with_shared_history(mut self, shared_history: bool) -> Self {
+        self.shared_history = shared_history;
+
+        self
+    }
+
+    /// Decide which lines are kept in the history file with `filter`, called after each
+    /// line ran with the line and whether it succeeded, e.g. to drop failed commands or
+    /// one-character lines. Rejected lines are left out of the history like lines starting
+    /// with a space.
+    pub fn with_history_filter(mut self, filter: HistoryFilterCallback) -> Self {
+        self.history_filter = Some(filter);
+
+        self
+    }
+
+    /// Apply the settings of the TOML config file at `path`, so end users can customize the
+    /// REPL: prompt, history file and size, pager, colors, timing, confirmations, theme colors
+    /// and keybindings running command lines. A missing file is ignored, an invalid one makes
+    /// `run` fail. Builder calls after this one take precedence over the file.
+    ///
+    /// ```toml
+    /// prompt = "db"
+    /// history = "~/.db_history"
+    /// history_size = 5000
+    /// pager = "less -R"
+    /// color = true
+    /// timing = false
+    /// confirm = true
+    ///
+    /// [theme]
+    /// command = "light_green"
+    /// error = "#ff5f5f"
+    ///
+    /// [keybindings]
+    /// "ctrl-s" = "status"
+    /// ```
+    #[cfg(feature = "config")]
+    pub fn with_config_file<P: AsRef<Path>>(mut self, path: P) -> Self {
+        let config = match Config::load(path.as_ref()) {
+            Ok(Some(config)) => config,
+            Ok(None) => return self,
+            Err(err) => {
+                self.config_error = Some(err);
+                return self;
+            }
+        };
+        if let Some(color) = config.color {
+            self.ansi = color;
+        }
+        self.theme = config.theme(self.theme);
+        if let Some((path, capacity)) = config.history {
+            self = self.with_history(path, capacity);
+        }
+        if let Some(pager) = config.pager {
+            self.pager = pager;
+        }
+        if let Some(timing) = config.timing {
+            self.timing = timing;
+        }
+        if let Some(confirm) = config.confirm {
+            self.confirmations = confirm;
+        }
+        for (modifiers, key_code, command) in &config.keybindings {
+            self.keybindings.add_binding(
+                *modifiers,
+                *key_code,
+                ReedlineEvent::ExecuteHostCommand(command.clone()),
+            );
+        }
+        match &config.prompt {
+            Some(prompt) => self.with_formatted_prompt(prompt),
+            None => self.reformat_prompt(),
+        }
+    }
+
     /// Give your Repl a custom prompt. The default prompt is the Repl name, followed by
     /// a `>`, all in green and bold, followed by a space:
     ///
     /// &Paint::green(format!("{}> ", name)).bold().to_string()
     pub fn with_prompt(mut self, prompt: &str) -> Self {
         self.prompt.update_prefix(prompt);
+        self.formatted_prompt = None;
 
         self
     }
 
-    /// Give your Repl a custom prompt while applying green/bold formatting automatically
+    /// Give your Repl a custom prompt while applying the prompt style of the theme
+    /// (green/bold by default) automatically
     ///
     /// &Paint::green(format!("{}> ", name)).bold().to_string()
     pub fn with_formatted_prompt(mut self, prompt: &str) -> Self {
-        self.prompt.update_prefix(prompt);
+        self.prompt
+            .update_prefix(&self.active_theme().prompt_style.paint(prompt).to_string());
+        self.formatted_prompt = Some(prompt.to_string());
 
         self
     }
 
-    /// Pass in a custom error handler. This is really only for testing - the default
-    /// error handler simply prints the error to stderr and then returns
-    pub fn with_error_handler(mut self, handler: ErrorHandler<Context, E>) -> Self {
-        self.error_handler = handler;
+    /// Use the colors and styles of `theme`, e.g. `Theme::no_color()`
+    pub fn with_theme(mut self, theme: Theme) -> Self {
+        self.theme = theme;
+        self.reformat_prompt()
+    }
+
+    /// Turn on/off colors and styles. By default they are off if the `NO_COLOR`
+    /// environment variable is set or stdout is not a terminal.
+    pub fn with_ansi(mut self, ansi: bool) -> Self {
+        self.ansi = ansi;
+        self.reformat_prompt()
+    }
+
+    /// Applies the active theme to a prompt set with `with_formatted_prompt`
+    fn reformat_prompt(mut self) -> Self {
+        match self.formatted_prompt.take() {
+            Some(prompt) => self.with_formatted_prompt(&prompt),
+            None => self,
+        }
+    }
+
+    /// Give your Repl a custom continuation prompt, shown in front of every further line
+    /// of an input continued with a trailing `\` or an unclosed `if` or `repeat` block.
+    /// The default is `... `
+    pub fn with_continuation_prompt(mut self, prompt: &str) -> Self {
+        self.prompt.update_multiline_indicator(prompt);
 
         self
     }
 
-    /// Turn on/off if REPL run is stopped on CTRG+C (Default: false)
-    pub fn with_stop_on_ctrl_c(mut self, stop_on_ctrl_c: bool) -> Self {
-        self.stop_on_ctrl_c = stop_on_ctrl_c;
+    /// Give your Repl a custom prompt indicator, shown between the prompt and the input
+    pub fn with_prompt_indicator(mut self, indicator: &str) -> Self {
+        self.prompt.update_indicator(indicator);
 
         self
     }
 
-    /// Turn on/off if REPL run is stopped on CTRG+D (Default: true)
-    pub fn with_stop_on_ctrl_d(mut self, stop_on_ctrl_d: bool) -> Self {
-        self.stop_on_ctrl_d = stop_on_ctrl_d;
+    /// Give your Repl a right prompt, e.g. the time or the connection status. The
+    /// callback renders it from the context before every prompt.
+    pub fn with_right_prompt(mut self, callback: RightPromptCallback<Context>) -> Self {
+        self.right_prompt = Some(callback);
 
         self
     }
 
-    /// Turn on quick completions. These completions will auto-select if the completer
-    /// ever narrows down to a single entry.
-    pub fn with_quick_completions(mut self, quick_completions: bool) -> Self {
-        self.quick_completions = quick_completions;
+    /// Show whether the last line succeeded and how long it took in front of the prompt,
+    /// e.g. `✔ 12ms` or `✘ 1.20s`, styled with the `status_*` styles of the theme
+    /// (Default: false)
+    pub fn with_status_segment(mut self, status_segment: bool) -> Self {
+        self.status_segment = status_segment;
 
         self
     }
 
-    /// Turn on partial completions. These completions will fill the buffer with the
-    /// smallest common string from all the options
-    pub fn with_partial_completions(mut self, partial_completions: bool) -> Self {
-        self.partial_completions = partial_completions;
+    /// Collapse the prompt of submitted lines to `prompt`, e.g. `> `, keeping the
+    /// scrollback of long sessions compact
+    pub fn with_transient_prompt(mut self, prompt: &str) -> Self {
+        self.transient_prompt = Some(prompt.to_string());
 
         self
     }
 
-    /// Sets the style for reedline's fish-style history autosuggestions
-    ///
-    /// Default: `nu_ansi_term::Style::new().italic().fg(nu_ansi_term::Color::LightGray)`
-    ///
-    pub fn with_hinter_style(mut self, style: Style) -> Self {
-        self.hinter_style = style;
+    /// Print `lines` blank lines after each executed command line before the next prompt.
+    /// The default is 0
+    pub fn with_output_spacing(mut self, lines: usize) -> Self {
+        self.output_spacing = lines;
 
         self
     }
 
-    /// Disables reedline's fish-style history autosuggestions
-    pub fn with_hinter_disabled(mut self) -> Self {
-        self.hinter_enabled = false;
+    /// Accept a value that is the prefix of exactly one of the possible values of an argument
+    /// as that value, e.g. `deploy stag` for `deploy staging`
+    pub fn with_choice_prefixes(mut self, infer_choices: bool) -> Self {
+        self.infer_choices = infer_choices;
 
         self
     }
 
-    /// Adds a reedline keybinding
-    ///
-    /// # Panics
-    ///
-    /// If `comamnd` is an empty [`ReedlineEvent::UntilFound`]
-    pub fn with_keybinding(
-        mut self,
-        modifier: KeyModifiers,
-        key_code: KeyCode,
-        command: ReedlineEvent,
-    ) -> Self {
-        self.keybindings.add_binding(modifier, key_code, command);
+    /// Expand `~` and globs like `*.csv` in the values of path arguments, i.e. arguments
+    /// with a path `ValueHint` or a `PathBuf` value parser, like a shell does. On by default.
+    pub fn with_path_expansion(mut self, expand_paths: bool) -> Self {
+        self.expand_paths = expand_paths;
 
         self
     }
 
-    /// Find a keybinding based on the modifier and keycode
-    pub fn find_keybinding(
-        &self,
-        modifier: KeyModifiers,
-        key_code: KeyCode,
-    ) -> Option<ReedlineEvent> {
-        self.keybindings.find_binding(modifier, key_code)
+    /// Ask for the values of required arguments missing from a command line, one
+    /// `name: ` prompt each, instead of failing with a usage error. An empty answer gives
+    /// up and shows the error. Only done when the REPL talks to a terminal.
+    pub fn with_prompt_for_missing_args(mut self, prompt_missing_args: bool) -> Self {
+        self.prompt_missing_args = prompt_missing_args;
+
+        self
     }
 
-    /// Get assigned keybindings
-    pub fn get_keybindings(&self) -> HashMap<(KeyModifiers, KeyCode), ReedlineEvent> {
-        // keybindings.get_keybindings() cannot be returned directly because KeyCombination is not visible
-        self.keybindings
-            .get_keybindings()
-            .iter()
-            .map(|(key, value)| ((key.modifier, key.key_code), value.clone()))
-            .collect()
+    /// Run lines starting with `escape` with the system shell, e.g. `!ls -la` with
+    /// `with_shell_escape('!')`. The rest of the line goes to the shell as is, including
+    /// `|`, `;` and `&&`. With `!` it takes the place of the history expansion of `!!` and
+    /// `!n`.
+    pub fn with_shell_escape(mut self, escape: char) -> Self {
+        self.shell_escape = Some(escape);
+
+        self
     }
 
-    /// Remove a keybinding
-    ///
-    /// Returns `Some(ReedlineEvent)` if the keycombination was previously bound to a particular [`ReedlineEvent`]
-    pub fn without_keybinding(mut self, modifier: KeyModifiers, key_code: KeyCode) -> Self {
-        self.keybindings.remove_binding(modifier, key_code);
+    /// Send the output of a command to a program with `report | grep error` or to a file
+    /// with `report > out.txt` and `report >> log.txt`. Quoted or escaped `|` and `>` stay
+    /// literal. Off by default, as it changes how unquoted `|` and `>` in arguments parse
+    /// and gives users the system shell and the file system. Lines of `feed_line` and
+    /// `serve` are never redirected.
+    pub fn with_redirects(mut self, redirects: bool) -> Self {
+        self.redirects = redirects;
 
         self
     }
 
-    /// Add a command to your REPL
-    pub fn with_command(mut self, command: Command, callback: Callback<Context, E>) -> Self {
-        let name = command.get_name().to_string();
-        self.commands
-            .insert(name.clone(), ReplCommand::new(&name, command, callback));
+    /// Disconnect a client of `serve` that sends no line for `timeout` (Default: 5 minutes),
+    /// so the next client isn't kept waiting by an idle one. `None` waits forever.
+    #[cfg(feature = "serve")]
+    pub fn with_client_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.client_timeout = timeout;
+
         self
     }
 
-    /// Add a command to your REPL
-    #[cfg(feature = "async")]
-    pub fn with_command_async(
-        mut self,
-        command: Command,
-        callback: AsyncCallback<Context, E>,
-    ) -> Self {
-        let name = command.get_name().to_string();
-        self.commands.insert(
-            name.clone(),
-            ReplCommand::new_async(&name, command, callback),
-        );
+    /// Run the command whose name starts with the typed word if it's the only one, e.g.
+    /// `conf` for `configure`. A prefix of several commands is an ambiguous command error.
+    pub fn with_command_prefixes(mut self, command_prefixes: bool) -> Self {
+        self.command_prefixes = command_prefixes;
+
         self
     }
 
-    fn show_help(&self, args: &[&str]) -> Result<()> {
-        if args.is_empty() {
-            let mut app = Command::new("app");
+    /// Ignore comments, from a `#` at the start of a line or after whitespace to the end of
+    /// the line, in input and scripts (Default: `true`). Disable it if arguments start
+    /// with `#`, like colors.
+    pub fn with_comments(mut self, comments: bool) -> Self {
+        self.comments = comments;
 
-            for (_, com) in self.commands.iter() {
-                app = app.subcommand(com.command.clone());
-            }
-            let mut help_bytes: Vec<u8> = Vec::new();
-            app.write_help(&mut help_bytes)
-                .expect("failed to print help");
-            let mut help_string =
-                String::from_utf8(help_bytes).expect("Help message was invalid UTF8");
-            let marker = "SUBCOMMANDS:";
-            if let Some(marker_pos) = help_string.find(marker) {
-                help_string = paint_yellow_bold("COMMANDS:")
-                    + &help_string[(marker_pos + marker.len())..help_string.len()];
-            }
-            let header = format!(
-                "{} {}\n{}\n",
-                paint_green_bold(&self.name),
-                self.version,
-                self.description
-            );
-            println!("{}", header);
-            println!("{}", help_string);
-        } else if let Some((_, subcommand)) = self
-            .commands
-            .iter()
-            .find(|(name, _)| name.as_str() == args[0])
-        {
-            subcommand
-                .command
-                .clone()
-                .print_help()
-                .expect("failed to print help");
-            println!();
-        } else {
-            eprintln!("Help not found for command '{}'", args[0]);
-        }
-        Ok(())
+        self
     }
 
-    fn handle_command(&mut self, command: &str, args: &[&str]) -> core::result::Result<(), E> {
-        match self.commands.get(command) {
+    /// Prefix errors printed by the default error handler with a red `error:` tag
+    pub fn with_error_tag(mut self, error_tag: bool) -> Self {
+        self.error_tag = error_tag;
+
+        self
+    }
+
+    /// Print the command line that failed above its error
+    pub fn with_echo_failed_command(mut self, echo: bool) -> Self {
+        self.echo_failed_command = echo;
+
+        self
+    }
+
+    /// Write command output, help and the banner to `output` instead of stdout, e.g. to
+    /// capture it in tests or send it to a log
+    pub fn with_output(mut self, output: impl Write + Send + 'static) -> Self {
+        self.output = RefCell::new(Box::new(output));
+        self.terminal_output = false;
+
+        self
+    }
+
+    /// Print how long each command took after its output, e.g. `took 1.42s`. Can also be
+    /// switched at runtime with `set timing on` and `set timing off`.
+    pub fn with_timing(mut self, timing: bool) -> Self {
+        self.timing = timing;
+
+        self
+    }
+
+    /// Append every entered line, output and error with a timestamp to the transcript at
+    /// `path`, e.g. to reproduce what a user did. Lines kept out of the history, like ones
+    /// with sensitive arguments, are not recorded.
+    pub fn with_transcript<P: AsRef<Path>>(mut self, path: P) -> Self {
+        self.transcript_path = Some(path.as_ref().to_path_buf());
+
+        self
+    }
+
+    /// Add a help topic beyond the commands, e.g. on the expression syntax. Topics are
+    /// listed by `help` with the first line of their text and shown with `help <name>`.
+    pub fn with_help_topic(mut self, name: &str, text: &str) -> Self {
+        self.help_topics.push((name.to_string(), text.to_string()));
+
+        self
+    }
+
+    /// Wrap help at `width` columns instead of the terminal width, e.g. for output that
+    /// is not a terminal
+    pub fn with_help_width(mut self, width: usize) -> Self {
+        self.help_width = Some(width);
+
+        self
+    }
+
+    /// Name of the built-in help command, `help` by default
+    pub fn with_help_command_name(mut self, name: &str) -> Self {
+        self.help_command_name = name.to_string();
+
+        self
+    }
+
+    /// Replace the texts of the built-in messages, e.g. with translations
+    pub fn with_messages(mut self, messages: Messages) -> Self {
+        self.messages = messages;
+
+        self
+    }
+
+    /// Give your REPL a listener for session and command events, e.g. to feed telemetry or
+    /// an audit log
+    pub fn with_event_listener(mut self, listener: EventListenerCallback) -> Self {
+        self.event_listener = Some(listener);
+
+        self
+    }
+
+    /// Show outputs and help listings longer than the terminal in a pager, either the
+    /// built-in one or an external program like `less -R`
+    pub fn with_pager(mut self, pager: PagerMode) -> Self {
+        self.pager = Some(pager);
+
+        self
+    }
+
+    /// Render the values returned by commands with `renderer`, e.g. as JSON or as a table.
+    /// The default renderer prints them with their `Display` implementation.
+    pub fn with_output_renderer(
+        mut self,
+        renderer: impl OutputRenderer<Output> + Send + 'static,
+    ) -> Self {
+        self.renderer = Box::new(renderer);
+
+        self
+    }
+
+    /// Write errors to `error_output` instead of stderr
+    pub fn with_error_output(mut self, error_output: impl Write + Send + 'static) -> Self {
+        self.error_output = RefCell::new(Box::new(error_output));
+
+        self
+    }
+
+    /// Pass in a custom error handler, e.g. a closure capturing a logger or counters. The
+    /// default error handler simply prints the error to the error output and then returns.
+    /// Returning an error from the handler stops the REPL.
+    pub fn with_error_handler(
+        mut self,
+        handler: impl FnMut(E, &Repl<Context, E, Output>) -> Result<()> + Send + 'static,
+    ) -> Self {
+        self.error_handler = Some(Box::new(handler));
+
+        self
+    }
+
+    /// Turn on/off if REPL run is stopped on CTRG+C (Default: false)
+    pub fn with_stop_on_ctrl_c(self, stop_on_ctrl_c: bool) -> Self {
+        self.with_on_ctrl_c(if stop_on_ctrl_c {
+            CtrlCAction::Quit
+        } else {
+            CtrlCAction::ClearLine
+        })
+    }
+
+    /// Turn on/off if REPL run is stopped on CTRG+D (Default: true)
+    pub fn with_stop_on_ctrl_d(self, stop_on_ctrl_d: bool) -> Self {
+        self.with_on_ctrl_d(if stop_on_ctrl_d {
+            CtrlDAction::Quit
+        } else {
+            CtrlDAction::Ignore
+        })
+    }
+
+    /// Set what happens on CTRL+C (Default: `CtrlCAction::ClearLine`)
+    pub fn with_on_ctrl_c(mut self, action: CtrlCAction) -> Self {
+        self.ctrl_c_action = action;
+
+        self
+    }
+
+    /// Set what happens on CTRL+D (Default: `CtrlDAction::Quit`)
+    pub fn with_on_ctrl_d(mut self, action: CtrlDAction) -> Self {
+        self.ctrl_d_action = action;
+
+        self
+    }
+
+    /// Replace the built-in `quit` and `exit` commands with a single quit command called `name`
+    pub fn with_quit_command(mut self, name: &str) -> Self {
+        self.quit_commands = vec![name.to_string()];
+
+        self
+    }
+
+    /// Remove the built-in `quit` and `exit` commands, e.g. to define your own
+    pub fn without_quit_command(mut self) -> Self {
+        self.quit_commands.clear();
+
+        self
+    }
+
+    /// Remove the built-in `clear` command, e.g. to define your own
+    pub fn without_clear_command(mut self) -> Self {
+        self.clear_command = false;
+
+        self
+    }
+
+    /// Keep the last `capacity` command outputs for the `outputs` and `diff` builtins,
+    /// 0 disables retaining outputs (Default: 100)
+    pub fn with_output_buffer(mut self, capacity: usize) -> Self {
+        self.output_buffer_capacity = capacity;
+
+        self
+    }
+
+    /// Turn on/off the preview and confirmation step of the `paste` builtin (Default: true)
+    pub fn with_paste_confirmation(mut self, paste_confirmation: bool) -> Self {
+        self.paste_confirmation = paste_confirmation;
+
+        self
+    }
+
+    /// Turn on/off evaluation of `$(expression)` arguments as arithmetic expressions,
+    /// e.g. `add $(2*3) 4` calls `add` with `6` and `4`. Values of numeric arguments may be
+    /// written without `$(...)`, e.g. `--count=10*1024`, as they are evaluated when they
+    /// fail validation (Default: false)
+    pub fn with_arithmetic(mut self, arithmetic: bool) -> Self {
+        self.arithmetic = arithmetic;
+
+        self
+    }
+
+    /// Turn on quick completions. These completions will auto-select if the completer
+    /// ever narrows down to a single entry.
+    pub fn with_quick_completions(mut self, quick_completions: bool) -> Self {
+        self.quick_completions = quick_completions;
+
+        self
+    }
+
+    /// Turn on partial completions. These completions will fill the buffer with the
+    /// smallest common string from all the options
+    pub fn with_partial_completions(mut self, partial_completions: bool) -> Self {
+        self.partial_completions = partial_completions;
+
+        self
+    }
+
+    /// Choose the order of the commands in help and completion (Default:
+    /// `HelpOrdering::Alphabetical`). Built-in commands are always listed last.
+    pub fn with_help_ordering(mut self, ordering: HelpOrdering) -> Self {
+        self.help_ordering = ordering;
+
+        self
+    }
+
+    /// Always use reedline, even if stdin or stdout is not a terminal or `TERM` is `dumb`,
+    /// which otherwise fall back to plain line input without colors, menus or raw mode
+    pub fn with_force_interactive(mut self) -> Self {
+        self.interactive = Some(true);
+
+        self
+    }
+
+    /// Always use plain line input without colors, menus or raw mode, like when stdin or
+    /// stdout is not a terminal
+    pub fn with_force_plain(mut self) -> Self {
+        self.interactive = Some(false);
+
+        self
+    }
+
+    /// Choose the layout of the completion menu (Default: `MenuKind::Columnar`)
+    pub fn with_menu(mut self, menu: MenuKind) -> Self {
+        self.menu = menu;
+
+        self
+    }
+
+    /// Set the number of columns of the columnar completion menu (Default: 4)
+    pub fn with_menu_columns(mut self, columns: u16) -> Self {
+        self.menu_columns = Some(columns);
+
+        self
+    }
+
+    /// Set the spaces between the columns of the columnar completion menu (Default: 2)
+    pub fn with_menu_column_padding(mut self, padding: usize) -> Self {
+        self.menu_column_padding = Some(padding);
+
+        self
+    }
+
+    /// Choose what happens with several lines pasted at once (Default:
+    /// `PastePolicy::Execute`). `PastePolicy::Review` turns on bracketed paste, so the
+    /// lines end up in the buffer for review and run one after another on Enter, each
+    /// failure attributed to its line of the paste.
+    pub fn with_paste_policy(mut self, policy: PastePolicy) -> Self {
+        self.paste_policy = policy;
+
+        self
+    }
+
+    /// Report a panicking command like a failed one and keep the REPL running instead of
+    /// unwinding out of `run`. The context may be left half updated by the command.
+    pub fn with_catch_panics(mut self, catch_panics: bool) -> Self {
+        self.catch_panics = catch_panics;
+
+        self
+    }
+
+    /// Choose how completions are matched against the word being completed (Default:
+    /// `CompletionMatching::Prefix`). Paths are always completed by prefix.
+    pub fn with_completion_matching(mut self, matching: CompletionMatching) -> Self {
+        self.completion_matching = matching;
+
+        self
+    }
+
+    /// Adjust the line editor before the REPL starts, e.g. to set reedline options that
+    /// have no builder method here. Completer and highlighter are replaced by the REPL
+    /// before each prompt.
+    pub fn with_reedline_configurator(mut self, configurator: ReedlineConfigurator) -> Self {
+        self.reedline_configurator = Some(configurator);
+
+        self
+    }
+
+    /// Sets the style for reedline's fish-style history autosuggestions, the `hint_style`
+    /// of the theme
+    ///
+    /// Default: `nu_ansi_term::Style::new().italic().fg(nu_ansi_term::Color::LightGray)`
+    ///
+    pub fn with_hinter_style(mut self, style: Style) -> Self {
+        self.theme.hint_style = style;
+
+        self
+    }
+
+    /// Disables reedline's fish-style history autosuggestions
+    pub fn with_hinter_disabled(mut self) -> Self {
+        self.hinter_enabled = false;
+
+        self
+    }
+
+    /// Replaces reedline's `DefaultHinter` with a custom hinter. The hinter is handed to
+    /// the line editor when the REPL starts.
+    pub fn with_hinter(mut self, hinter: Box<dyn Hinter>) -> Self {
+        self.hinter = Some(hinter);
+        self.command_hints = false;
+
+        self
+    }
+
+    /// Replaces the built-in highlighter, which colors commands, flags, quoted strings and
+    /// surplus arguments with the colors of the theme
+    pub fn with_highlighter(mut self, highlighter: Box<dyn Highlighter>) -> Self {
+        self.highlighter = Some(SharedHighlighter::new(highlighter));
+
+        self
+    }
+
+    /// Replaces the validator deciding whether the line is complete or another line is
+    /// read, by default lines ending in `\` and unclosed `if` and `repeat` blocks continue.
+    /// Scripts are still split with the default rules.
+    pub fn with_validator(mut self, validator: Box<dyn Validator>) -> Self {
+        self.validator = Some(validator);
+
+        self
+    }
+
+    /// Only suggest previous invocations of known commands, so lines that failed with an
+    /// unknown command are never hinted
+    pub fn with_command_hints(mut self) -> Self {
+        self.command_hints = true;
+        self.hinter = None;
+
+        self
+    }
+
+    /// Adds a reedline keybinding
+    ///
+    /// # Panics
+    ///
+    /// If `comamnd` is an empty [`ReedlineEvent::UntilFound`]
+    pub fn with_keybinding(
+        mut self,
+        modifier: KeyModifiers,
+        key_code: KeyCode,
+        command: ReedlineEvent,
+    ) -> Self {
+        self.keybindings.add_binding(modifier, key_code, command);
+
+        self
+    }
+
+    /// Insert a newline into the line being edited with `modifier` and `key_code` while
+    /// Enter submits it, for commands taking multiline text like SQL or a message body.
+    /// Alt-Enter does this by default, remove it with `without_keybinding`. Quoted text
+    /// keeps its newlines when the line is split into arguments.
+    pub fn with_newline_keybinding(self, modifier: KeyModifiers, key_code: KeyCode) -> Self {
+        self.with_keybinding(
+            modifier,
+            key_code,
+            ReedlineEvent::Edit(vec![EditCommand::InsertNewline]),
+        )
+    }
+
+    /// Open a pane with the help of the command being typed with `modifier` and `key_code`,
+    /// without losing the input. F1 does this by default, remove it with
+    /// `without_keybinding`. On an empty line it lists every command with its description.
+    pub fn with_help_menu_keybinding(self, modifier: KeyModifiers, key_code: KeyCode) -> Self {
+        self.with_keybinding(
+            modifier,
+            key_code,
+            ReedlineEvent::Menu(HELP_MENU.to_string()),
+        )
+    }
+
+    /// Find a keybinding based on the modifier and keycode
+    pub fn find_keybinding(
+        &self,
+        modifier: KeyModifiers,
+        key_code: KeyCode,
+    ) -> Option<ReedlineEvent> {
+        self.keybindings.find_binding(modifier, key_code)
+    }
+
+    /// Get assigned keybindings
+    pub fn get_keybindings(&self) -> HashMap<(KeyModifiers, KeyCode), ReedlineEvent> {
+        // keybindings.get_keybindings() cannot be returned directly because KeyCombination is not visible
+        self.keybindings
+            .get_keybindings()
+            .iter()
+            .map(|(key, value)| ((key.modifier, key.key_code), value.clone()))
+            .collect()
+    }
+
+    /// Remove a keybinding
+    ///
+    /// Returns `Some(ReedlineEvent)` if the keycombination was previously bound to a particular [`ReedlineEvent`]
+    pub fn without_keybinding(mut self, modifier: KeyModifiers, key_code: KeyCode) -> Self {
+        self.keybindings.remove_binding(modifier, key_code);
+
+        self
+    }
+
+    /// Add a command to your REPL. A command named like a built-in, e.g. `set` or `show`,
+    /// replaces the built-in.
+    pub fn with_command(
+        mut self,
+        command: Command,
+        callback: Callback<Context, E, Output>,
+    ) -> Self {
+        self.register_command(command, callback);
+        self
+    }
+
+    /// Add a command to your REPL
+    #[cfg(feature = "async")]
+    pub fn with_command_async(
+        mut self,
+        command: Command,
+        callback: AsyncCallback<Context, E, Output>,
+    ) -> Self {
+        self.register_command_async(command, callback);
+        self
+    }
+
+    /// Add a command while the REPL is set up or between runs, e.g. when a plugin is loaded.
+    /// Help, completion and highlighting pick it up before the next prompt.
+    pub fn register_command(&mut self, command: Command, callback: Callback<Context, E, Output>) {
+        let name = command.get_name().to_string();
+        let definition = ReplCommand::new(&name, command, callback);
+        self.insert_command(name, definition);
+    }
+
+    /// Add an async command while the REPL is set up or between runs
+    #[cfg(feature = "async")]
+    pub fn register_command_async(
+        &mut self,
+        command: Command,
+        callback: AsyncCallback<Context, E, Output>,
+    ) {
+        let name = command.get_name().to_string();
+        let definition = ReplCommand::new_async(&name, command, callback);
+        self.insert_command(name, definition);
+    }
+
+    /// Like [register_command](#method.register_command), but fails with
+    /// `Error::DuplicateCommand` instead of replacing a command, alias or built-in with the
+    /// same name
+    pub fn try_register_command(
+        &mut self,
+        command: Command,
+        callback: Callback<Context, E, Output>,
+    ) -> Result<()> {
+        self.check_duplicate(&command)?;
+        self.register_command(command, callback);
+        Ok(())
+    }
+
+    /// Like [register_command_async](#method.register_command_async), but fails with
+    /// `Error::DuplicateCommand` if the name is taken
+    #[cfg(feature = "async")]
+    pub fn try_register_command_async(
+        &mut self,
+        command: Command,
+        callback: AsyncCallback<Context, E, Output>,
+    ) -> Result<()> {
+        self.check_duplicate(&command)?;
+        self.register_command_async(command, callback);
+        Ok(())
+    }
+
+    /// Fails if the name or an alias of `command` is used by a global command, a built-in
+    /// or a mount
+    fn check_duplicate(&self, command: &Command) -> Result<()> {
+        let taken: Vec<String> = self
+            .commands
+            .values()
+            .filter(|definition| definition.mode.is_none())
+            .map(|definition| definition.command.clone())
+            .chain(self.builtin_commands())
+            .flat_map(|command| {
+                std::iter::once(command.get_name().to_string())
+                    .chain(command.get_all_aliases().map(|alias| alias.to_string()))
+                    .collect::<Vec<_>>()
+            })
+            .chain(self.mounts.keys().cloned())
+            .chain(std::iter::once(self.help_command_name.clone()))
+            .collect();
+        match std::iter::once(command.get_name())
+            .chain(command.get_all_aliases())
+            .find(|name| taken.iter().any(|taken| taken == name))
+        {
+            Some(name) => Err(Error::DuplicateCommand(name.to_string())),
+            None => Ok(()),
+        }
+    }
+
+    /// Remove the command `name` as it is called in the current mode, a command of another
+    /// mode as `"<mode> <name>"`, or the REPL mounted under `name`. Options set for the
+    /// command are dropped too, so they don't apply if it is registered again. Returns
+    /// whether something was removed.
+    pub fn unregister_command(&mut self, name: &str) -> bool {
+        self.invalidate_completion_index();
+        if self.mounts.remove(name).is_some() {
+            return true;
+        }
+        let key = if self.commands.contains_key(name) {
+            name.to_string()
+        } else {
+            match find_command_key(&self.commands, name, &self.scope()) {
+                Some(key) => key.clone(),
+                None => return false,
+            }
+        };
+        let removed = self.commands.remove(&key).expect("found above");
+        self.command_order.retain(|order| *order != key);
+        // options for the name alone stay while a command of another mode still uses it
+        let name_used = self
+            .commands
+            .values()
+            .any(|definition| definition.name == removed.name);
+        self.command_settings
+            .retain(|(setting, _)| *setting != key && (name_used || *setting != removed.name));
+        if !name_used {
+            self.plugin_commands
+                .retain(|plugin| *plugin != removed.name);
+        }
+
+        true
+    }
+
+    /// Registers and removes the commands queued in the command registry
+    fn apply_registrations(&mut self) {
+        for registration in self.registry.take() {
+            match registration {
+                Registration::Add(command, callback) => {
+                    self.add_plugin_command(command.get_name());
+                    self.register_command(*command, callback)
+                }
+                #[cfg(feature = "async")]
+                Registration::AddAsync(command, callback) => {
+                    self.add_plugin_command(command.get_name());
+                    self.register_command_async(*command, callback)
+                }
+                Registration::Remove(name) => {
+                    self.unregister_command(&name);
+                }
+            }
+        }
+    }
+
+    /// Remembers a command added through the registry for the session report
+    fn add_plugin_command(&mut self, name: &str) {
+        if !self.plugin_commands.iter().any(|plugin| plugin == name) {
+            self.plugin_commands.push(name.to_string());
+        }
+    }
+
+    /// Drops the completion index, it's built again for the changed commands
+    fn invalidate_completion_index(&self) {
+        if let Ok(mut index) = self.completion_index.lock() {
+            index.take();
+        }
+    }
+
+    /// Stores a command under `key`, remembering the order commands were added in
+    fn insert_command(&mut self, key: String, mut definition: ReplCommand<Context, E, Output>) {
+        for (name, setting) in &self.command_settings {
+            if *name == key || *name == definition.name {
+                definition.apply(setting);
+            }
+        }
+        if !self.commands.contains_key(&key) {
+            self.command_order.push(key.clone());
+        }
+        self.invalidate_completion_index();
+        self.commands.insert(key, definition);
+    }
+
+    /// Applies `setting` to the commands called `name`, the ones registered so far and the
+    /// ones registered later, so options can be set in any order. `name` matches the command
+    /// in every mode, `"<mode> <name>"` only the one of that mode.
+    fn configure_command(&mut self, name: &str, setting: CommandSetting<Context, E>) {
+        for (key, command) in &mut self.commands {
+            if key == name || command.name == name {
+                command.apply(&setting);
+            }
+        }
+        self.invalidate_completion_index();
+        self.command_settings.push((name.to_string(), setting));
+    }
+
+    /// Add every subcommand of an existing clap application as a command, e.g. to offer the
+    /// commands of your command line interface in the REPL. The callback receives the
+    /// matches of the whole application as if `app <command> <args>` had been run, so it can
+    /// share the dispatch code of the command line interface via `ArgMatches::subcommand`.
+    pub fn with_clap_commands(
+        mut self,
+        app: Command,
+        callback: Callback<Context, E, Output>,
+    ) -> Self {
+        for subcommand in app.get_subcommands() {
+            let name = subcommand.get_name().to_string();
+            let mut definition = ReplCommand::new(&name, subcommand.clone(), callback);
+            definition.app = Some(app.clone());
+            self.insert_command(name, definition);
+        }
+
+        self
+    }
+
+    /// Mount the commands of another REPL under `prefix`, e.g. `db query ..` runs the
+    /// `query` command of a database REPL with its own context. `help` and completion
+    /// include the mounted commands, which are executed synchronously, so `db sleep 1s`
+    /// blocks the thread in an async REPL too.
+    pub fn with_mount<MountContext: 'static, MountOutput: 'static>(
+        mut self,
+        prefix: &str,
+        repl: Repl<MountContext, E, MountOutput>,
+    ) -> Self
+    where
+        E: 'static,
+        Repl<MountContext, E, MountOutput>: Send,
+    {
+        self.mounts.insert(prefix.to_string(), Box::new(repl));
+
+        self
+    }
+
+    /// Add a command that is only available while `mode` is the active mode, it takes
+    /// precedence over a global command with the same name
+    pub fn with_mode_command(
+        mut self,
+        mode: &str,
+        command: Command,
+        callback: Callback<Context, E, Output>,
+    ) -> Self {
+        let name = command.get_name().to_string();
+        let mut definition = ReplCommand::new(&name, command, callback);
+        definition.mode = Some(mode.to_string());
+        self.insert_command(format!("{} {}", mode, name), definition);
+
+        self
+    }
+
+    /// Make `mode` a sub-REPL: while it is the active mode only its own commands and the
+    /// built-ins are available, and `prompt_suffix` follows the prompt, e.g. `MyApp(tx)〉`
+    /// for a transaction mode. Commands enter and leave it by pushing and popping the mode
+    /// in the [Modes](struct.Modes.html) of the context, like `begin` and `commit`.
+    pub fn with_subrepl(mut self, mode: &str, prompt_suffix: &str) -> Self {
+        self.subrepls
+            .insert(mode.to_string(), prompt_suffix.to_string());
+
+        self
+    }
+
+    /// Tell the REPL where to find the [Modes](struct.Modes.html) in your context. Help,
+    /// completion and the prompt then follow the active mode.
+    pub fn with_modes(mut self, modes: ModesCallback<Context>) -> Self {
+        self.modes = Some(modes);
+
+        self
+    }
+
+    /// Use `variables` for the `set` builtin and `$name` expansion, e.g. a clone of the
+    /// variables kept in your context to read them from command callbacks
+    pub fn with_variables(mut self, variables: Variables) -> Self {
+        self.variables = variables;
+
+        self
+    }
+
+    /// Cancel `token` when Ctrl-C is pressed while a command runs, e.g. a clone of the
+    /// token kept in your context to stop long-running callbacks
+    pub fn with_cancellation_token(mut self, token: CancellationToken) -> Self {
+        self.cancellation = token;
+
+        self
+    }
+
+    /// Apply the commands registered and unregistered through `registry`, e.g. a clone of
+    /// the registry kept in your context to add commands from command callbacks
+    pub fn with_command_registry(mut self, registry: CommandRegistry<Context, E, Output>) -> Self {
+        self.registry = registry;
+
+        self
+    }
+
+    /// The session variables, clones share their values with the REPL
+    pub fn variables(&self) -> Variables {
+        self.variables.clone()
+    }
+
+    /// Handle for printing lines from background threads above the prompt without
+    /// corrupting the line being edited. Clone it into your workers and send lines with
+    /// `printer.sender().send(..)`, they are shown while the REPL waits for input.
+    #[cfg(feature = "external_printer")]
+    pub fn printer(&self) -> ExternalPrinter<String> {
+        self.printer.clone()
+    }
+
+    /// Shows the active mode in front of the prompt, or the suffix of a sub-REPL after it
+    fn update_prompt_mode(&mut self) {
+        let workspace = (!self.contexts.is_empty()).then(|| self.context_name.clone());
+        self.prompt.update_workspace(workspace);
+        let mode = self.active_mode();
+        let suffix = mode
+            .as_ref()
+            .and_then(|mode| self.subrepls.get(mode))
+            .cloned();
+        match suffix {
+            Some(suffix) => {
+                self.prompt.update_mode(None);
+                self.prompt.update_suffix(Some(suffix));
+            }
+            None => {
+                self.prompt.update_mode(mode);
+                self.prompt.update_suffix(None);
+            }
+        }
+    }
+
+    /// Register a named context, e.g. one per environment like `dev`, `stage` and `prod`,
+    /// to switch between with the `switch <name>` builtin. Commands get the active context,
+    /// its name is shown in front of the prompt. The context the REPL was created with is
+    /// named `default` and is replaced by one registered under that name.
+    pub fn with_context(mut self, name: &str, context: Context) -> Self {
+        if name == self.context_name {
+            self.context = context;
+        } else {
+            self.contexts.insert(name.to_string(), context);
+        }
+
+        self
+    }
+
+    /// Name of the active context, see [with_context](#method.with_context)
+    pub fn context_name(&self) -> &str {
+        &self.context_name
+    }
+
+    /// Makes the context `name` the active one, keeping the current one under its name
+    fn switch_context(&mut self, name: &str) -> Result<()> {
+        if name == self.context_name {
+            return Ok(());
+        }
+        let context = self.contexts.remove(name).ok_or_else(|| {
+            let known = std::iter::once(&self.context_name).chain(self.contexts.keys());
+            Error::UnknownContext(name.to_string(), known.cloned().collect())
+        })?;
+        let previous = std::mem::replace(&mut self.context, context);
+        let previous_name = std::mem::replace(&mut self.context_name, name.to_string());
+        self.contexts.insert(previous_name, previous);
+
+        Ok(())
+    }
+
+    /// Lists the registered contexts, marking the active one
+    fn show_contexts(&self) {
+        let mut names: Vec<&String> = self.contexts.keys().collect();
+        names.push(&self.context_name);
+        names.sort();
+        for name in names {
+            let marker = if *name == self.context_name { '*' } else { ' ' };
+            self.print_output(format!("{} {}", marker, name));
+        }
+    }
+
+    /// Put the command `name` into `category`, the general help lists the commands grouped
+    /// by category
+    pub fn with_category(mut self, name: &str, category: &str) -> Self {
+        self.configure_command(name, CommandSetting::Category(category.to_string()));
+
+        self
+    }
+
+    /// Handle the failures of the command `name` with `handler` instead of the global error
+    /// handler, e.g. to print remediation steps. Errors it returns go to the global handler.
+    pub fn with_command_error_handler(
+        mut self,
+        name: &str,
+        handler: CommandErrorHandler<Context, E>,
+    ) -> Self {
+        self.configure_command(name, CommandSetting::ErrorHandler(handler));
+
+        self
+    }
+
+    /// Mark the command `name` as deprecated, it still runs but warns with `note` first,
+    /// e.g. `"use 'export' instead"`, and help shows the note
+    pub fn with_deprecated(mut self, name: &str, note: &str) -> Self {
+        self.configure_command(name, CommandSetting::Deprecated(note.to_string()));
+
+        self
+    }
+
+    /// Ask `question` before running the command `name`, e.g. `"Delete all records?"`, and
+    /// run it only if the answer is `y` or `yes`. The command gets a `--yes` flag, and `-y`
+    /// if that is free, to skip the question. Without a terminal to ask at, e.g. in scripts,
+    /// the command is refused and the line fails unless `--yes` is given or `set confirm off`
+    /// turned the questions off.
+    pub fn with_confirmation(mut self, name: &str, question: &str) -> Self {
+        self.configure_command(name, CommandSetting::Confirmation(question.to_string()));
+
+        self
+    }
+
+    /// Ask for the argument `arg` of the command `name` with hidden input if it is omitted,
+    /// e.g. the password of `login <user> [password]`. The argument must not be required so
+    /// the line is accepted without it. Lines running the command are kept out of the history.
+    /// An `arg` the command doesn't define is ignored.
+    pub fn with_sensitive_arg(mut self, name: &str, arg: &str) -> Self {
+        self.configure_command(name, CommandSetting::SensitiveArg(arg.to_string()));
+
+        self
+    }
+
+    /// Complete the argument `arg` of the command `name` with values from the context, e.g.
+    /// the names of open tables. The callback runs before every prompt.
+    pub fn with_arg_completer(
+        mut self,
+        name: &str,
+        arg: &str,
+        callback: ArgCompleterCallback<Context>,
+    ) -> Self {
+        self.configure_command(
+            name,
+            CommandSetting::ArgCompleter(arg.to_string(), callback),
+        );
+
+        self
+    }
+
+    /// Keep lines running the command `name` out of the history, e.g. for noisy commands.
+    /// Lines starting with a space are never added to the history.
+    pub fn with_history_excluded(mut self, name: &str) -> Self {
+        self.configure_command(name, CommandSetting::HistoryExcluded);
+
+        self
+    }
+
+    /// Check `guard` before the command `name` runs and reject the command with the error
+    /// it returns, e.g. `"not connected"` or `"admin role required"`, so the callback
+    /// doesn't have to check its preconditions itself
+    pub fn with_guard(mut self, name: &str, guard: GuardCallback<Context>) -> Self {
+        self.configure_command(name, CommandSetting::Guard(guard));
+
+        self
+    }
+
+    /// Only show the command `name` in help and completion while `predicate` returns true
+    /// for the current context. The command can still be executed while hidden.
+    pub fn with_visible_when(
+        mut self,
+        name: &str,
+        predicate: VisibleWhenCallback<Context>,
+    ) -> Self {
+        self.configure_command(name, CommandSetting::VisibleWhen(predicate));
+
+        self
+    }
+
+    /// Parse the arguments of the command `name` with `parser` instead of the default
+    /// tokenizer and clap, e.g. for DSL-like commands like `filter status=open AND age>3d`.
+    /// The callback gets each value the parser returns as a string argument of its name,
+    /// `args.get_one::<String>("status")`, and clap validates none of them.
+    pub fn with_parser(mut self, name: &str, parser: ParserCallback<Context, E>) -> Self {
+        self.configure_command(name, CommandSetting::Parser(parser));
+
+        self
+    }
+
+    /// Commands handled by the REPL itself, listed in help and completion
+    fn builtin_commands(&self) -> Vec<Command> {
+        let mut builtins = vec![
+            Command::new("paste")
+                .arg(Arg::new("sentinel").help("Line terminating the block [default: EOF]"))
+                .about("Execute a block of commands terminated by a sentinel line"),
+            Command::new("search")
+                .arg(
+                    Arg::new("pattern")
+                        .required(true)
+                        .help("Regular expression"),
+                )
+                .about("Search the history, run a numbered entry again with !n"),
+            Command::new("history")
+                .arg(
+                    Arg::new("pattern")
+                        .num_args(0..)
+                        .help("Only show entries containing this text, `clear` clears the history"),
+                )
+                .about("Show the recent history, run a numbered entry again with !n"),
+            Command::new("outputs").about("List the retained command outputs"),
+            Command::new("diff")
+                .arg(Arg::new("old").help("Output number [default: second to last]"))
+                .arg(Arg::new("new").help("Output number [default: last]"))
+                .about("Show the differences between two command outputs"),
+            Command::new("sleep")
+                .arg(
+                    Arg::new("duration")
+                        .required(true)
+                        .help("Time to wait, e.g. 500ms, 2s or 1m"),
+                )
+                .about("Wait before running the next command"),
+            Command::new("set")
+                .arg(Arg::new("name").help("Option or variable, variables are used as $name"))
+                .arg(
+                    Arg::new("value")
+                        .num_args(0..)
+                        .help("Value of the option or variable"),
+                )
+                .about("Change an option or set a variable, list the variables without arguments"),
+            Command::new("show")
+                .subcommand(Command::new("options").about("List the options changed with set"))
+                .about("Show the options of the REPL"),
+            Command::new("echo")
+                .arg(Arg::new("text").num_args(0..).help("Words to print"))
+                .about("Print the arguments, expanding $VARIABLES and escapes like \\n"),
+            Command::new("print")
+                .arg(
+                    Arg::new("format")
+                        .required(true)
+                        .help("Text with a {} placeholder for each argument"),
+                )
+                .arg(
+                    Arg::new("args")
+                        .num_args(0..)
+                        .help("Values for the placeholders"),
+                )
+                .about("Print formatted text, expanding $VARIABLES and escapes like \\n"),
+            Command::new("session")
+                .subcommand(
+                    Command::new("info").about("Show version, uptime, settings and plugins"),
+                )
+                .about("Show information about this session"),
+            Command::new("stats")
+                .about("Show how often each command ran, failed and how long it took"),
+            Command::new("wait-for")
+                .arg(
+                    Arg::new("command")
+                        .required(true)
+                        .help("Command line to run, quoted if it has arguments"),
+                )
+                .arg(
+                    Arg::new("until-success")
+                        .long("until-success")
+                        .action(ArgAction::SetTrue)
+                        .help("Retry the command until it succeeds [default]"),
+                )
+                .arg(
+                    Arg::new("timeout")
+                        .long("timeout")
+                        .default_value("60s")
+                        .value_parser(parse_duration)
+                        .help("Give up after this time"),
+                )
+                .arg(
+                    Arg::new("interval")
+                        .long("interval")
+                        .default_value("1s")
+                        .value_parser(parse_duration)
+                        .help("Time between two attempts"),
+                )
+                .about("Run a command repeatedly until it succeeds"),
+            Command::new("capture")
+                .arg(
+                    Arg::new("name")
+                        .required(true)
+                        .help("Variable to store the output in, used as $name"),
+                )
+                .arg(
+                    Arg::new("command")
+                        .required(true)
+                        .num_args(1..)
+                        .help("Command line to run, quoted if it has arguments"),
+                )
+                .about("Run a command and store its output in a variable"),
+        ];
+        if cfg!(feature = "scripts") && !self.served {
+            builtins.push(
+                Command::new("source")
+                    .arg(Arg::new("path").required(true).help("Script to execute"))
+                    .about("Execute the commands of a script file"),
+            );
+        }
+        if self.spawner.is_some() {
+            builtins.push(Command::new("jobs").about("List the background jobs"));
+            builtins.push(
+                Command::new("fg")
+                    .arg(Arg::new("id").help("Job to wait for [default: last]"))
+                    .about("Wait for a background job and print its output"),
+            );
+        }
+        if !self.contexts.is_empty() {
+            builtins.push(
+                Command::new("switch")
+                    .arg(Arg::new("name").help("Context to make active"))
+                    .about("Switch to another context, list them without arguments"),
+            );
+        }
+        if self.clear_command && !self.served {
+            builtins.push(Command::new("clear").about("Clear the screen"));
+        }
+        for name in &self.quit_commands {
+            builtins.push(Command::new(name.clone()).about("Quit the REPL"));
+        }
+        builtins
+    }
+
+    /// Registered commands available and visible for the current context and mode
+    fn registered_commands(&self) -> Vec<Command> {
+        let scope = self.scope();
+        let mut commands: Vec<Command> = self
+            .command_order
+            .iter()
+            .filter_map(|key| self.commands.get(key))
+            .filter(|command| command.is_visible(&self.context) && command.is_available(&scope))
+            .map(|command| command.help_command(&self.messages.deprecated_marker))
+            .collect();
+        if self.help_ordering == HelpOrdering::Alphabetical {
+            commands.sort_by(|a, b| a.get_name().cmp(b.get_name()));
+        }
+        commands
+    }
+
+    /// Commands to show in help and completion for the current context
+    fn visible_commands(&self) -> Vec<Command> {
+        self.registered_commands()
+            .into_iter()
+            .chain(self.mounts.iter().map(|(prefix, mount)| {
+                Command::new(prefix.clone())
+                    .about(mount.description())
+                    .subcommands(mount.visible_commands())
+            }))
+            .chain(self.unshadowed_builtins())
+            .collect()
+    }
+
+    /// The built-in commands that aren't replaced by a registered command or mount of the
+    /// same name in the current mode
+    fn unshadowed_builtins(&self) -> Vec<Command> {
+        let scope = self.scope();
+        self.builtin_commands()
+            .into_iter()
+            .filter(|builtin| {
+                let name = builtin.get_name();
+                find_command(&self.commands, name, &scope).is_none()
+                    && !self.mounts.contains_key(name)
+            })
+            .collect()
+    }
+
+    /// Shows help, `-v` adds the arguments of every command and `-vv` their long help
+    fn show_help(&self, args: &[&str]) -> Result<()> {
+        let mut verbosity = 0;
+        let mut names = vec![];
+        for arg in args {
+            match *arg {
+                "--verbose" => verbosity += 1,
+                flag if flag.len() > 1
+                    && flag.starts_with('-')
+                    && flag[1..].chars().all(|c| c == 'v') =>
+                {
+                    verbosity += flag.len() - 1
+                }
+                name => names.push(name),
+            }
+        }
+        let help_width = self.help_width();
+        let render = |command: &mut Command, verbosity: usize| {
+            let mut command = command.clone().term_width(help_width);
+            match verbosity {
+                0 => command.render_help().ansi().to_string(),
+                _ => command.render_long_help().ansi().to_string(),
+            }
+        };
+
+        if names.is_empty() && verbosity > 0 {
+            let helps: Vec<String> = self
+                .visible_commands()
+                .into_iter()
+                .map(|mut command| render(&mut command, verbosity - 1).trim_end().to_string())
+                .collect();
+            self.print_paged(format!("{}\n{}", self.help_header(), helps.join("\n\n")));
+        } else if names.is_empty() && self.commands.values().any(|c| c.category.is_some()) {
+            self.print_paged(format!(
+                "{}\n{}{}",
+                self.help_header(),
+                self.categorized_help(),
+                self.topics_help()
+            ));
+        } else if names.is_empty() {
+            let mut app = Command::new("app").term_width(help_width);
+
+            for command in self.visible_commands() {
+                app = app.subcommand(command);
+            }
+            let mut help_bytes: Vec<u8> = Vec::new();
+            app.write_help(&mut help_bytes)
+                .expect("failed to print help");
+            let mut help_string =
+                String::from_utf8(help_bytes).expect("Help message was invalid UTF8");
+            let marker = "SUBCOMMANDS:";
+            if let Some(marker_pos) = help_string.find(marker) {
+                help_string = self
+                    .active_theme()
+                    .help_heading_style
+                    .paint(&self.messages.commands_heading)
+                    .to_string()
+                    + &help_string[(marker_pos + marker.len())..help_string.len()];
+            }
+            self.print_paged(format!(
+                "{}\n{}{}",
+                self.help_header(),
+                help_string,
+                self.topics_help()
+            ));
+            self.print_output(Messages::fill(
+                &self.messages.help_verbose_hint,
+                &[&self.help_command_name],
+            ));
+        } else if let Some(mut subcommand) = self.visible_commands().into_iter().find(|command| {
+            command.get_name() == names[0] || command.get_all_aliases().any(|a| a == names[0])
+        }) {
+            self.print_output(render(&mut subcommand, verbosity));
+        } else if let Some((_, text)) = self.help_topics.iter().find(|(name, _)| name == names[0]) {
+            self.print_paged(text);
+        } else {
+            self.print_error(Messages::fill(&self.messages.help_not_found, &[names[0]]));
+        }
+        Ok(())
+    }
+
+    /// Calls a command callback, a panic becomes an error with `with_catch_panics`
+    fn call_callback(
+        &mut self,
+        command: &str,
+        callback: Callback<Context, E, Output>,
+        matches: ArgMatches,
+    ) -> core::result::Result<Option<Output>, E> {
+        if !self.catch_panics {
+            return callback(matches, &mut self.context);
+        }
+        let context = &mut self.context;
+        std::panic::catch_unwind(AssertUnwindSafe(|| callback(matches, context))).unwrap_or_else(
+            |payload| {
+                Err(Error::CommandPanicked(command.to_string(), panic_message(&*payload)).into())
+            },
+        )
+    }
+
+    /// Fails with the reason the guard of `command` gives for not running it
+    pub(crate) fn check_guard(
+        &self,
+        command: &str,
+        guard: Option<GuardCallback<Context>>,
+    ) -> Result<()> {
+        match guard {
+            Some(guard) => guard(&self.context)
+                .map_err(|reason| Error::CommandRejected(command.to_string(), reason)),
+            None => Ok(()),
+        }
+    }
+
+    /// Whether the command may run: it needs no confirmation, `--yes` was given, questions
+    /// are turned off or the user answered yes
+    pub(crate) fn confirmed(
+        &self,
+        command: &str,
+        question: Option<&str>,
+        matches: &ArgMatches,
+    ) -> bool {
+        let question = match question {
+            Some(question) if self.confirmations => question,
+            _ => return true,
+        };
+        if matches!(matches.try_get_one::<bool>("yes"), Ok(Some(true))) {
+            return true;
+        }
+        if !self.terminal_output || !std::io::stdin().is_terminal() {
+            self.print_error(Messages::fill(
+                &self.messages.confirmation_required,
+                &[command],
+            ));
+            self.arguments_rejected.set(true);
+            return false;
+        }
+        if confirm(question).unwrap_or(false) {
+            true
+        } else {
+            self.print_output(&self.messages.confirmation_cancelled);
+            false
+        }
+    }
+
+    /// Warns that `command` is deprecated before it runs
+    fn warn_deprecated(&self, command: &str, note: Option<&str>) {
+        if let Some(note) = note {
+            let warning = Messages::fill(&self.messages.deprecated_warning, &[command, note]);
+            self.print_error(self.active_theme().warning_style.paint(warning));
+        }
+    }
+
+    /// Width help is wrapped at, the terminal width unless set with `with_help_width`
+    fn help_width(&self) -> usize {
+        match self.help_width {
+            Some(width) => width,
+            None if self.terminal_output && std::io::stdout().is_terminal() => {
+                size().map(|(columns, _)| columns as usize).unwrap_or(100)
+            }
+            None => 100,
+        }
+    }
+
+    /// Lists the help topics with the first line of their text
+    fn topics_help(&self) -> String {
+        if self.help_topics.is_empty() {
+            return String::new();
+        }
+        let width = self
+            .help_topics
+            .iter()
+            .map(|(name, _)| name.len())
+            .max()
+            .unwrap_or(0);
+        let mut help = format!(
+            "\n{}\n",
+            self.active_theme()
+                .help_heading_style
+                .paint(&self.messages.topics_heading)
+        );
+        for (name, text) in &self.help_topics {
+            let summary = text.lines().next().unwrap_or_default();
+            help += &help_entry(name, summary, width, self.help_width());
+        }
+        help
+    }
+
+    /// Lists the visible commands grouped by category, commands without one come first
+    fn categorized_help(&self) -> String {
+        let mut groups: BTreeMap<Option<&str>, Vec<Command>> = BTreeMap::new();
+        groups
+            .entry(None)
+            .or_default()
+            .push(Command::new(&self.help_command_name).about(&self.messages.help_about));
+        for command in self.visible_commands() {
+            let category = self
+                .commands
+                .get(command.get_name())
+                .and_then(|definition| definition.category.as_deref());
+            groups.entry(category).or_default().push(command);
+        }
+        let width = groups
+            .values()
+            .flatten()
+            .map(|command| command.get_name().len())
+            .max()
+            .unwrap_or(0);
+
+        let mut help = String::new();
+        for (category, mut commands) in groups {
+            if self.help_ordering == HelpOrdering::Alphabetical {
+                commands.sort_by(|a, b| a.get_name().cmp(b.get_name()));
+            }
+            let header = match category {
+                Some(category) => format!("{}:", category.to_uppercase()),
+                None => self.messages.commands_heading.clone(),
+            };
+            help += &format!(
+                "{}\n",
+                self.active_theme().help_heading_style.paint(&header)
+            );
+            for command in commands {
+                let about = command.get_about().map(|about| about.to_string());
+                help += &help_entry(
+                    command.get_name(),
+                    &about.unwrap_or_default(),
+                    width,
+                    self.help_width(),
+                );
+            }
+            help.push('\n');
+        }
+        help
+    }
+
+    /// Writes a completion script for `shell`, completing the commands of the REPL as
+    /// arguments of the binary named like the REPL, e.g. for apps that also run a single
+    /// command given on the command line
+    #[cfg(feature = "shell_completions")]
+    pub fn generate_completions(&self, shell: Shell, buf: &mut impl Write) {
+        let mut app = self.cli_app();
+        clap_complete::generate(shell, &mut app, self.name.clone(), buf);
+    }
+
+    /// The global commands and mounts of the REPL as subcommands of a clap application
+    /// named like the REPL
+    fn cli_app(&self) -> Command {
+        let commands = self
+            .command_order
+            .iter()
+            .filter_map(|key| self.commands.get(key))
+            .filter(|command| command.mode.is_none())
+            .map(|command| command.command.clone());
+        let mounts = self.mounts.iter().map(|(prefix, mount)| {
+            Command::new(prefix.clone())
+                .about(mount.description())
+                .subcommands(mount.visible_commands())
+        });
+        Command::new(self.name.clone())
+            .version(self.version.clone())
+            .about(self.description.clone())
+            .subcommands(commands.chain(mounts))
+    }
+
+    /// Writes the documentation of all commands and their arguments, e.g. to keep the docs
+    /// of a project in sync with the REPL
+    pub fn export_help(&self, format: HelpFormat, buf: &mut impl Write) -> Result<()> {
+        help_export::export(&self.cli_app(), format, buf)?;
+        Ok(())
+    }
+
+    /// Describes this session for bug reports: version, uptime, executed commands, history
+    /// file, active settings and loaded plugins, i.e. the mounted REPLs and the commands
+    /// added through the command registry
+    pub fn session_report(&self) -> String {
+        let uptime = match self.started {
+            Some(started) => format!("{}s", started.elapsed().as_secs()),
+            None => "not running".to_string(),
+        };
+        let history = match &self.history {
+            Some(path) => path.display().to_string(),
+            None => "none".to_string(),
+        };
+        let mut commands: Vec<&str> = self.commands.keys().map(String::as_str).collect();
+        commands.sort();
+        let none_if_empty = |list: String| {
+            if list.is_empty() {
+                "none".to_string()
+            } else {
+                list
+            }
+        };
+        let mut mounts: Vec<String> = self
+            .mounts
+            .iter()
+            .map(|(prefix, mount)| {
+                format!("{} ({} commands)", prefix, mount.visible_commands().len())
+            })
+            .collect();
+        mounts.sort();
+        let plugin_commands: Vec<&str> = self
+            .plugin_commands
+            .iter()
+            .filter(|name| self.commands.contains_key(*name))
+            .map(String::as_str)
+            .collect();
+        let settings = [
+            ("arithmetic", self.arithmetic.to_string()),
+            ("paste confirmation", self.paste_confirmation.to_string()),
+            ("choice prefixes", self.infer_choices.to_string()),
+            ("path expansion", self.expand_paths.to_string()),
+            ("shared history", self.shared_history.to_string()),
+            (
+                "prompt for missing args",
+                self.prompt_missing_args.to_string(),
+            ),
+            (
+                "line editor",
+                if self.plain_editor() {
+                    "plain"
+                } else {
+                    "reedline"
+                }
+                .to_string(),
+            ),
+            (
+                "shell escape",
+                self.shell_escape
+                    .map_or_else(|| "off".to_string(), String::from),
+            ),
+            ("redirects", self.redirects.to_string()),
+            ("command prefixes", self.command_prefixes.to_string()),
+            ("output buffer", self.output_buffer_capacity.to_string()),
+            ("ctrl-c", format!("{:?}", self.ctrl_c_action)),
+            ("ctrl-d", format!("{:?}", self.ctrl_d_action)),
+            ("quit commands", self.quit_commands.join(", ")),
+        ];
+
+        let mut report = vec![
+            format!("{} {}", self.name, self.version),
+            format!("library: reedline-repl-rs {}", env!("CARGO_PKG_VERSION")),
+            format!("uptime: {}", uptime),
+            format!("commands executed: {}", self.invocation_count),
+            format!("history file: {}", history),
+            format!("registered commands: {}", commands.join(", ")),
+            format!("mounted: {}", none_if_empty(mounts.join(", "))),
+            format!(
+                "plugin commands: {}",
+                none_if_empty(plugin_commands.join(", "))
+            ),
+            "settings:".to_string(),
+        ];
+        for (name, value) in settings {
+            report.push(format!("  {}: {}", name, value));
+        }
+        report.join("\n")
+    }
+
+    fn help_header(&self) -> String {
+        format!(
+            "{} {}\n{}\n",
+            self.active_theme().prompt_style.paint(&self.name),
+            self.version,
+            self.description
+        )
+    }
+
+    fn handle_command(&mut self, command: &str, args: &[&str]) -> core::result::Result<(), E> {
+        match find_command(&self.commands, command, &self.scope()) {
+            Some(definition) => {
+                self.check_guard(command, definition.guard)?;
+                let mut argv: Vec<&str> = vec![command];
+                argv.extend(args);
+                match definition.get_matches(
+                    &argv,
+                    self.infer_choices,
+                    self.expand_paths,
+                    self.arithmetic,
+                    self.prompt_missing(),
+                ) {
+                    Ok(matches)
+                        if !self.confirmed(
+                            command,
+                            definition.confirmation.as_deref(),
+                            &matches,
+                        ) => {}
+                    Ok(matches) => {
+                        let deprecated = definition.deprecated.clone();
+                        let callback = definition
+                            .callback
+                            .expect("Must be filled for sync commands");
+                        self.warn_deprecated(command, deprecated.as_deref());
+                        self.execute_before_command_callback(command);
+                        let guard = self.cancellation.watch();
+                        let start = Instant::now();
+                        #[cfg(feature = "tracing")]
+                        let _span =
+                            tracing::info_span!("command", name = command, args = args.len())
+                                .entered();
+                        let result = self.call_callback(command, callback, matches);
+                        drop(guard);
+                        self.apply_registrations();
+                        self.handle_command_result(command, result, start.elapsed())?;
+                    }
+                    Err(errors) => {
+                        self.print_clap_errors(command, &errors);
+                    }
+                };
+                self.execute_after_command_callback()?;
+            }
+            None => self.handle_builtin(command, args)?,
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn handle_error(&mut self, error: E) -> Result<()> {
+        if let Some(source) = self.sources.last() {
+            self.print_error(format!(
+                "{}:{}: {}",
+                source.name, source.line, self.current_line
+            ));
+        } else if self.echo_failed_command && !self.current_line.is_empty() {
+            self.print_error(Paint::new(format!("> {}", self.current_line)).dimmed());
+        }
+        match self.error_handler.take() {
+            Some(mut handler) => {
+                let result = handler(error, self);
+                self.error_handler = Some(handler);
+                result
+            }
+            None => default_error_handler(error, self),
+        }
+    }
+
+    /// Executes the commands handled by the REPL itself
+    fn handle_builtin(&mut self, command: &str, args: &[&str]) -> core::result::Result<(), E> {
+        if let Some(mount) = self.mounts.get_mut(command) {
+            // the mounted REPL prints to the outputs of this one, e.g. captured by feed_line
+            mount.swap_outputs(self.output.get_mut(), self.error_output.get_mut());
+            let result = match args.split_first() {
+                None => mount.help(&[]).map_err(E::from),
+                Some((subcommand, rest)) if *subcommand == self.help_command_name => {
+                    mount.help(rest).map_err(E::from)
+                }
+                Some((subcommand, rest)) => mount.execute(subcommand, rest),
+            };
+            mount.swap_outputs(self.output.get_mut(), self.error_output.get_mut());
+            return result;
+        }
+        if command == self.help_command_name {
+            self.show_help(args)?;
+        } else if command == "paste" {
+            self.start_paste(args);
+        } else if command == "search" {
+            if args.is_empty() {
+                return Err(Error::MissingRequiredArgument(
+                    command.to_string(),
+                    "pattern".to_string(),
+                )
+                .into());
+            }
+            self.search_history(&args.join(" "))?;
+        } else if command == "history" {
+            match args {
+                ["clear"] => {
+                    self.history_lines.clear();
+                    self.history_store()?.clear()?;
+                }
+                [] => self.show_history(None),
+                pattern => self.show_history(Some(&pattern.join(" "))),
+            }
+        } else if command == "outputs" {
+            for retained in &self.outputs {
+                self.print_output(format!("{:>5}  {}", retained.number, retained.command));
+            }
+        } else if command == "diff" {
+            self.diff_outputs(args)?;
+        } else if command == "sleep" {
+            std::thread::sleep(Self::parse_sleep(args)?);
+        } else if command == "wait-for" {
+            self.wait_for(args)?;
+        } else if command == "capture" {
+            self.capture(args)?;
+        } else if cfg!(feature = "scripts") && !self.served && command == "source" {
+            #[cfg(feature = "scripts")]
+            self.source(args)?;
+        } else if command == "set" {
+            match args {
+                [] => {
+                    for (name, value) in self.variables.all() {
+                        self.print_output(format!("{}={}", name, value));
+                    }
+                }
+                [_] => {
+                    return Err(Error::MissingRequiredArgument(
+                        command.to_string(),
+                        "value".to_string(),
+                    )
+                    .into())
+                }
+                [name, value @ ..] if is_setting(name) => {
+                    self.apply_setting(name, &value.join(" "))?
+                }
+                [name, value @ ..] => self.variables.set(name, &value.join(" ")),
+            }
+        } else if command == "show" {
+            match args {
+                ["options"] => self.show_options(),
+                _ => {
+                    let command = format!("show {}", args.join(" "));
+                    let suggestions = vec!["show options".to_string()];
+                    return Err(
+                        Error::UnknownCommand(command.trim().to_string(), suggestions).into(),
+                    );
+                }
+            }
+        } else if command == "echo" {
+            self.print_output(expand_escapes(&args.join(" ")));
+        } else if command == "print" {
+            let (format, values) = match args.split_first() {
+                Some(split) => split,
+                None => {
+                    return Err(Error::MissingRequiredArgument(
+                        command.to_string(),
+                        "format".to_string(),
+                    )
+                    .into())
+                }
+            };
+            let text = format_placeholders(format, values);
+            self.print_output(expand_escapes(&text));
+        } else if command == "session" {
+            match args {
+                ["info"] => self.print_output(self.session_report()),
+                _ => {
+                    let command = format!("session {}", args.join(" "));
+                    let suggestions = vec!["session info".to_string()];
+                    return Err(
+                        Error::UnknownCommand(command.trim().to_string(), suggestions).into(),
+                    );
+                }
+            }
+        } else if command == "stats" {
+            self.show_stats();
+        } else if !self.contexts.is_empty() && command == "switch" {
+            match args {
+                [] => self.show_contexts(),
+                [name] => self.switch_context(name)?,
+                _ => return Err(Error::TooManyArguments(command.to_string(), 1).into()),
+            }
+        } else if self.spawner.is_some() && command == "jobs" {
+            self.list_jobs();
+        } else if self.spawner.is_some() && command == "fg" {
+            self.foreground_job(args)?;
+        } else if self.clear_command && !self.served && command == "clear" {
+            clear_screen(&mut *self.output.borrow_mut()).map_err(Error::from)?;
+        } else if self.quit_commands.iter().any(|name| name == command) {
+            self.quit = true;
+        } else {
+            let commands = self.visible_commands();
+            let names = commands
+                .iter()
+                .flat_map(|command| {
+                    std::iter::once(command.get_name()).chain(command.get_visible_aliases())
+                })
+                .chain(std::iter::once(self.help_command_name.as_str()));
+            let suggestions = did_you_mean(command, names);
+            #[cfg(feature = "tracing")]
+            tracing::warn!(command, "unknown command");
+            self.emit(ReplEvent::UnknownCommand {
+                command: command.to_string(),
+            });
+            return Err(Error::UnknownCommand(command.to_string(), suggestions).into());
+        }
+
+        Ok(())
+    }
+
+    /// Parses the duration `sleep` waits
+    fn parse_sleep(args: &[&str]) -> Result<Duration> {
+        match args {
+            [] => Err(Error::MissingRequiredArgument(
+                "sleep".to_string(),
+                "duration".to_string(),
+            )),
+            [duration] => parse_duration(duration),
+            _ => Err(Error::TooManyArguments("sleep".to_string(), 1)),
+        }
+    }
+
+    /// Parses the arguments of `wait-for`, returns `None` if clap already reported a problem,
+    /// rejected arguments fail the line like those of other commands
+    fn parse_wait_for(&self, args: &[&str]) -> Option<WaitFor> {
+        let command = self
+            .builtin_commands()
+            .into_iter()
+            .find(|command| command.get_name() == "wait-for")
+            .expect("wait-for is a builtin");
+        match command.try_get_matches_from(std::iter::once(&"wait-for").chain(args)) {
+            Ok(matches) => Some(WaitFor {
+                command: matches
+                    .get_one::<String>("command")
+                    .expect("required")
+                    .clone(),
+                timeout: *matches.get_one::<Duration>("timeout").expect("has default"),
+                interval: *matches
+                    .get_one::<Duration>("interval")
+                    .expect("has default"),
+            }),
+            Err(err) => {
+                self.print_clap_errors("wait-for", &[err]);
+                None
+            }
+        }
+    }
+
+    /// Runs a command line until it succeeds or the timeout expires. Arguments clap rejects
+    /// won't be accepted on a later attempt, so they end the wait and fail the line.
+    fn wait_for(&mut self, args: &[&str]) -> core::result::Result<(), E> {
+        let Some(wait) = self.parse_wait_for(args) else {
+            return Ok(());
+        };
+        let start = Instant::now();
+        loop {
+            self.arguments_rejected.set(false);
+            let result = self.execute_line(wait.command.clone());
+            if self.arguments_rejected.get() || result.is_ok() {
+                return Ok(());
+            }
+            if start.elapsed() + wait.interval > wait.timeout {
+                return Err(Error::WaitTimeout(wait.command).into());
+            }
+            std::thread::sleep(wait.interval);
+        }
+    }
+
+    #[cfg(feature = "async")]
+    async fn wait_for_async(&mut self, args: &[&str]) -> core::result::Result<(), E> {
+        let Some(wait) = self.parse_wait_for(args) else {
+            return Ok(());
+        };
+        let start = Instant::now();
+        loop {
+            self.arguments_rejected.set(false);
+            // boxed to break the recursion through `handle_command_async`
+            let attempt: Pin<Box<dyn Future<Output = core::result::Result<(), E>> + '_>> =
+                Box::pin(self.execute_line_async(wait.command.clone()));
+            let result = attempt.await;
+            if self.arguments_rejected.get() || result.is_ok() {
+                return Ok(());
+            }
+            if start.elapsed() + wait.interval > wait.timeout {
+                return Err(Error::WaitTimeout(wait.command).into());
+            }
+            delay(wait.interval).await;
+        }
+    }
+
+    /// Splits the arguments of `capture` into the variable and the command line to run
+    fn parse_capture(args: &[&str]) -> Result<(String, String)> {
+        match args {
+            [] => Err(Error::MissingRequiredArgument(
+                "capture".to_string(),
+                "name".to_string(),
+            )),
+            [_] => Err(Error::MissingRequiredArgument(
+                "capture".to_string(),
+                "command".to_string(),
+            )),
+            [name, line @ ..] => Ok((name.to_string(), line.join(" "))),
+        }
+    }
+
+    /// Sends the output of commands to a capture instead of the terminal until the
+    /// returned output is put back with `end_capture`
+    fn start_capture(&mut self) -> (Capture, Box<dyn Write + Send>, bool) {
+        let capture = Capture::default();
+        let output = std::mem::replace(self.output.get_mut(), Box::new(capture.clone()));
+        let terminal_output = std::mem::replace(&mut self.terminal_output, false);
+        (capture, output, terminal_output)
+    }
+
+    /// Restores the output replaced by `start_capture`, returns the captured text
+    fn end_capture(&mut self, started: (Capture, Box<dyn Write + Send>, bool)) -> String {
+        let (capture, output, terminal_output) = started;
+        *self.output.get_mut() = output;
+        self.terminal_output = terminal_output;
+        capture.take()
+    }
+
+    /// Runs a command line and stores its output in a variable, errors are still printed
+    /// and leave the variable unchanged
+    fn capture(&mut self, args: &[&str]) -> core::result::Result<(), E> {
+        let (name, line) = Self::parse_capture(args)?;
+        let started = self.start_capture();
+        let result = self.execute_line(line);
+        let captured = self.end_capture(started);
+        result?;
+        self.variables
+            .set(&name, captured.trim_end_matches(['\r', '\n']));
+
+        Ok(())
+    }
+
+    #[cfg(feature = "async")]
+    async fn capture_async(&mut self, args: &[&str]) -> core::result::Result<(), E> {
+        let (name, line) = Self::parse_capture(args)?;
+        let started = self.start_capture();
+        // boxed to break the recursion through `handle_command_async`
+        let run: Pin<Box<dyn Future<Output = core::result::Result<(), E>> + '_>> =
+            Box::pin(self.execute_line_async(line));
+        let result = run.await;
+        let captured = self.end_capture(started);
+        result?;
+        self.variables
+            .set(&name, captured.trim_end_matches(['\r', '\n']));
+
+        Ok(())
+    }
+
+    /// Keeps the output of a command for the `diff` builtin
+    fn retain_output(&mut self, command: &str, output: &str) {
+        if self.output_buffer_capacity == 0 {
+            return;
+        }
+        self.output_count += 1;
+        self.outputs.push_back(RetainedOutput {
+            number: self.output_count,
+            command: command.to_string(),
+            output: output.to_string(),
+        });
+        while self.outputs.len() > self.output_buffer_capacity {
+            self.outputs.pop_front();
+        }
+    }
+
+    fn retained_output(&self, number: usize) -> Result<&RetainedOutput> {
+        self.outputs
+            .iter()
+            .find(|retained| retained.number == number)
+            .ok_or(Error::OutputNotFound(number))
+    }
+
+    /// Prints the diff between two retained outputs, the last two by default
+    fn diff_outputs(&self, args: &[&str]) -> Result<()> {
+        if args.len() > 2 {
+            return Err(Error::TooManyArguments("diff".to_string(), 2));
+        }
+        let last = self.output_count;
+        let old = match args.first() {
+            Some(number) => number.parse()?,
+            None => last.saturating_sub(1),
+        };
+        let new = match args.get(1) {
+            Some(number) => number.parse()?,
+            None => last,
+        };
+        let old = self.retained_output(old)?;
+        let new = self.retained_output(new)?;
+        match unified_diff(
+            &format!("{} ({})", old.number, old.command),
+            &old.output,
+            &format!("{} ({})", new.number, new.command),
+            &new.output,
+        ) {
+            Some(diff) => self.print_output(diff),
+            None => self.print_output(&self.messages.outputs_identical),
+        }
+
+        Ok(())
+    }
+
+    fn execute_before_command_callback(&mut self, command: &str) {
+        self.emit(ReplEvent::CommandStarted {
+            command: command.to_string(),
+        });
+        if let Some(callback) = self.before_command_callback {
+            callback(command, &mut self.context);
+        }
+    }
+
+    /// Prints the output of a command, passing it to the after command hook first
+    pub(crate) fn handle_command_result(
+        &mut self,
+        command: &str,
+        result: core::result::Result<Option<Output>, E>,
+        elapsed: Duration,
+    ) -> core::result::Result<(), E> {
+        let mut elapsed = elapsed;
+        let mut streamed = false;
+        let output = match result {
+            Ok(Some(mut value)) => {
+                let started = Instant::now();
+                let text = self
+                    .renderer
+                    .stream(&mut value)
+                    .map(|pieces| self.print_stream(pieces));
+                streamed = text.is_some();
+                elapsed += started.elapsed();
+                Some(text.unwrap_or_else(|| self.renderer.render(&value)))
+            }
+            Ok(None) => None,
+            Err(error) => {
+                #[cfg(feature = "tracing")]
+                tracing::error!(command, %error, "command failed");
+                self.record_stats(command, elapsed, false);
+                self.emit(ReplEvent::CommandFinished {
+                    command: command.to_string(),
+                    duration: elapsed,
+                    ok: false,
+                });
+                if let Some(callback) = self.after_command_hook {
+                    callback(command, &None, elapsed, &mut self.context);
+                }
+                self.print_timing(elapsed);
+                let handler = find_command(&self.commands, command, &self.scope())
+                    .and_then(|definition| definition.error_handler);
+                return match handler {
+                    Some(handler) => handler(error, &mut self.context),
+                    None => Err(error),
+                };
+            }
+        };
+        self.record_stats(command, elapsed, true);
+        self.emit(ReplEvent::CommandFinished {
+            command: command.to_string(),
+            duration: elapsed,
+            ok: true,
+        });
+        if let Some(callback) = self.after_command_hook {
+            callback(command, &output, elapsed, &mut self.context);
+        }
+        if let Some(value) = output {
+            self.retain_output(command, &value);
+            if !streamed {
+                self.print_paged(value);
+            }
+        }
+        self.print_timing(elapsed);
+
+        Ok(())
+    }
+
+    /// Changes the option `name` of the `set` builtin
+    fn apply_setting(&mut self, name: &str, value: &str) -> Result<()> {
+        let invalid = || {
+            Error::InvalidValue(format!(
+                "invalid value '{}' for '{}', use {}",
+                value,
+                name,
+                setting_values(name)
+            ))
+        };
+        let switch = || parse_bool(value).map_err(|_| invalid());
+        match name {
+            "timing" => self.timing = switch()?,
+            "color" => {
+                self.ansi = switch()?;
+                if self.ansi {
+                    Paint::enable();
+                } else {
+                    Paint::disable();
+                }
+            }
+            "paging" => {
+                self.pager = match value {
+                    "" => return Err(invalid()),
+                    "off" => None,
+                    "internal" => Some(PagerMode::Internal),
+                    program => Some(PagerMode::External(program.to_string())),
+                }
+            }
+            "spacing" => self.output_spacing = value.parse().map_err(|_| invalid())?,
+            "echo" => self.echo_failed_command = switch()?,
+            "confirm" => self.confirmations = switch()?,
+            _ => return Err(invalid()),
+        }
+
+        Ok(())
+    }
+
+    /// The current value of the option `name`
+    fn setting(&self, name: &str) -> String {
+        match name {
+            "timing" => on_off(self.timing),
+            "color" => on_off(self.ansi),
+            "paging" => match &self.pager {
+                None => "off".to_string(),
+                Some(PagerMode::Internal) => "internal".to_string(),
+                Some(PagerMode::External(program)) => program.clone(),
+            },
+            "spacing" => self.output_spacing.to_string(),
+            "echo" => on_off(self.echo_failed_command),
+            "confirm" => on_off(self.confirmations),
+            _ => String::new(),
+        }
+    }
+
+    /// Lists the options of the `set` builtin with their values
+    fn show_options(&self) {
+        let width = SETTINGS
+            .iter()
+            .map(|(name, _, _)| name.len())
+            .max()
+            .unwrap_or(0);
+        for (name, values, description) in SETTINGS {
+            self.print_output(format!(
+                "  {:width$}  {:10}  {} ({})",
+                name,
+                self.setting(name),
+                description,
+                values,
+                width = width
+            ));
+        }
+    }
+
+    fn record_stats(&mut self, command: &str, elapsed: Duration, ok: bool) {
+        self.stats
+            .entry(command.to_string())
+            .or_default()
+            .record(elapsed, ok);
+    }
+
+    /// Lists the commands run in this session, the most used first
+    fn show_stats(&self) {
+        if self.stats.is_empty() {
+            return self.print_output(&self.messages.no_stats);
+        }
+        let mut stats: Vec<(&String, &CommandStats)> = self.stats.iter().collect();
+        stats.sort_by_key(|(_, stats)| Reverse(stats.invocations));
+        let width = stats.iter().map(|(name, _)| name.len()).max().unwrap_or(0);
+        let mut lines = vec![format!(
+            "{:<width$}  {:>6}  {:>6}  {:>9}  {:>9}",
+            "command", "runs", "errors", "total", "average"
+        )];
+        for (name, stats) in stats {
+            lines.push(format!(
+                "{:<width$}  {:>6}  {:>6}  {:>9}  {:>9}",
+                name,
+                stats.invocations,
+                stats.errors,
+                format_elapsed(stats.total_time),
+                format_elapsed(stats.average_time()),
+            ));
+        }
+        self.print_output(lines.join("\n"));
+    }
+
+    /// The status segment of the prompt for the last line, if enabled
+    fn status_segment(&self) -> Option<String> {
+        if !self.status_segment {
+            return None;
+        }
+        let (ok, elapsed) = self.last_status?;
+        let theme = self.active_theme();
+        let (mark, style) = match ok {
+            true => ("✔", theme.status_success_style),
+            false => ("✘", theme.status_failure_style),
+        };
+        let status = format!("{} {}", mark, format_elapsed(elapsed));
+        Some(format!("{} ", style.paint(status)))
+    }
+
+    /// Prints how long a command took if timing is enabled with `with_timing` or
+    /// `set timing on`
+    fn print_timing(&self, elapsed: Duration) {
+        if self.timing {
+            let took = Messages::fill(&self.messages.took, &[&format_elapsed(elapsed)]);
+            self.print_error(Paint::new(took).dimmed());
+        }
+    }
+
+    fn execute_after_command_callback(&mut self) -> core::result::Result<(), E> {
+        if let Some(callback) = self.after_command_callback {
+            match callback(&mut self.context) {
+                Ok(Some(new_prompt)) => {
+                    self.prompt.update_prefix(&new_prompt);
+                }
+                Ok(None) => {}
+                Err(err) => {
+                    self.print_error(Messages::fill(
+                        &self.messages.after_command_failed,
+                        &[&format!("{:?}", err)],
+                    ));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    #[cfg(feature = "async")]
+    async fn execute_after_command_callback_async(&mut self) -> core::result::Result<(), E> {
+        self.execute_after_command_callback()?;
+        if let Some(callback) = self.after_command_callback_async {
+            match callback(&mut self.context).await {
+                Ok(new_prompt) => {
+                    if let Some(new_prompt) = new_prompt {
+                        self.prompt.update_prefix(&new_prompt);
+                    }
+                }
+                Err(err) => {
+                    self.print_error(Messages::fill(
+                        &self.messages.after_command_failed,
+                        &[&format!("{:?}", err)],
+                    ));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    #[cfg(feature = "async")]
+    async fn handle_command_async(
+        &mut self,
+        command: &str,
+        args: &[&str],
+    ) -> core::result::Result<(), E> {
+        match find_command(&self.commands, command, &self.scope()) {
             Some(definition) => {
+                self.check_guard(command, definition.guard)?;
                 let mut argv: Vec<&str> = vec![command];
                 argv.extend(args);
-                match definition.command.clone().try_get_matches_from_mut(argv) {
-                    Ok(matches) => match (definition
-                        .callback
-                        .expect("Must be filled for sync commands"))(
-                        matches, &mut self.context
-                    ) {
-                        Ok(Some(value)) => println!("{}", value),
-                        Ok(None) => (),
-                        Err(error) => return Err(error),
-                    },
-                    Err(err) => {
-                        err.print().expect("failed to print");
+                match definition.get_matches(
+                    &argv,
+                    self.infer_choices,
+                    self.expand_paths,
+                    self.arithmetic,
+                    self.prompt_missing(),
+                ) {
+                    Ok(matches)
+                        if !self.confirmed(
+                            command,
+                            definition.confirmation.as_deref(),
+                            &matches,
+                        ) => {}
+                    Ok(matches) => {
+                        let deprecated = definition.deprecated.clone();
+                        let async_callback = definition.async_callback;
+                        let callback = definition.callback;
+                        self.warn_deprecated(command, deprecated.as_deref());
+                        self.execute_before_command_callback(command);
+                        let guard = self.cancellation.watch();
+                        let start = Instant::now();
+                        #[cfg(feature = "tracing")]
+                        let span =
+                            tracing::info_span!("command", name = command, args = args.len());
+                        let result = if let Some(async_callback) = async_callback {
+                            let catch_panics = self.catch_panics;
+                            let future = async_callback(matches, &mut self.context);
+                            #[cfg(feature = "tracing")]
+                            let future = tracing::Instrument::instrument(future, span.clone());
+                            if catch_panics {
+                                catch_unwind_async(command, future).await
+                            } else {
+                                future.await
+                            }
+                        } else {
+                            #[cfg(feature = "tracing")]
+                            let _entered = span.enter();
+                            self.call_callback(
+                                command,
+                                callback.expect("Either async or sync callback must be set"),
+                                matches,
+                            )
+                        };
+                        drop(guard);
+                        self.apply_registrations();
+                        self.handle_command_result(command, result, start.elapsed())?;
+                    }
+                    Err(errors) => {
+                        self.print_clap_errors(command, &errors);
                     }
                 };
-                self.execute_after_command_callback()?;
+                self.execute_after_command_callback_async().await?;
+            }
+            None if command == "sleep" => delay(Self::parse_sleep(args)?).await,
+            None if command == "wait-for" => self.wait_for_async(args).await?,
+            None if self.spawner.is_some() && command == "fg" => {
+                self.foreground_job_async(args).await?
+            }
+            None if command == "capture" => self.capture_async(args).await?,
+            #[cfg(feature = "scripts")]
+            None if !self.served && command == "source" => self.source_async(args).await?,
+            None => self.handle_builtin(command, args)?,
+        }
+
+        Ok(())
+    }
+
+    fn parse_line(&self, line: &str) -> core::result::Result<(String, Vec<String>), E> {
+        let (name, raw_args) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        let name = self.resolve_command(name)?;
+        if let Some(parser) = find_command(&self.commands, &name, &self.scope())
+            .and_then(|definition| definition.parser)
+        {
+            let mut values: Vec<String> = parser(raw_args.trim_start(), &self.context)?
+                .into_iter()
+                .map(|(name, value)| format!("--{}={}", name, value))
+                .collect();
+            values.sort();
+            return Ok((name, values));
+        }
+        let mut args = split_words(line)?;
+        let command: String = args.drain(..1).collect();
+        let command = self.resolve_command(&command)?;
+        if self.arithmetic {
+            for arg in args.iter_mut().filter(|arg| arg.starts_with("$(")) {
+                *arg = expand_arithmetic(arg)?;
+            }
+        }
+        Ok((command, args))
+    }
+
+    /// Expands an unambiguous command prefix to the full command name when enabled with
+    /// `with_command_prefixes`, other names are returned unchanged
+    fn resolve_command(&self, name: &str) -> Result<String> {
+        if !self.command_prefixes {
+            return Ok(name.to_string());
+        }
+        let commands = self.visible_commands();
+        let mut names: Vec<&str> = commands
+            .iter()
+            .flat_map(|command| {
+                std::iter::once(command.get_name()).chain(command.get_visible_aliases())
+            })
+            .chain(std::iter::once(self.help_command_name.as_str()))
+            .collect();
+        let exact = commands.iter().any(|command| {
+            command.get_name() == name || command.get_all_aliases().any(|a| a == name)
+        });
+        if exact || name == self.help_command_name {
+            return Ok(name.to_string());
+        }
+        names.retain(|candidate| candidate.starts_with(name));
+        names.sort_unstable();
+        names.dedup();
+        match names.as_slice() {
+            [] => Ok(name.to_string()),
+            [command] => Ok(command.to_string()),
+            candidates => Err(Error::AmbiguousCommand(
+                name.to_string(),
+                candidates
+                    .iter()
+                    .map(|candidate| candidate.to_string())
+                    .collect(),
+            )),
+        }
+    }
+
+    fn start_paste(&mut self, args: &[&str]) {
+        let sentinel = args.first().unwrap_or(&DEFAULT_PASTE_SENTINEL).to_string();
+        self.print_output(Messages::fill(&self.messages.paste_start, &[&sentinel]));
+        self.paste = Some(PasteState::Collecting {
+            sentinel,
+            lines: vec![],
+        });
+    }
+
+    /// Feeds a line into the running `paste` and returns the collected lines once
+    /// they are ready to be executed
+    fn feed_paste(&mut self, line: &str) -> Option<Vec<String>> {
+        match self.paste.take()? {
+            PasteState::Collecting {
+                sentinel,
+                mut lines,
+            } => {
+                let trimmed = line.trim();
+                if trimmed != sentinel {
+                    if !trimmed.is_empty() {
+                        lines.push(trimmed.to_string());
+                    }
+                    self.paste = Some(PasteState::Collecting { sentinel, lines });
+                    return None;
+                }
+                if !self.paste_confirmation {
+                    return Some(lines);
+                }
+                for (i, pasted) in lines.iter().enumerate() {
+                    self.print_output(format!("{:>4}  {}", i + 1, pasted));
+                }
+                self.print_output(Messages::fill(
+                    &self.messages.paste_confirm,
+                    &[&lines.len().to_string()],
+                ));
+                self.paste = Some(PasteState::Confirming(lines));
+                None
+            }
+            PasteState::Confirming(lines) => match line.trim().to_lowercase().as_str() {
+                "y" | "yes" => Some(lines),
+                _ => {
+                    self.print_output(&self.messages.paste_cancelled);
+                    None
+                }
+            },
+        }
+    }
+
+    /// What `line` runs: a reviewed paste, the lines of the `paste` builtin or a chain
+    fn line_work(&mut self, line: String) -> LineWork {
+        let line = join_continuations(line);
+        if self.is_pasted_block(&line) {
+            LineWork::Block(line)
+        } else if self.paste.is_some() {
+            match self.feed_paste(&line) {
+                Some(lines) => LineWork::Pasted(lines),
+                None => LineWork::Nothing,
+            }
+        } else {
+            LineWork::Chain(line)
+        }
+    }
+
+    fn process_line(&mut self, line: String) -> core::result::Result<(), E> {
+        match self.line_work(line) {
+            LineWork::Block(lines) => Ok(self.run_source("paste", lines.as_bytes())?),
+            LineWork::Pasted(lines) => {
+                self.push_source("paste");
+                let mut result = Ok(());
+                for (i, pasted) in lines.into_iter().enumerate() {
+                    self.set_source_line(i + 1);
+                    let executed = self.execute_chain(pasted);
+                    if let Some(stopped) = self.paste_stopped(i + 1, executed) {
+                        result = stopped;
+                        break;
+                    }
+                }
+                self.sources.pop();
+                Ok(result?)
+            }
+            LineWork::Chain(line) => self.execute_chain(line),
+            LineWork::Nothing => Ok(()),
+        }
+    }
+
+    /// Reports the error of the pasted line `number` if it failed, which stops the paste.
+    /// Returns the outcome of the error handler then, `None` to run the next line.
+    fn paste_stopped(
+        &mut self,
+        number: usize,
+        executed: core::result::Result<(), E>,
+    ) -> Option<Result<()>> {
+        if executed.is_ok() && !self.arguments_rejected.get() {
+            return None;
+        }
+        let handled = match executed {
+            Ok(()) => Ok(()),
+            Err(err) => self.handle_error(err),
+        };
+        self.print_error(Messages::fill(
+            &self.messages.paste_stopped,
+            &[&number.to_string()],
+        ));
+        Some(handled)
+    }
+
+    /// Whether `line` is a reviewed paste of several complete lines to run one by one
+    fn is_pasted_block(&self, line: &str) -> bool {
+        if self.paste_policy != PastePolicy::Review || self.paste.is_some() {
+            return false;
+        }
+        let mut pending = String::new();
+        let mut lines = 0;
+        for part in line.split('\n') {
+            pending.push_str(part);
+            if !is_complete(&pending) {
+                pending.push('\n');
+                continue;
+            }
+            if !pending.trim().is_empty() {
+                lines += 1;
+            }
+            pending.clear();
+        }
+        lines > 1
+    }
+
+    /// Executes all commands of a line chained with `;` and `&&`
+    fn execute_chain(&mut self, line: String) -> core::result::Result<(), E> {
+        let mut commands = self.chain_commands(line)?.into_iter().peekable();
+        while let Some((command, separator)) = commands.next() {
+            let result = match self.start_chained_command(&command) {
+                Ok((command, redirection)) => {
+                    let result = self.execute_line(command);
+                    self.end_chained_command(result, redirection)
+                }
+                Err(err) => Err(err),
+            };
+            if self.chain_ends(result, separator, commands.peek().is_none())? {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// The commands of a line chained with `;` and `&&`, after the line preprocessor and
+    /// the history expansion. A shell command runs right away and leaves none.
+    fn chain_commands(
+        &mut self,
+        line: String,
+    ) -> core::result::Result<Vec<(String, Separator)>, E> {
+        let line = self.preprocess_line(self.strip_comments(line));
+        if let Some(command) = self.shell_command(&line) {
+            self.current_line = line.trim().to_string();
+            self.run_shell(command)?;
+            return Ok(vec![]);
+        }
+        // before splitting, so an expanded entry like `a; b` runs both commands
+        let line = self.expand_history(line)?;
+        Ok(split_chain(&line))
+    }
+
+    /// Starts the redirection of a command of a chain, returns the command without it
+    fn start_chained_command(
+        &mut self,
+        command: &str,
+    ) -> core::result::Result<(String, Option<Redirection>), E> {
+        self.current_line = command.trim().to_string();
+        self.arguments_rejected.set(false);
+        Ok(self.start_redirect(command)?)
+    }
+
+    /// Ends the redirection of a command of a chain, a failure to end it fails the command
+    fn end_chained_command(
+        &mut self,
+        result: core::result::Result<(), E>,
+        redirection: Option<Redirection>,
+    ) -> core::result::Result<(), E> {
+        let ended = self.end_redirect(redirection);
+        result.and(ended.map_err(E::from))
+    }
+
+    /// Whether the chain stops after a command with `result`. The error of a command
+    /// followed by `;` is reported and the next command runs, other errors end the chain.
+    fn chain_ends(
+        &mut self,
+        result: core::result::Result<(), E>,
+        separator: Separator,
+        last: bool,
+    ) -> core::result::Result<bool, E> {
+        if result.is_err() || self.arguments_rejected.get() {
+            self.line_failed.set(true);
+        }
+        if let Err(err) = result {
+            if separator == Separator::And || last {
+                return Err(err);
+            }
+            self.handle_error(err)?;
+        }
+        // arguments clap rejected were reported already, they fail the command too
+        Ok(self.quit || (separator == Separator::And && self.arguments_rejected.get()))
+    }
+
+    /// The shell command of a line starting with the shell escape, served clients have
+    /// no shell escape
+    fn shell_command<'a>(&self, line: &'a str) -> Option<&'a str> {
+        let escape = self.shell_escape.filter(|_| !self.served)?;
+        line.trim_start().strip_prefix(escape).map(str::trim)
+    }
+
+    /// Runs `command` with the system shell. Its output goes straight to the terminal, so
+    /// interactive programs work, and to the configured outputs otherwise.
+    fn run_shell(&mut self, command: &str) -> Result<()> {
+        let status = if self.terminal_output {
+            redirect::shell(command).status()?
+        } else {
+            let output = redirect::shell(command).output()?;
+            self.output.get_mut().write_all(&output.stdout)?;
+            self.error_output.get_mut().write_all(&output.stderr)?;
+            output.status
+        };
+        if !status.success() {
+            return Err(Error::ShellCommandFailed(
+                command.to_string(),
+                status.to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Sends the output of `line` to the program or file after a `|` or `>` in it until
+    /// `end_redirect`, returns the command without the redirection
+    fn start_redirect(&mut self, line: &str) -> Result<(String, Option<Redirection>)> {
+        if !self.redirects {
+            return Ok((line.to_string(), None));
+        }
+        let (command, redirect) = split_redirect(line)?;
+        let Some(redirect) = redirect else {
+            return Ok((command, None));
+        };
+        let (writer, child) = redirect::open(&redirect)?;
+        let redirection = Redirection {
+            previous: std::mem::replace(self.output.get_mut(), writer),
+            terminal_output: self.terminal_output,
+            child,
+        };
+        self.terminal_output = false;
+        Ok((command, Some(redirection)))
+    }
+
+    /// Restores the output after a redirected command ran
+    fn end_redirect(&mut self, redirection: Option<Redirection>) -> Result<()> {
+        let Some(Redirection {
+            previous,
+            terminal_output,
+            child,
+        }) = redirection
+        else {
+            return Ok(());
+        };
+        self.terminal_output = terminal_output;
+        let mut redirected = std::mem::replace(self.output.get_mut(), previous);
+        redirected.flush()?;
+        // closes the input of a piped program so it can finish
+        drop(redirected);
+        if let Some(mut child) = child {
+            child.wait()?;
+        }
+        Ok(())
+    }
+
+    fn strip_comments(&self, line: String) -> String {
+        if self.comments && line.contains('#') {
+            strip_comments(&line)
+        } else {
+            line
+        }
+    }
+
+    fn preprocess_line(&self, line: String) -> String {
+        match self.line_preprocessor {
+            Some(preprocessor) => preprocessor(line, &self.context),
+            None => line,
+        }
+    }
+
+    /// Expands the session variables of a line. The `echo` and `print` builtins also see
+    /// environment variables, unset ones are empty, in the same pass so `$$` stays a `$`.
+    fn expand_line_variables(&self, line: &str) -> String {
+        let command = line.split_whitespace().next().unwrap_or_default();
+        let environment = matches!(command, "echo" | "print")
+            && find_command(&self.commands, command, &self.scope()).is_none()
+            && !self.mounts.contains_key(command);
+        expand_session_variables(line, |name| match self.variables.get(name) {
+            Some(value) => Some(value),
+            None if environment => Some(std::env::var(name).unwrap_or_default()),
+            None => None,
+        })
+    }
+
+    /// Expands and tokenizes a line, returns `None` for empty lines
+    pub(crate) fn prepare_line(&self, line: String) -> core::result::Result<Option<Invocation>, E> {
+        let line = if line.contains('$') {
+            self.expand_line_variables(&line)
+        } else {
+            line
+        };
+        let trimmed = line.trim();
+        if trimmed.is_empty() {
+            return Ok(None);
+        }
+        let (command, args) = self.parse_line(trimmed)?;
+        Ok(Some(Invocation {
+            number: 0,
+            line: trimmed.to_string(),
+            command,
+            args,
+            success: false,
+        }))
+    }
+
+    fn execute_line(&mut self, line: String) -> core::result::Result<(), E> {
+        match self.foreground_invocation(line)? {
+            Some(invocation) => self.execute_invocation(invocation),
+            None => Ok(()),
+        }
+    }
+
+    /// The invocation of a line to run in the foreground. A line ending with `&` starts a
+    /// background job instead and has none, like an empty line.
+    fn foreground_invocation(
+        &mut self,
+        line: String,
+    ) -> core::result::Result<Option<Invocation>, E> {
+        if let Some(line) = self.background_line(&line) {
+            self.start_job(line)?;
+            return Ok(None);
+        }
+        self.prepare_line(line)
+    }
+
+    /// Executes a parsed command and records it in the invocation history
+    fn execute_invocation(&mut self, invocation: Invocation) -> core::result::Result<(), E> {
+        let args: Vec<&str> = invocation.args.iter().map(String::as_str).collect();
+        let result = self.handle_command(&invocation.command, &args);
+        self.record_invocation(invocation, result.is_ok());
+        result
+    }
+
+    /// Keeps an executed command for [Repl::rerun](struct.Repl.html#method.rerun)
+    pub(crate) fn record_invocation(&mut self, mut invocation: Invocation, success: bool) {
+        invocation.success = success;
+        self.invocation_count += 1;
+        invocation.number = self.invocation_count;
+        self.invocations.push_back(invocation);
+        while self.invocations.len() > INVOCATION_CAPACITY {
+            self.invocations.pop_front();
+        }
+    }
+
+    /// The commands executed in this session with their parsed arguments, oldest first.
+    /// Only the last 100 invocations are kept.
+    pub fn last_invocations(&self) -> impl DoubleEndedIterator<Item = &Invocation> {
+        self.invocations.iter()
+    }
+
+    fn invocation(&self, number: usize) -> Result<Invocation> {
+        self.invocations
+            .iter()
+            .find(|invocation| invocation.number == number)
+            .cloned()
+            .ok_or(Error::InvocationNotFound(number))
+    }
+
+    /// Run the invocation with the given number again with the same parsed arguments
+    pub fn rerun(&mut self, number: usize) -> core::result::Result<(), E> {
+        let invocation = self.invocation(number)?;
+        self.execute_invocation(invocation)
+    }
+
+    #[cfg(feature = "async")]
+    async fn process_line_async(&mut self, line: String) -> core::result::Result<(), E> {
+        match self.line_work(line) {
+            LineWork::Block(lines) => Ok(self.run_source_async("paste", lines.as_bytes()).await?),
+            LineWork::Pasted(lines) => {
+                self.push_source("paste");
+                let mut result = Ok(());
+                for (i, pasted) in lines.into_iter().enumerate() {
+                    self.set_source_line(i + 1);
+                    let executed = self.execute_chain_async(pasted).await;
+                    if let Some(stopped) = self.paste_stopped(i + 1, executed) {
+                        result = stopped;
+                        break;
+                    }
+                }
+                self.sources.pop();
+                Ok(result?)
+            }
+            LineWork::Chain(line) => self.execute_chain_async(line).await,
+            LineWork::Nothing => Ok(()),
+        }
+    }
+
+    #[cfg(feature = "async")]
+    async fn execute_chain_async(&mut self, line: String) -> core::result::Result<(), E> {
+        let mut commands = self.chain_commands(line)?.into_iter().peekable();
+        while let Some((command, separator)) = commands.next() {
+            let result = match self.start_chained_command(&command) {
+                Ok((command, redirection)) => {
+                    let result = self.execute_line_async(command).await;
+                    self.end_chained_command(result, redirection)
+                }
+                Err(err) => Err(err),
+            };
+            if self.chain_ends(result, separator, commands.peek().is_none())? {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "async")]
+    async fn execute_line_async(&mut self, line: String) -> core::result::Result<(), E> {
+        match self.foreground_invocation(line)? {
+            Some(invocation) => self.execute_invocation_async(invocation).await,
+            None => Ok(()),
+        }
+    }
+
+    #[cfg(feature = "async")]
+    async fn execute_invocation_async(
+        &mut self,
+        invocation: Invocation,
+    ) -> core::result::Result<(), E> {
+        let args: Vec<&str> = invocation.args.iter().map(String::as_str).collect();
+        let result = self.handle_command_async(&invocation.command, &args).await;
+        self.record_invocation(invocation, result.is_ok());
+        result
+    }
+
+    /// Run the invocation with the given number again with the same parsed arguments
+    #[cfg(feature = "async")]
+    pub async fn rerun_async(&mut self, number: usize) -> core::result::Result<(), E> {
+        let invocation = self.invocation(number)?;
+        self.execute_invocation_async(invocation).await
+    }
+
+    fn print_spacing(&self) {
+        for _ in 0..self.output_spacing {
+            self.print_output("");
+        }
+    }
+
+    fn run_health_check(&mut self) -> Result<()> {
+        let callback = match self.health_check {
+            Some(callback) => callback,
+            None => return Ok(()),
+        };
+        let items = match callback(&mut self.context) {
+            Ok(items) => items,
+            Err(err) => return self.handle_error(err),
+        };
+        for item in &items {
+            self.print_output(item);
+        }
+        let failed: Vec<String> = items
+            .into_iter()
+            .filter(|item| item.status == HealthStatus::Fail)
+            .map(|item| item.name)
+            .collect();
+        if self.health_check_strict && !failed.is_empty() {
+            return Err(Error::HealthCheckFailed(failed));
+        }
+
+        Ok(())
+    }
+
+    /// Updates completer and highlighter to the commands visible for the current context
+    fn update_line_editor(&self, line_editor: Reedline) -> Reedline {
+        let commands = self.visible_commands();
+        let mut valid_commands: Vec<String> = commands
+            .iter()
+            .flat_map(|command| {
+                std::iter::once(command.get_name()).chain(command.get_visible_aliases())
+            })
+            .map(|name| name.to_string())
+            .collect();
+        valid_commands.push(self.help_command_name.clone());
+        let mut line_editor = line_editor;
+        if self.hinter_enabled && self.command_hints {
+            line_editor = line_editor.with_hinter(Box::new(CommandHinter::new(
+                valid_commands.clone(),
+                self.active_theme().hint_style,
+            )));
+        }
+        let highlighter: Box<dyn Highlighter> = match &self.highlighter {
+            Some(highlighter) => Box::new(highlighter.clone()),
+            None => {
+                let help = Command::new(&self.help_command_name)
+                    .arg(Arg::new("command").num_args(0..))
+                    .arg(Arg::new("verbose").short('v').action(ArgAction::Count));
+                let mut highlighted = commands.clone();
+                highlighted.push(help);
+                Box::new(
+                    ReplHighlighter::new(highlighted, self.active_theme())
+                        .with_shell_escape(self.shell_escape),
+                )
+            }
+        };
+        line_editor
+            .with_completer(Box::new(self.completer(commands)))
+            .with_highlighter(highlighter)
+    }
+
+    /// The completer of `commands` and the help command
+    fn completer(&self, commands: Vec<Command>) -> ReplCompleter {
+        ReplCompleter::new(self.completion_index(commands))
+            .with_values(self.completion_values())
+            .with_matching(self.completion_matching)
+            .with_help(&self.help_command_name, &self.messages.help_about)
+    }
+
+    /// What Tab at the end of `line` offers for the current context
+    pub(crate) fn complete(&self, line: &str) -> Vec<String> {
+        self.completer(self.visible_commands())
+            .complete(line, line.len())
+            .into_iter()
+            .map(|suggestion| suggestion.value)
+            .collect()
+    }
+
+    /// The completion index of `commands`, the one of the last prompt if the commands are
+    /// the same, so large command sets aren't indexed again before every prompt
+    fn completion_index(&self, commands: Vec<Command>) -> Arc<CompletionIndex> {
+        let mut cached = self
+            .completion_index
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        match &*cached {
+            Some(index) if index.indexes(&commands) => Arc::clone(index),
+            _ => {
+                let index = Arc::new(CompletionIndex::new(commands));
+                *cached = Some(Arc::clone(&index));
+                index
             }
+        }
+    }
+
+    /// Runs the argument completers of the available commands against the context
+    fn completion_values(&self) -> HashMap<(String, String), Vec<String>> {
+        let scope = self.scope();
+        self.commands
+            .values()
+            .filter(|command| command.is_available(&scope))
+            .flat_map(|command| {
+                command.completers.iter().map(|(arg, callback)| {
+                    (
+                        (command.command.get_name().to_string(), arg.clone()),
+                        callback(&self.context),
+                    )
+                })
+            })
+            .collect()
+    }
+
+    /// The plain editor without a terminal or with the `plain_editor` feature, reedline
+    /// otherwise
+    fn build_editor(&mut self) -> Result<LineEditor> {
+        if self.plain_editor() {
+            return Ok(LineEditor::Plain(self.take_validator()));
+        }
+        Ok(LineEditor::Reedline(Box::new(self.build_line_editor()?)))
+    }
+
+    /// The validator of `with_validator`, or the default one
+    pub(crate) fn take_validator(&mut self) -> Box<dyn Validator> {
+        self.validator
+            .take()
+            .unwrap_or_else(|| Box::new(ReplValidator))
+    }
+
+    /// Whether lines are read without reedline: forced with `with_force_plain` and
+    /// `with_force_interactive`, else with the `plain_editor` feature, when stdin or stdout
+    /// is not a terminal, e.g. in CI or `docker exec` without `-t`, and in dumb terminals
+    /// like the Emacs shell
+    fn plain_editor(&self) -> bool {
+        match self.interactive {
+            Some(interactive) => !interactive,
             None => {
-                if command == "help" {
-                    self.show_help(args)?;
-                } else {
-                    return Err(Error::UnknownCommand(command.to_string()).into());
+                cfg!(feature = "plain_editor")
+                    || !std::io::stdin().is_terminal()
+                    || !std::io::stdout().is_terminal()
+                    || std::env::var("TERM").is_ok_and(|term| term == "dumb")
+            }
+        }
+    }
+
+    /// Whether missing required arguments are asked for, which needs someone at a terminal
+    pub(crate) fn prompt_missing(&self) -> bool {
+        self.prompt_missing_args && self.terminal_output && std::io::stdin().is_terminal()
+    }
+
+    /// Updates the completer and highlighter of reedline, the plain editor has none
+    fn update_editor(&self, line_editor: LineEditor) -> LineEditor {
+        match line_editor {
+            LineEditor::Reedline(line_editor) => {
+                LineEditor::Reedline(Box::new(self.update_line_editor(*line_editor)))
+            }
+            LineEditor::Plain(validator) => LineEditor::Plain(validator),
+        }
+    }
+
+    fn build_line_editor(&mut self) -> Result<Reedline> {
+        let mut keybindings = self.keybindings.clone();
+        if self.ctrl_c_action == CtrlCAction::Ignore {
+            keybindings.remove_binding(KeyModifiers::CONTROL, KeyCode::Char('c'));
+        }
+        let theme = self.active_theme();
+        let completion_menu: Box<dyn Menu> = match self.menu {
+            MenuKind::Columnar => {
+                let mut menu = ColumnarMenu::default()
+                    .with_name("completion_menu")
+                    .with_text_style(theme.menu_style)
+                    .with_selected_text_style(theme.menu_selected_style)
+                    .with_description_text_style(theme.menu_description_style);
+                if let Some(columns) = self.menu_columns {
+                    menu = menu.with_columns(columns);
                 }
+                if let Some(padding) = self.menu_column_padding {
+                    menu = menu.with_column_padding(padding);
+                }
+                Box::new(menu)
             }
+            MenuKind::List => Box::new(
+                ListMenu::default()
+                    .with_name("completion_menu")
+                    .with_text_style(theme.menu_style)
+                    .with_selected_text_style(theme.menu_selected_style)
+                    .with_description_text_style(theme.menu_description_style),
+            ),
+        };
+        let validator = self.take_validator();
+        let mut line_editor = Reedline::create()
+            .with_edit_mode(Box::new(Emacs::new(keybindings)))
+            .with_menu(ReedlineMenu::EngineCompleter(completion_menu))
+            .with_menu(ReedlineMenu::WithCompleter {
+                menu: Box::new(
+                    ColumnarMenu::default()
+                        .with_name(HELP_MENU)
+                        .with_columns(1)
+                        .with_text_style(theme.menu_style)
+                        .with_selected_text_style(theme.menu_selected_style)
+                        .with_description_text_style(theme.menu_description_style)
+                        .with_only_buffer_difference(false),
+                ),
+                completer: Box::new(HelpCompleter::new(Arc::clone(&self.completion_index))),
+            })
+            .with_validator(validator)
+            .with_partial_completions(self.partial_completions)
+            .with_quick_completions(self.quick_completions)
+            .use_bracketed_paste(self.paste_policy == PastePolicy::Review);
+
+        #[cfg(feature = "external_printer")]
+        {
+            line_editor = line_editor.with_external_printer(self.printer.clone());
+        }
+
+        if self.hinter_enabled {
+            line_editor = match self.hinter.take() {
+                Some(hinter) => line_editor.with_hinter(hinter),
+                None => line_editor.with_hinter(Box::new(
+                    DefaultHinter::default().with_style(self.active_theme().hint_style),
+                )),
+            };
+        }
+
+        line_editor = line_editor.with_history(self.history_store()?.editor_history());
+        if let Some(transient_prompt) = &self.transient_prompt {
+            let mut prompt = ReplPrompt::new(transient_prompt);
+            prompt.update_indicator("");
+            prompt.update_right(Some(String::new()));
+            line_editor = line_editor.with_transient_prompt(Box::new(prompt));
+        }
+        if let Some(configurator) = self.reedline_configurator {
+            line_editor = configurator(line_editor);
+        }
+
+        Ok(line_editor)
+    }
+
+    #[cfg(feature = "scripts")]
+    /// Executs REPL taking an object with a `std::io::BufRead` implementation
+    /// as input
+    /// This is useful for executing scripts. Exampel structure that can be used here
+    /// is `std::io::BufReader` built on `std::fs::File`
+    pub fn run_with_reader(&mut self, reader: impl std::io::BufRead) -> Result<()> {
+        self.apply_ansi();
+        self.open_transcript()?;
+        self.quit = false;
+        self.started = Some(Instant::now());
+        self.restore_context();
+        if let Some(callback) = self.on_start_callback {
+            callback(&mut self.context);
         }
+        self.emit(ReplEvent::SessionStarted);
+        let result = self.run_source("<input>", reader);
+        let saved = self.save_context();
+        if let Some(callback) = self.on_exit_callback {
+            callback(&mut self.context);
+        }
+        self.emit(ReplEvent::SessionEnded);
 
-        Ok(())
+        result.and(saved)
     }
 
-    fn execute_after_command_callback(&mut self) -> core::result::Result<(), E> {
-        if let Some(callback) = self.after_command_callback {
-            match callback(&mut self.context) {
-                Ok(Some(new_prompt)) => {
-                    self.prompt.update_prefix(&new_prompt);
-                }
-                Ok(None) => {}
-                Err(err) => {
-                    eprintln!("failed to execute after_command_callback {:?}", err);
-                }
-            }
+    /// Runs a single command given as `args`, e.g. `std::env::args().skip(1)`, and returns,
+    /// so the tool can be used from the shell with the same definitions, validation and
+    /// rendering as in the REPL. Problems are printed like in the REPL, an error is returned
+    /// afterwards when the command failed so the process can exit with a nonzero status.
+    /// Without arguments the help is shown.
+    pub fn run_command(&mut self, args: &[String]) -> Result<()> {
+        self.apply_ansi();
+        self.open_transcript()?;
+        self.quit = false;
+        self.started = Some(Instant::now());
+        self.restore_context();
+        if let Some(callback) = self.on_start_callback {
+            callback(&mut self.context);
         }
+        self.emit(ReplEvent::SessionStarted);
+        let result = self.run_args(args);
+        let saved = self.save_context();
+        if let Some(callback) = self.on_exit_callback {
+            callback(&mut self.context);
+        }
+        self.emit(ReplEvent::SessionEnded);
 
-        Ok(())
+        result.and(saved).and(self.take_write_error())
     }
 
-    #[cfg(feature = "async")]
-    async fn execute_after_command_callback_async(&mut self) -> core::result::Result<(), E> {
-        self.execute_after_command_callback()?;
-        if let Some(callback) = self.after_command_callback_async {
-            match callback(&mut self.context).await {
-                Ok(new_prompt) => {
-                    if let Some(new_prompt) = new_prompt {
-                        self.prompt.update_prefix(&new_prompt);
-                    }
-                }
-                Err(err) => {
-                    eprintln!("failed to execute after_command_callback {:?}", err);
-                }
+    fn run_args(&mut self, args: &[String]) -> Result<()> {
+        let (command, args) = match args.split_first() {
+            Some((command, args)) => (self.resolve_command(command)?, args),
+            None => (self.help_command_name.clone(), args),
+        };
+        let args: Vec<&str> = args.iter().map(String::as_str).collect();
+        self.current_line = std::iter::once(command.as_str())
+            .chain(args.iter().copied())
+            .collect::<Vec<_>>()
+            .join(" ");
+        self.arguments_rejected.set(false);
+        let failed = match self.handle_command(&command, &args) {
+            Ok(()) => self.arguments_rejected.get(),
+            Err(err) => {
+                self.handle_error(err)?;
+                true
             }
+        };
+        if failed {
+            return Err(Error::CommandFailed(command));
         }
 
         Ok(())
     }
 
+    /// How often each command ran and failed and how long it took during this session, by
+    /// command name, to learn what users of the REPL actually run. Also shown by the
+    /// `stats` builtin.
+    pub fn stats(&self) -> &BTreeMap<String, CommandStats> {
+        &self.stats
+    }
+
+    /// Executes `line` like typed input and returns its output and errors instead of
+    /// printing them, to drive the REPL from a frontend other than the terminal, e.g. a GUI.
+    /// `run` is the terminal frontend over the same commands, help and validation. A line
+    /// with arguments clap rejected doesn't succeed.
+    pub fn feed_line(&mut self, line: &str) -> ReplResponse {
+        let fed = self.start_fed_line(line);
+        let result = self.process_line(line.to_string());
+        self.finish_fed_line(fed, line, result)
+    }
+
+    /// Executes `line` like typed input and returns its output and errors instead of
+    /// printing them, see [feed_line](#method.feed_line)
     #[cfg(feature = "async")]
-    async fn handle_command_async(
+    pub async fn feed_line_async(&mut self, line: &str) -> ReplResponse {
+        let fed = self.start_fed_line(line);
+        let result = self.process_line_async(line.to_string()).await;
+        self.finish_fed_line(fed, line, result)
+    }
+
+    /// Captures the outputs and records `line` in the history before it is executed
+    fn start_fed_line(&mut self, line: &str) -> FedLine {
+        let (output, errors) = (Capture::default(), Capture::default());
+        let replaced = self.replace_outputs(Box::new(output.clone()), Box::new(errors.clone()));
+        let terminal_output = std::mem::replace(&mut self.terminal_output, false);
+        let redirects = std::mem::replace(&mut self.redirects, false);
+        self.quit = false;
+        if let Err(err) = self.open_transcript() {
+            self.print_error(err);
+        }
+        let recorded = self.record_history(line).unwrap_or_else(|err| {
+            self.print_error(err);
+            false
+        });
+        self.record_input(line);
+        self.arguments_rejected.set(false);
+        self.line_failed.set(false);
+        FedLine {
+            output,
+            errors,
+            replaced,
+            terminal_output,
+            redirects,
+            recorded,
+        }
+    }
+
+    /// Stores the outcome of a fed line and restores the outputs, returns what it printed
+    fn finish_fed_line(
         &mut self,
-        command: &str,
-        args: &[&str],
-    ) -> core::result::Result<(), E> {
-        match self.commands.get(command) {
-            Some(definition) => {
-                let mut argv: Vec<&str> = vec![command];
-                argv.extend(args);
-                match definition.command.clone().try_get_matches_from_mut(argv) {
-                    Ok(matches) => match if let Some(async_callback) = definition.async_callback {
-                        async_callback(matches, &mut self.context).await
-                    } else {
-                        definition
-                            .callback
-                            .expect("Either async or sync callback must be set")(
-                            matches,
-                            &mut self.context,
-                        )
-                    } {
-                        Ok(Some(value)) => println!("{}", value),
-                        Ok(None) => (),
-                        Err(error) => return Err(error),
-                    },
-                    Err(err) => {
-                        err.print().expect("failed to print");
-                    }
-                };
-                self.execute_after_command_callback_async().await?;
+        fed: FedLine,
+        line: &str,
+        result: core::result::Result<(), E>,
+    ) -> ReplResponse {
+        if fed.recorded {
+            if let Err(err) = self.store_history(line, result.is_ok()) {
+                self.print_error(err);
             }
-            None => {
-                if command == "help" {
-                    self.show_help(args)?;
-                } else {
-                    return Err(Error::UnknownCommand(command.to_string()).into());
+        }
+        let success = match result {
+            Ok(()) => !self.arguments_rejected.get() && !self.line_failed.get(),
+            Err(err) => {
+                if let Err(err) = self.handle_error(err) {
+                    self.print_error(err);
                 }
+                false
             }
+        };
+        self.replace_outputs(fed.replaced.0, fed.replaced.1);
+        self.terminal_output = fed.terminal_output;
+        self.redirects = fed.redirects;
+        ReplResponse {
+            output: fed.output.take(),
+            errors: fed.errors.take(),
+            success,
+            quit: std::mem::replace(&mut self.quit, false),
         }
+    }
 
-        Ok(())
+    /// Swaps the output and error output, returns the previous ones
+    pub(crate) fn replace_outputs(
+        &mut self,
+        output: Box<dyn Write + Send>,
+        error_output: Box<dyn Write + Send>,
+    ) -> (Box<dyn Write + Send>, Box<dyn Write + Send>) {
+        (
+            std::mem::replace(self.output.get_mut(), output),
+            std::mem::replace(self.error_output.get_mut(), error_output),
+        )
     }
 
-    fn parse_line(&self, line: &str) -> (String, Vec<String>) {
-        let r = regex::Regex::new(r#"("[^"\n]+"|[\S]+)"#).unwrap();
-        let mut args = r
-            .captures_iter(line)
-            .map(|a| a[0].to_string().replace('\"', ""))
-            .collect::<Vec<String>>();
-        let command: String = args.drain(..1).collect();
-        (command, args)
+    /// Loads the context saved by the last session, a snapshot that can't be loaded is
+    /// reported and the session starts with the initial context
+    fn restore_context(&mut self) {
+        let Some((_, load, path)) = &self.context_persistence else {
+            return;
+        };
+        let (load, path) = (*load, path.clone());
+        let restored = match std::fs::read_to_string(&path) {
+            Ok(snapshot) => load(&mut self.context, &snapshot).map_err(|err| err.to_string()),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(err) => Err(err.to_string()),
+        };
+        if let Err(err) = restored {
+            let path = path.display().to_string();
+            self.print_error(Messages::fill(
+                &self.messages.context_restore_failed,
+                &[&path, &err],
+            ));
+        }
     }
 
-    fn process_line(&mut self, line: String) -> core::result::Result<(), E> {
-        let trimmed = line.trim();
-        if !trimmed.is_empty() {
-            let (command, args) = self.parse_line(trimmed);
-            let args = args.iter().fold(vec![], |mut state, a| {
-                state.push(a.as_str());
-                state
-            });
-            self.handle_command(&command, &args)?;
+    /// Writes the context to the file of `with_context_persistence`
+    fn save_context(&self) -> Result<()> {
+        let Some((save, _, path)) = &self.context_persistence else {
+            return Ok(());
+        };
+        let failed = |err: String| Error::ContextNotSaved(path.display().to_string(), err);
+        let snapshot = save(&self.context).map_err(|err| failed(err.to_string()))?;
+        std::fs::write(path, snapshot).map_err(|err| failed(err.to_string()))
+    }
+
+    /// Executes the lines of a script, errors are reported with the script name and line
+    fn run_source(&mut self, name: &str, reader: impl std::io::BufRead) -> Result<()> {
+        self.push_source(name);
+        let result = self.run_lines(reader, 0);
+        self.sources.pop();
+        result
+    }
+
+    /// Executes lines numbered after the first `offset` lines of the current source
+    pub(crate) fn run_lines(&mut self, reader: impl std::io::BufRead, offset: usize) -> Result<()> {
+        for line in ScriptLines::new(reader, offset) {
+            let Some(line) = self.start_script_line(line?) else {
+                continue;
+            };
+            match self.script_block(&line) {
+                Some(block) => self.run_block(block)?,
+                None => {
+                    let result = self.process_line(line);
+                    self.finish_script_line(result)?;
+                }
+            }
+            self.take_write_error()?;
+            if self.quit {
+                break;
+            }
         }
+
         Ok(())
     }
 
-    #[cfg(feature = "async")]
-    async fn process_line_async(&mut self, line: String) -> core::result::Result<(), E> {
-        let trimmed = line.trim();
-        if !trimmed.is_empty() {
-            let (command, args) = self.parse_line(trimmed);
-            let args = args.iter().fold(vec![], |mut state, a| {
-                state.push(a.as_str());
-                state
-            });
-            self.handle_command_async(&command, &args).await?;
+    /// The `if` or `repeat` block on `line`, unless a command is called like the keyword
+    fn script_block<'a>(&self, line: &'a str) -> Option<Result<Block<'a>>> {
+        let keyword = line.split_whitespace().next()?;
+        if find_command(&self.commands, keyword, &self.scope()).is_some() {
+            return None;
+        }
+        parse_block(line)
+    }
+
+    /// Attributes errors to the script line `number` starts on, returns the line unless
+    /// it has no command
+    fn start_script_line(&mut self, (number, line): (usize, String)) -> Option<String> {
+        self.set_source_line(number);
+        if strip_comments(&line).trim().is_empty() {
+            return None;
         }
+        self.arguments_rejected.set(false);
+        Some(line)
+    }
+
+    /// Reports the error of a script line and sets `$status` to its outcome, arguments clap
+    /// rejected fail the line too
+    fn finish_script_line(&mut self, result: core::result::Result<(), E>) -> Result<()> {
+        let failed = match result {
+            Ok(()) => self.arguments_rejected.get(),
+            Err(err) => {
+                self.variables.set(STATUS_VARIABLE, "1");
+                return self.handle_error(err);
+            }
+        };
+        self.variables
+            .set(STATUS_VARIABLE, if failed { "1" } else { "0" });
+
         Ok(())
     }
 
-    fn build_line_editor(&mut self) -> Result<Reedline> {
-        let mut valid_commands: Vec<String> = self
-            .commands
-            .iter()
-            .map(|(_, command)| command.name.clone())
-            .collect();
-        valid_commands.push("help".to_string());
-        let completer = Box::new(ReplCompleter::new(&self.commands));
-        let completion_menu = Box::new(ColumnarMenu::default().with_name("completion_menu"));
-        let validator = Box::new(DefaultValidator);
-        let mut line_editor = Reedline::create()
-            .with_edit_mode(Box::new(Emacs::new(self.keybindings.clone())))
-            .with_completer(completer)
-            .with_menu(ReedlineMenu::EngineCompleter(completion_menu))
-            .with_highlighter(Box::new(ExampleHighlighter::new(valid_commands.clone())))
-            .with_validator(validator)
-            .with_partial_completions(self.partial_completions)
-            .with_quick_completions(self.quick_completions);
+    /// Runs the branch of an `if` its condition selects or the body of a `repeat`, a
+    /// condition or count that isn't valid is reported like a failed command
+    fn run_block(&mut self, block: Result<Block<'_>>) -> Result<()> {
+        let Some((body, times)) = self.block_runs(block)? else {
+            return Ok(());
+        };
+        for _ in 0..times {
+            self.run_body(body)?;
+            if self.quit {
+                break;
+            }
+        }
 
-        if self.hinter_enabled {
-            line_editor = line_editor.with_hinter(Box::new(
-                DefaultHinter::default().with_style(self.hinter_style),
-            ));
+        Ok(())
+    }
+
+    /// The body a block runs and how often, `None` if it runs nothing or its condition or
+    /// count isn't valid, which is reported
+    fn block_runs<'a>(&mut self, block: Result<Block<'a>>) -> Result<Option<(Body<'a>, usize)>> {
+        match self.block_body(block) {
+            Ok((Some(body), times)) => Ok(Some((body, times))),
+            Ok((None, _)) => Ok(None),
+            Err(err) => self.finish_script_line(Err(err.into())).map(|()| None),
         }
+    }
 
-        if let Some(history_path) = &self.history {
-            let capacity = self.history_capacity.unwrap();
-            let history =
-                FileBackedHistory::with_file(capacity, history_path.to_path_buf()).unwrap();
-            line_editor = line_editor.with_history(Box::new(history));
+    /// The body a block runs and how often
+    fn block_body<'a>(&self, block: Result<Block<'a>>) -> Result<(Option<Body<'a>>, usize)> {
+        let expand = |text: &str| expand_session_variables(text, |name| self.variables.get(name));
+        Ok(match block? {
+            Block::If {
+                condition,
+                then,
+                otherwise,
+            } => match evaluate(&expand(condition))? {
+                true => (Some(then), 1),
+                false => (otherwise, 1),
+            },
+            Block::Repeat { count, body } => (Some(body), repetitions(&expand(count))?),
+        })
+    }
+
+    /// Lines of the current source before the line the running block starts on
+    fn block_offset(&self) -> usize {
+        self.sources
+            .last()
+            .map_or(0, |source| source.line.saturating_sub(1))
+    }
+
+    /// Runs the lines of a block body, numbered from the line of the script it starts on
+    fn run_body(&mut self, body: Body<'_>) -> Result<()> {
+        let offset = self.block_offset();
+        let result = self.run_lines(body.text.as_bytes(), offset + body.line);
+        self.set_source_line(offset + 1);
+        result
+    }
+
+    /// Enters a script or pasted block, errors are attributed to it until it is popped
+    fn push_source(&mut self, name: &str) {
+        self.sources.push(InputSource {
+            name: name.to_string(),
+            line: 0,
+        });
+    }
+
+    fn set_source_line(&mut self, line: usize) {
+        if let Some(source) = self.sources.last_mut() {
+            source.line = line;
         }
+    }
 
-        Ok(line_editor)
+    /// Opens the script of the `source` builtin, unless scripts are nested too deeply
+    #[cfg(feature = "scripts")]
+    fn open_source(&self, args: &[&str]) -> Result<(String, std::io::BufReader<std::fs::File>)> {
+        let path = source_path(args)?;
+        if self.sources.len() >= MAX_SOURCE_DEPTH {
+            return Err(Error::SourceDepthExceeded(path, MAX_SOURCE_DEPTH));
+        }
+        let file = std::fs::File::open(&path)?;
+        Ok((path, std::io::BufReader::new(file)))
     }
 
+    /// Executes the script `path` from the `source` builtin
     #[cfg(feature = "scripts")]
-    /// Executs REPL taking an object with a `std::io::BufRead` implementation
-    /// as input
-    /// This is useful for executing scripts. Exampel structure that can be used here
-    /// is `std::io::BufReader` built on `std::fs::File`
-    pub fn run_with_reader(&mut self, reader: impl std::io::BufRead) -> Result<()> {
-        let lines = reader.lines();
-        for line in lines {
-            let line = line.expect("failed to read line");
-            if let Err(err) = self.process_line(line) {
-                (self.error_handler)(err, self)?;
+    fn source(&mut self, args: &[&str]) -> Result<()> {
+        let (path, reader) = self.open_source(args)?;
+        self.run_source(&path, reader)
+    }
+
+    #[cfg(all(feature = "scripts", feature = "async"))]
+    async fn source_async(&mut self, args: &[&str]) -> Result<()> {
+        let (path, reader) = self.open_source(args)?;
+        self.run_source_async(&path, reader).await
+    }
+
+    /// Executes the lines of a script, errors are reported with the script name and line
+    #[cfg(feature = "async")]
+    async fn run_source_async(&mut self, name: &str, reader: impl std::io::BufRead) -> Result<()> {
+        self.push_source(name);
+        let result = self.run_lines_async(reader, 0).await;
+        self.sources.pop();
+        result
+    }
+
+    #[cfg(feature = "async")]
+    async fn run_lines_async(
+        &mut self,
+        reader: impl std::io::BufRead,
+        offset: usize,
+    ) -> Result<()> {
+        for line in ScriptLines::new(reader, offset) {
+            let Some(line) = self.start_script_line(line?) else {
+                continue;
+            };
+            match self.script_block(&line) {
+                Some(block) => self.run_block_async(block).await?,
+                None => {
+                    // boxed to break the recursion through `handle_command_async`
+                    let processed: Pin<Box<dyn Future<Output = core::result::Result<(), E>> + '_>> =
+                        Box::pin(self.process_line_async(line));
+                    let result = processed.await;
+                    self.finish_script_line(result)?;
+                }
+            }
+            self.take_write_error()?;
+            if self.quit {
+                break;
+            }
+        }
+
+        Ok(())
+    }
+
+    #[cfg(feature = "async")]
+    async fn run_block_async(&mut self, block: Result<Block<'_>>) -> Result<()> {
+        let Some((body, times)) = self.block_runs(block)? else {
+            return Ok(());
+        };
+        for _ in 0..times {
+            let offset = self.block_offset();
+            // boxed to break the recursion through nested blocks
+            let run: Pin<Box<dyn Future<Output = Result<()>> + '_>> =
+                Box::pin(self.run_lines_async(body.text.as_bytes(), offset + body.line));
+            let result = run.await;
+            self.set_source_line(offset + 1);
+            result?;
+            if self.quit {
+                break;
             }
         }
 
         Ok(())
     }
 
+    /// The init script with its name, if there is one
+    fn open_init_script(&self) -> Option<(String, std::io::BufReader<std::fs::File>)> {
+        let path = self.init_script.as_ref()?;
+        let file = std::fs::File::open(path).ok()?;
+        Some((
+            path.to_string_lossy().to_string(),
+            std::io::BufReader::new(file),
+        ))
+    }
+
+    /// Runs the init script and the init commands
+    fn run_init(&mut self) -> Result<()> {
+        if let Some((name, reader)) = self.open_init_script() {
+            self.run_source(&name, reader)?;
+        }
+        let commands = self.init_commands.join("\n");
+        self.run_source("<init>", commands.as_bytes())
+    }
+
+    #[cfg(feature = "async")]
+    async fn run_init_async(&mut self) -> Result<()> {
+        if let Some((name, reader)) = self.open_init_script() {
+            self.run_source_async(&name, reader).await?;
+        }
+        let commands = self.init_commands.join("\n");
+        self.run_source_async("<init>", commands.as_bytes()).await
+    }
+
+    /// Prints the fixed banner and the one computed from the context
+    fn print_banner(&self) {
+        if let Some(banner) = &self.banner {
+            self.print_output(banner);
+        }
+        if let Some(callback) = self.banner_callback {
+            self.print_output(callback(&self.context));
+        }
+    }
+
     /// Execute REPL
+    ///
+    /// Errors returned by commands are passed to the error handler, `Err` is only returned
+    /// if the line editor fails or the error handler returns an error.
     pub fn run(&mut self) -> Result<()> {
-        enable_virtual_terminal_processing();
-        if let Some(banner) = &self.banner {
-            println!("{}", banner);
+        let _terminal = TerminalGuard::new();
+        if self.plain_editor() {
+            self.ansi = false;
         }
-        let mut line_editor = self.build_line_editor()?;
+        self.apply_ansi();
+        self.run_loop()
+    }
 
-        loop {
-            let sig = line_editor
-                .read_line(&self.prompt)
-                .expect("failed to read_line");
-            match sig {
+    fn run_loop(&mut self) -> Result<()> {
+        let mut line_editor = self.start_session()?;
+        self.run_init()?;
+
+        while !self.quit {
+            line_editor = self.prepare_prompt(line_editor);
+            match line_editor.read_line(&self.prompt)? {
                 Signal::Success(line) => {
-                    if let Err(err) = self.process_line(line) {
-                        (self.error_handler)(err, self)?;
-                    }
-                }
-                Signal::CtrlC => {
-                    if self.stop_on_ctrl_c {
-                        break;
-                    }
-                }
-                Signal::CtrlD => {
-                    if self.stop_on_ctrl_d {
-                        break;
-                    }
+                    let entered = self.start_entered_line(&line)?;
+                    let result = self.process_line(line.clone());
+                    self.finish_entered_line(entered, &line, result)?;
                 }
+                signal if self.quits_on(&signal) => break,
+                _ => {}
             }
         }
-        disable_virtual_terminal_processing();
-        Ok(())
+        self.end_session(line_editor)
     }
 
     /// Execute REPL
+    ///
+    /// Errors returned by commands are passed to the error handler, `Err` is only returned
+    /// if the line editor fails or the error handler returns an error.
     #[cfg(feature = "async")]
     pub async fn run_async(&mut self) -> Result<()> {
-        enable_virtual_terminal_processing();
-        if let Some(banner) = &self.banner {
-            println!("{}", banner);
+        let _terminal = TerminalGuard::new();
+        if self.plain_editor() {
+            self.ansi = false;
         }
-        let mut line_editor = self.build_line_editor()?;
+        self.apply_ansi();
+        self.run_loop_async().await
+    }
 
-        loop {
-            let sig = line_editor
-                .read_line(&self.prompt)
-                .expect("failed to read_line");
-            match sig {
+    #[cfg(feature = "async")]
+    async fn run_loop_async(&mut self) -> Result<()> {
+        let mut line_editor = self.start_session()?;
+        self.run_init_async().await?;
+
+        while !self.quit {
+            line_editor = self.prepare_prompt(line_editor);
+            match line_editor.read_line(&self.prompt)? {
                 Signal::Success(line) => {
-                    if let Err(err) = self.process_line_async(line).await {
-                        (self.error_handler)(err, self)?;
-                    }
-                }
-                Signal::CtrlC => {
-                    if self.stop_on_ctrl_c {
-                        break;
-                    }
-                }
-                Signal::CtrlD => {
-                    if self.stop_on_ctrl_d {
-                        break;
-                    }
+                    let entered = self.start_entered_line(&line)?;
+                    let result = self.process_line_async(line.clone()).await;
+                    self.finish_entered_line(entered, &line, result)?;
                 }
+                signal if self.quits_on(&signal) => break,
+                _ => {}
+            }
+        }
+        self.end_session(line_editor)
+    }
+
+    /// Restores the context, greets the user and builds the line editor of a session
+    fn start_session(&mut self) -> Result<LineEditor> {
+        if let Some(err) = self.config_error.take() {
+            return Err(err);
+        }
+        self.open_transcript()?;
+        self.restore_context();
+        if let Some(callback) = self.on_start_callback {
+            callback(&mut self.context);
+        }
+        self.print_banner();
+        self.started = Some(Instant::now());
+        self.run_health_check()?;
+        let line_editor = self.build_editor()?;
+        self.quit = false;
+        self.emit(ReplEvent::SessionStarted);
+        Ok(line_editor)
+    }
+
+    /// Updates the line editor and the prompt before the next line is read
+    fn prepare_prompt(&mut self, line_editor: LineEditor) -> LineEditor {
+        self.apply_registrations();
+        let line_editor = self.update_editor(line_editor);
+        self.update_prompt_mode();
+        if let Some(callback) = self.right_prompt {
+            self.prompt.update_right(Some(callback(&self.context)));
+        }
+        self.prompt.update_status(self.status_segment());
+        self.report_finished_jobs();
+        line_editor
+    }
+
+    /// Records an entered line in the history before it is executed
+    fn start_entered_line(&mut self, line: &str) -> Result<EnteredLine> {
+        let recorded = self.record_history(line)?;
+        self.record_input(line);
+        self.arguments_rejected.set(false);
+        self.line_failed.set(false);
+        Ok(EnteredLine {
+            recorded,
+            empty: line.trim().is_empty(),
+            started: Instant::now(),
+        })
+    }
+
+    /// Keeps the outcome of an entered line for the status segment and the history
+    fn finish_entered_line(
+        &mut self,
+        entered: EnteredLine,
+        line: &str,
+        result: core::result::Result<(), E>,
+    ) -> Result<()> {
+        if !entered.empty {
+            let ok = result.is_ok() && !self.arguments_rejected.get() && !self.line_failed.get();
+            self.last_status = Some((ok, entered.started.elapsed()));
+        }
+        if entered.recorded {
+            self.store_history(line, result.is_ok())?;
+        }
+        if let Err(err) = result {
+            self.handle_error(err)?;
+        }
+        if !entered.empty {
+            self.print_spacing();
+        }
+        self.take_write_error()
+    }
+
+    /// Whether CTRL+C or CTRL+D ends the session
+    fn quits_on(&self, signal: &Signal) -> bool {
+        match signal {
+            Signal::CtrlC => self.ctrl_c_action == CtrlCAction::Quit,
+            Signal::CtrlD => self.ctrl_d_action == CtrlDAction::Quit,
+            _ => false,
+        }
+    }
+
+    /// Saves the history and the context and says goodbye
+    fn end_session(&mut self, line_editor: LineEditor) -> Result<()> {
+        drop(line_editor);
+        self.history_store()?.sync()?;
+        if let Some(callback) = self.goodbye_callback {
+            self.print_output(callback(&self.context));
+        }
+        let saved = self.save_context();
+        if let Some(callback) = self.on_exit_callback {
+            callback(&mut self.context);
+        }
+        self.emit(ReplEvent::SessionEnded);
+        saved
+    }
+}
+
+impl<Context, E: Display, Output> Repl<Context, E, Output> {
+    /// Usage line of the command `name` like `query [OPTIONS] <sql>`, for help texts and
+    /// docs, `None` if there is no such command
+    pub fn usage(&self, name: &str) -> Option<String> {
+        find_command(&self.commands, name, &self.scope()).map(ReplCommand::usage)
+    }
+
+    /// The active mode, `None` at the top level or if no modes are configured
+    fn active_mode(&self) -> Option<String> {
+        let modes = self.modes?;
+        modes(&self.context).current().map(str::to_string)
+    }
+
+    /// The commands reachable in the active mode
+    pub(crate) fn scope(&self) -> Scope {
+        let mode = self.active_mode();
+        let exclusive = mode
+            .as_ref()
+            .is_some_and(|mode| self.subrepls.contains_key(mode));
+        Scope { mode, exclusive }
+    }
+
+    /// The theme in use, without colors if ANSI output is turned off
+    fn active_theme(&self) -> Theme {
+        if self.ansi {
+            self.theme
+        } else {
+            Theme::no_color()
+        }
+    }
+
+    /// Turns off the colors painted with yansi, e.g. of `paint_green_bold` and diffs
+    fn apply_ansi(&self) {
+        if !self.ansi {
+            Paint::disable();
+        }
+    }
+
+    /// Writes a line to the configured output
+    pub(crate) fn print_output(&self, message: impl Display) {
+        let message = message.to_string();
+        let written = writeln!(self.output.borrow_mut(), "{}", message);
+        self.record_write(written);
+        self.record_transcript(' ', &message);
+    }
+
+    /// Keeps the first failed write, it's reported once the line finished with
+    /// `take_write_error`
+    fn record_write(&self, written: std::io::Result<()>) {
+        if let Err(err) = written {
+            self.write_error.borrow_mut().get_or_insert(err);
+        }
+    }
+
+    /// The first write to the output or error output that failed since the last call
+    fn take_write_error(&self) -> Result<()> {
+        match self.write_error.borrow_mut().take() {
+            Some(err) => Err(err.into()),
+            None => Ok(()),
+        }
+    }
+
+    /// Passes an event to the event listener
+    pub(crate) fn emit(&self, event: ReplEvent) {
+        if let Some(listener) = self.event_listener {
+            listener(event);
+        }
+    }
+
+    /// Appends text to the transcript, if one is written
+    pub(crate) fn record_transcript(&self, marker: char, text: &str) {
+        if let Some(transcript) = self.transcript.borrow_mut().as_mut() {
+            if let Err(err) = transcript.record(marker, text) {
+                let _ = writeln!(self.error_output.borrow_mut(), "transcript: {}", err);
+            }
+        }
+    }
+
+    /// Opens the transcript file set with `with_transcript`, unless it's already open
+    pub(crate) fn open_transcript(&self) -> Result<()> {
+        if let Some(path) = &self.transcript_path {
+            let mut transcript = self.transcript.borrow_mut();
+            if transcript.is_none() {
+                *transcript = Some(Transcript::open(path)?);
             }
         }
-        disable_virtual_terminal_processing();
         Ok(())
     }
+
+    /// Prints the pieces of a streamed output as soon as they're ready, returns the whole text
+    fn print_stream(&self, pieces: impl Iterator<Item = String>) -> String {
+        let mut text = vec![];
+        for piece in pieces {
+            self.print_output(&piece);
+            let _ = self.output.borrow_mut().flush();
+            text.push(piece);
+        }
+        text.join("\n")
+    }
+
+    /// Writes output like `print_output`, through the pager if one is set and the output
+    /// doesn't fit on the terminal
+    fn print_paged(&self, text: impl Display) {
+        let text = text.to_string();
+        if let Some(pager) = &self.pager {
+            if self.terminal_output
+                && self.sources.is_empty()
+                && std::io::stdout().is_terminal()
+                && exceeds_screen(&text)
+                && page(&text, pager).is_ok()
+            {
+                self.record_transcript(' ', &text);
+                return;
+            }
+        }
+        self.print_output(text);
+    }
+
+    /// Writes a line to the configured error output
+    pub(crate) fn print_error(&self, message: impl Display) {
+        let message = message.to_string();
+        let written = writeln!(self.error_output.borrow_mut(), "{}", message);
+        self.record_write(written);
+        self.record_transcript('!', &message);
+    }
+
+    /// Writes a clap parse error, or the help/version output clap produces instead
+    fn print_clap_error(&self, error: &clap::Error) {
+        let rendered = if self.ansi {
+            error.render().ansi().to_string()
+        } else {
+            error.render().to_string()
+        };
+        let rendered = rendered.trim_end();
+        if error.use_stderr() {
+            self.print_error(rendered);
+        } else {
+            self.print_output(rendered);
+        }
+    }
+
+    /// Writes all problems found in the arguments of a command as one error
+    pub(crate) fn print_clap_errors(&self, command: &str, errors: &[clap::Error]) {
+        if errors.iter().any(clap::Error::use_stderr) {
+            self.arguments_rejected.set(true);
+        }
+        if let [error] = errors {
+            return self.print_clap_error(error);
+        }
+        let mut message = Messages::fill(
+            &self.messages.argument_problems,
+            &[&errors.len().to_string(), command],
+        );
+        for error in errors {
+            let rendered = error.render().to_string();
+            let problem = rendered.split("\n\n").next().unwrap_or_default();
+            let problem = problem.strip_prefix("error: ").unwrap_or(problem);
+            message += &format!("\n  - {}", problem.replace('\n', "\n    "));
+        }
+        if let Some(usage) = self.usage(command) {
+            message += &format!("\n\n{}", Messages::fill(&self.messages.usage, &[&usage]));
+        }
+        let help = format!("{} {}", self.help_command_name, command);
+        message += &format!(
+            "\n\n{}",
+            Messages::fill(&self.messages.help_more_information, &[&help])
+        );
+        self.print_error(message);
+    }
+}
+
+/// The message a panic was started with
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    match payload.downcast_ref::<&str>() {
+        Some(message) => message.to_string(),
+        None => match payload.downcast_ref::<String>() {
+            Some(message) => message.clone(),
+            None => "unknown panic".to_string(),
+        },
+    }
+}
+
+/// Awaits `future`, a panic while polling it becomes an error
+#[cfg(feature = "async")]
+async fn catch_unwind_async<T, E: From<Error>>(
+    command: &str,
+    future: impl Future<Output = core::result::Result<T, E>>,
+) -> core::result::Result<T, E> {
+    let mut future = std::pin::pin!(future);
+    std::future::poll_fn(|cx| {
+        std::panic::catch_unwind(AssertUnwindSafe(|| future.as_mut().poll(cx))).unwrap_or_else(
+            |payload| {
+                Poll::Ready(Err(Error::CommandPanicked(
+                    command.to_string(),
+                    panic_message(&*payload),
+                )
+                .into()))
+            },
+        )
+    })
+    .await
 }
 
 #[cfg(windows)]
@@ -0,0 +1,42 @@
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex, PoisonError};
+
+/// What a line fed to [Repl::feed_line](struct.Repl.html#method.feed_line) produced, for
+/// frontends other than the terminal like tests, GUIs or web terminals
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ReplResponse {
+    /// Output of the commands, help and built-ins
+    pub output: String,
+    /// Errors as the error handler printed them
+    pub errors: String,
+    /// Whether all commands of the line succeeded
+    pub success: bool,
+    /// Whether the line asked the REPL to quit
+    pub quit: bool,
+}
+
+/// Output collected in memory while a fed line runs
+#[derive(Clone, Default)]
+pub(crate) struct Capture(Arc<Mutex<Vec<u8>>>);
+
+impl Capture {
+    /// The text written so far, leaving the capture empty
+    pub(crate) fn take(&self) -> String {
+        let mut text = self.0.lock().unwrap_or_else(PoisonError::into_inner);
+        String::from_utf8_lossy(&std::mem::take(&mut *text)).to_string()
+    }
+}
+
+impl Write for Capture {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,66 @@
+use crate::terminal::set_alternate_screen;
+use crossterm::cursor::{MoveTo, Show};
+use crossterm::execute;
+use crossterm::terminal::{
+    disable_raw_mode, enable_raw_mode, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen,
+};
+use std::io::{self, stdout};
+
+/// Switches the terminal to its alternate screen until dropped, so commands can show
+/// full-screen output like a pager or a dashboard. Dropping the guard restores the main
+/// screen with the previous REPL output and the line editor draws its prompt as usual.
+/// The line editor is suspended while commands run. If the command panics, the main
+/// screen is restored before the panic message is printed, and on SIGTERM before the
+/// REPL exits.
+///
+/// ```rust,no_run
+/// use reedline_repl_rs::AlternateScreen;
+///
+/// fn show_dashboard() -> std::io::Result<()> {
+///     let _screen = AlternateScreen::enter()?;
+///     // draw the dashboard and wait for the user to close it
+///     Ok(())
+/// }
+/// ```
+pub struct AlternateScreen {
+    raw_mode: bool,
+}
+
+impl AlternateScreen {
+    /// Switch to the alternate screen
+    pub fn enter() -> io::Result<Self> {
+        execute!(stdout(), EnterAlternateScreen)?;
+        set_alternate_screen(true);
+        Ok(Self { raw_mode: false })
+    }
+
+    /// Switch to the alternate screen in raw mode, to read single key presses
+    pub fn enter_raw() -> io::Result<Self> {
+        let mut screen = Self::enter()?;
+        enable_raw_mode()?;
+        screen.raw_mode = true;
+        Ok(screen)
+    }
+}
+
+impl Drop for AlternateScreen {
+    fn drop(&mut self) {
+        if self.raw_mode {
+            let _ = disable_raw_mode();
+        }
+        let _ = execute!(stdout(), Show, LeaveAlternateScreen);
+        set_alternate_screen(false);
+    }
+}
+
+/// Runs `f` on the alternate screen and switches back to the main screen afterwards,
+/// even if `f` panics
+pub fn with_alternate_screen<T>(f: impl FnOnce() -> T) -> io::Result<T> {
+    let _screen = AlternateScreen::enter()?;
+    Ok(f())
+}
+
+/// Clears the terminal and moves the cursor to the top left corner
+pub(crate) fn clear_screen(output: &mut impl io::Write) -> io::Result<()> {
+    execute!(output, Clear(ClearType::All), MoveTo(0, 0))
+}
@@ -0,0 +1,355 @@
+use crate::error::{Error, Result};
+use crate::tokenizer::split_words;
+use crate::validator::is_complete;
+use std::io::{self, BufRead, Lines};
+use std::iter::{Enumerate, Peekable};
+
+/// Control flow of scripts, parsed from a complete line
+pub(crate) enum Block<'a> {
+    /// `if <condition> { ... } else { ... }`, the else branch is optional and may be
+    /// another `if`
+    If {
+        condition: &'a str,
+        then: Body<'a>,
+        otherwise: Option<Body<'a>>,
+    },
+    /// `repeat <count> { ... }`
+    Repeat { count: &'a str, body: Body<'a> },
+}
+
+/// The lines of a block, with the number of lines before them in the parsed line to
+/// attribute errors to the lines of the script
+#[derive(Clone, Copy)]
+pub(crate) struct Body<'a> {
+    pub(crate) text: &'a str,
+    pub(crate) line: usize,
+}
+
+/// The complete lines of a script, a line ending with a backslash or in an open block
+/// continues on the next one. Each comes with the number of its first line, counted after
+/// the first `offset` lines.
+pub(crate) struct ScriptLines<R: BufRead> {
+    lines: Peekable<Enumerate<Lines<R>>>,
+    offset: usize,
+}
+
+impl<R: BufRead> ScriptLines<R> {
+    pub(crate) fn new(reader: R, offset: usize) -> Self {
+        ScriptLines {
+            lines: reader.lines().enumerate().peekable(),
+            offset,
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for ScriptLines<R> {
+    type Item = io::Result<(usize, String)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (index, line) = self.lines.next()?;
+        let mut line = match line {
+            Ok(line) => line,
+            Err(err) => return Some(Err(err)),
+        };
+        // the input ends an incomplete line, so its error is reported rather than lost
+        while !is_complete(&line) {
+            let Some((_, next)) = self.lines.next() else {
+                break;
+            };
+            line.push('\n');
+            match next {
+                Ok(next) => line.push_str(&next),
+                Err(err) => return Some(Err(err)),
+            }
+        }
+        Some(Ok((self.offset + index + 1, line)))
+    }
+}
+
+/// Parses `line` as an `if` or `repeat` block, `None` if it is a command line
+pub(crate) fn parse_block(line: &str) -> Option<Result<Block<'_>>> {
+    let trimmed = line.trim_start();
+    let keyword = trimmed.split(char::is_whitespace).next()?;
+    if keyword != "if" && keyword != "repeat" {
+        return None;
+    }
+    let open = block_start(line)?;
+    let head = line[..open].trim_start()[keyword.len()..].trim();
+    Some(if keyword == "repeat" {
+        parse_repeat(line, head, open)
+    } else {
+        parse_if(line, head, open)
+    })
+}
+
+fn parse_repeat<'a>(line: &'a str, count: &'a str, open: usize) -> Result<Block<'a>> {
+    if count.is_empty() {
+        return Err(syntax_error("missing number of repetitions after 'repeat'"));
+    }
+    let (body, end) = body_at(line, open)?;
+    expect_end(&line[end..])?;
+    Ok(Block::Repeat { count, body })
+}
+
+fn parse_if<'a>(line: &'a str, condition: &'a str, open: usize) -> Result<Block<'a>> {
+    if condition.is_empty() {
+        return Err(syntax_error("missing condition after 'if'"));
+    }
+    let (then, end) = body_at(line, open)?;
+    let rest = line[end..].trim_start();
+    let otherwise = match rest.strip_prefix("else") {
+        None => {
+            expect_end(rest)?;
+            None
+        }
+        Some(branch) => {
+            let branch = branch.trim_start();
+            let start = line.len() - branch.len();
+            if branch.starts_with('{') {
+                let (otherwise, end) = body_at(line, start)?;
+                expect_end(&line[end..])?;
+                Some(otherwise)
+            } else if branch.starts_with("if") {
+                Some(Body {
+                    text: branch,
+                    line: newlines(&line[..start]),
+                })
+            } else {
+                return Err(syntax_error("expected '{' or 'if' after 'else'"));
+            }
+        }
+    };
+    Ok(Block::If {
+        condition,
+        then,
+        otherwise,
+    })
+}
+
+/// Evaluates the expanded condition of an `if`: `a == b`, `a != b`, numeric comparisons
+/// with `<`, `<=`, `>` and `>=`, or a single value that is true unless it is empty, `0`
+/// or `false`
+pub(crate) fn evaluate(condition: &str) -> Result<bool> {
+    let words = split_words(condition)?;
+    match words.as_slice() {
+        [value] => Ok(!matches!(value.as_str(), "" | "0" | "false")),
+        [left, operator, right] => match operator.as_str() {
+            "==" => Ok(left == right),
+            "!=" => Ok(left != right),
+            "<" | "<=" | ">" | ">=" => {
+                let (left, right) = (number(left)?, number(right)?);
+                Ok(match operator.as_str() {
+                    "<" => left < right,
+                    "<=" => left <= right,
+                    ">" => left > right,
+                    _ => left >= right,
+                })
+            }
+            _ => Err(syntax_error(&format!("unknown operator '{}'", operator))),
+        },
+        _ => Err(syntax_error(&format!("invalid condition '{}'", condition))),
+    }
+}
+
+/// The expanded count of a `repeat`
+pub(crate) fn repetitions(count: &str) -> Result<usize> {
+    count
+        .trim()
+        .parse()
+        .map_err(|_| syntax_error(&format!("'{}' isn't a number of repetitions", count.trim())))
+}
+
+fn number(word: &str) -> Result<f64> {
+    word.parse()
+        .map_err(|_| syntax_error(&format!("'{}' isn't a number", word)))
+}
+
+fn syntax_error(message: &str) -> Error {
+    Error::ScriptSyntax(message.to_string())
+}
+
+fn expect_end(rest: &str) -> Result<()> {
+    match rest.trim() {
+        "" => Ok(()),
+        rest => Err(syntax_error(&format!(
+            "unexpected '{}' after the block",
+            rest
+        ))),
+    }
+}
+
+fn newlines(text: &str) -> usize {
+    text.matches('\n').count()
+}
+
+/// Position of the `{` opening the block, braces of `${name}` don't count
+fn block_start(line: &str) -> Option<usize> {
+    let mut variables = 0usize;
+    let mut previous = ' ';
+    for (index, c) in unquoted(line) {
+        match c {
+            '{' if previous == '$' => variables += 1,
+            '{' if variables == 0 => return Some(index),
+            '}' => variables = variables.saturating_sub(1),
+            _ => {}
+        }
+        previous = c;
+    }
+    None
+}
+
+/// The body of the block opened at `open` and the position after its closing `}`
+fn body_at(line: &str, open: usize) -> Result<(Body<'_>, usize)> {
+    let mut depth = 0usize;
+    for (index, c) in unquoted(&line[open..]) {
+        match c {
+            '{' => depth += 1,
+            '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    let body = Body {
+                        text: &line[open + 1..open + index],
+                        line: newlines(&line[..open]),
+                    };
+                    return Ok((body, open + index + 1));
+                }
+            }
+            _ => {}
+        }
+    }
+    Err(syntax_error("missing '}' closing the block"))
+}
+
+/// Characters of `text` outside of quotes and comments with their positions, escaped
+/// characters are skipped
+fn unquoted(text: &str) -> impl Iterator<Item = (usize, char)> + '_ {
+    let mut chars = text.char_indices();
+    let mut quote: Option<char> = None;
+    let mut previous = ' ';
+    std::iter::from_fn(move || loop {
+        let (index, c) = chars.next()?;
+        let before = std::mem::replace(&mut previous, c);
+        match (c, quote) {
+            (_, Some(open)) if c == open => quote = None,
+            // quotes end with their line like they do for the validator
+            ('\n', Some(_)) => quote = None,
+            ('\\', q) if q != Some('\'') => {
+                chars.next();
+            }
+            (_, Some(_)) => {}
+            ('"' | '\'', None) => quote = Some(c),
+            ('#', None) if before.is_whitespace() => {
+                for (_, c) in chars.by_ref() {
+                    if c == '\n' {
+                        break;
+                    }
+                }
+                previous = '\n';
+            }
+            _ => return Some((index, c)),
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn continued_lines_are_joined() {
+        let script = "say a\nrepeat 2 {\n  say b\n}\nsay c \\\n d\nsay e";
+        let lines: Vec<(usize, String)> = ScriptLines::new(script.as_bytes(), 10)
+            .map(|line| line.unwrap())
+            .collect();
+        assert_eq!(
+            lines,
+            [
+                (11, "say a".to_string()),
+                (12, "repeat 2 {\n  say b\n}".to_string()),
+                (15, "say c \\\n d".to_string()),
+                (17, "say e".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn command_lines_are_not_blocks() {
+        assert!(parse_block("say hello").is_none());
+        assert!(parse_block("iffy { x }").is_none());
+    }
+
+    #[test]
+    fn repeat_block() {
+        match parse_block("repeat ${count} {\n  say hi\n}") {
+            Some(Ok(Block::Repeat { count, body })) => {
+                assert_eq!(count, "${count}");
+                assert_eq!(body.text, "\n  say hi\n");
+                assert_eq!(body.line, 0);
+            }
+            _ => panic!("expected a repeat block"),
+        }
+    }
+
+    #[test]
+    fn if_else_if_chain() {
+        match parse_block("if $x == 1 { say one }\nelse if $x == 2 { say two }") {
+            Some(Ok(Block::If {
+                condition,
+                then,
+                otherwise: Some(otherwise),
+            })) => {
+                assert_eq!(condition, "$x == 1");
+                assert_eq!(then.text, " say one ");
+                assert_eq!(otherwise.text, "if $x == 2 { say two }");
+                assert_eq!(otherwise.line, 1);
+            }
+            _ => panic!("expected an if block with an else branch"),
+        }
+    }
+
+    #[test]
+    fn braces_in_quotes_and_comments_are_ignored() {
+        match parse_block("if a { say '}' # }\n}") {
+            Some(Ok(Block::If { then, .. })) => assert_eq!(then.text, " say '}' # }\n"),
+            _ => panic!("expected an if block"),
+        }
+    }
+
+    #[test]
+    fn malformed_blocks_are_syntax_errors() {
+        for line in [
+            "repeat { say hi }",
+            "if { say hi }",
+            "if a { say hi",
+            "if a { say hi } extra",
+            "if a { say hi } else say bye",
+        ] {
+            assert!(
+                matches!(parse_block(line), Some(Err(Error::ScriptSyntax(_)))),
+                "{}",
+                line
+            );
+        }
+    }
+
+    #[test]
+    fn conditions() {
+        assert!(evaluate("yes").unwrap());
+        assert!(!evaluate("0").unwrap());
+        assert!(!evaluate("''").unwrap());
+        assert!(evaluate("a == a").unwrap());
+        assert!(evaluate("a != b").unwrap());
+        assert!(evaluate("2 < 10").unwrap());
+        assert!(!evaluate("2 >= 10").unwrap());
+        assert!(evaluate("a < 10").is_err());
+        assert!(evaluate("a =~ b").is_err());
+        assert!(evaluate("a b").is_err());
+    }
+
+    #[test]
+    fn repetition_counts() {
+        assert_eq!(repetitions(" 3 ").unwrap(), 3);
+        assert!(repetitions("-1").is_err());
+        assert!(repetitions("many").is_err());
+    }
+}
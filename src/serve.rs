@@ -0,0 +1,125 @@
+use crate::error::*;
+use crate::event::ReplEvent;
+use crate::repl::Repl;
+use std::fmt::Display;
+use std::io::{self, Read, Write};
+use std::net::{TcpListener, TcpStream};
+#[cfg(unix)]
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::time::{Duration, Instant};
+use yansi::Paint;
+
+/// Listener [Repl::serve](struct.Repl.html#method.serve) accepts clients from, implemented
+/// for `TcpListener` and, on unix, `UnixListener`
+pub trait Listener {
+    /// Connection to one client
+    type Stream: Read + Write + Send + 'static;
+
+    /// Waits for the next client to connect
+    fn accept_client(&self) -> io::Result<Self::Stream>;
+
+    /// Another handle to the same connection, to read from one and write to the other
+    fn try_clone(stream: &Self::Stream) -> io::Result<Self::Stream>;
+
+    /// Makes reads from `stream` fail once no data arrived for `timeout`, `None` waits
+    /// forever. Does nothing by default.
+    fn set_read_timeout(stream: &Self::Stream, timeout: Option<Duration>) -> io::Result<()> {
+        let _ = (stream, timeout);
+        Ok(())
+    }
+}
+
+impl Listener for TcpListener {
+    type Stream = TcpStream;
+
+    fn accept_client(&self) -> io::Result<TcpStream> {
+        self.accept().map(|(stream, _)| stream)
+    }
+
+    fn try_clone(stream: &TcpStream) -> io::Result<TcpStream> {
+        stream.try_clone()
+    }
+
+    fn set_read_timeout(stream: &TcpStream, timeout: Option<Duration>) -> io::Result<()> {
+        stream.set_read_timeout(timeout)
+    }
+}
+
+#[cfg(unix)]
+impl Listener for UnixListener {
+    type Stream = UnixStream;
+
+    fn accept_client(&self) -> io::Result<UnixStream> {
+        self.accept().map(|(stream, _)| stream)
+    }
+
+    fn try_clone(stream: &UnixStream) -> io::Result<UnixStream> {
+        stream.try_clone()
+    }
+
+    fn set_read_timeout(stream: &UnixStream, timeout: Option<Duration>) -> io::Result<()> {
+        stream.set_read_timeout(timeout)
+    }
+}
+
+impl<Context, E, Output> Repl<Context, E, Output>
+where
+    E: Display + From<Error> + std::fmt::Debug,
+{
+    /// Serves the commands to clients connecting to `listener`, e.g.
+    /// `repl.serve(TcpListener::bind("127.0.0.1:7000")?)`. Clients are served one at a
+    /// time: the next client connecting waits until the previous one quit, disconnected or
+    /// sent no line for the [client timeout](#method.with_client_timeout). Every line a
+    /// client sends is executed like typed input and the output and errors are written back
+    /// without colors. Clients can't use the shell escape, redirects, `source` or `clear`.
+    /// A client that disconnects, times out or fails to receive output ends its session
+    /// and is reported on the error output. Only returns if accepting a client fails.
+    pub fn serve<L: Listener>(&mut self, listener: L) -> Result<()> {
+        self.open_transcript()?;
+        loop {
+            let stream = listener.accept_client()?;
+            if let Err(err) = self.serve_client::<L>(stream) {
+                let _ = writeln!(self.error_output.borrow_mut(), "{}", err);
+            }
+        }
+    }
+
+    /// Runs the lines of one client with the output going to its connection
+    fn serve_client<L: Listener>(&mut self, stream: L::Stream) -> Result<()> {
+        L::set_read_timeout(&stream, self.client_timeout)?;
+        let reader = std::io::BufReader::new(L::try_clone(&stream)?);
+        let output = L::try_clone(&stream)?;
+        let replaced = self.replace_outputs(Box::new(output), Box::new(stream));
+        let terminal_output = std::mem::replace(&mut self.terminal_output, false);
+        let redirects = std::mem::replace(&mut self.redirects, false);
+        let ansi = std::mem::replace(&mut self.ansi, false);
+        Paint::disable();
+        self.served = true;
+        self.quit = false;
+        self.started = Some(Instant::now());
+        if let Some(callback) = self.on_start_callback {
+            callback(&mut self.context);
+        }
+        self.emit(ReplEvent::SessionStarted);
+        let result = self.run_lines(reader, 0);
+        if let Err(err) = &result {
+            self.print_error(err);
+        }
+        if let Some(callback) = self.on_exit_callback {
+            callback(&mut self.context);
+        }
+        self.emit(ReplEvent::SessionEnded);
+        self.replace_outputs(replaced.0, replaced.1);
+        self.terminal_output = terminal_output;
+        self.redirects = redirects;
+        self.ansi = ansi;
+        if ansi {
+            Paint::enable();
+        }
+        self.served = false;
+        self.quit = false;
+        // writes to a connection that is gone have nothing more to report
+        self.write_error.borrow_mut().take();
+        result
+    }
+}
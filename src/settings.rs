@@ -0,0 +1,44 @@
+/// Settings of the REPL changed at runtime with `set <option> <value>` and listed by
+/// `show options`: name, accepted values and description
+pub(crate) const SETTINGS: &[(&str, &str, &str)] = &[
+    ("timing", "on|off", "Print how long each command took"),
+    ("color", "on|off", "Colors and styles in output and errors"),
+    (
+        "paging",
+        "off|internal|<program>",
+        "Pager for outputs longer than the terminal",
+    ),
+    (
+        "spacing",
+        "<lines>",
+        "Empty lines after the output of a command",
+    ),
+    (
+        "echo",
+        "on|off",
+        "Print the command line that failed above its error",
+    ),
+    (
+        "confirm",
+        "on|off",
+        "Ask before running commands that need confirmation",
+    ),
+];
+
+/// Whether `name` is a setting rather than a session variable
+pub(crate) fn is_setting(name: &str) -> bool {
+    SETTINGS.iter().any(|(setting, _, _)| *setting == name)
+}
+
+/// The accepted values of the setting `name`
+pub(crate) fn setting_values(name: &str) -> &'static str {
+    SETTINGS
+        .iter()
+        .find(|(setting, _, _)| *setting == name)
+        .map_or("", |(_, values, _)| values)
+}
+
+/// `on` or `off`
+pub(crate) fn on_off(enabled: bool) -> String {
+    if enabled { "on" } else { "off" }.to_string()
+}
@@ -0,0 +1,32 @@
+use std::time::Duration;
+
+/// What a command did during the session, collected by
+/// [Repl::stats](struct.Repl.html#method.stats)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CommandStats {
+    /// How often the command ran
+    pub invocations: usize,
+    /// How often it returned an error
+    pub errors: usize,
+    /// Time spent running it and rendering its output
+    pub total_time: Duration,
+}
+
+impl CommandStats {
+    /// Average time of a run
+    pub fn average_time(&self) -> Duration {
+        match u32::try_from(self.invocations) {
+            Ok(0) => Duration::ZERO,
+            Ok(invocations) => self.total_time / invocations,
+            Err(_) => self.total_time.div_f64(self.invocations as f64),
+        }
+    }
+
+    pub(crate) fn record(&mut self, elapsed: Duration, ok: bool) {
+        self.invocations += 1;
+        if !ok {
+            self.errors += 1;
+        }
+        self.total_time += elapsed;
+    }
+}
@@ -0,0 +1,96 @@
+use crate::repl::{disable_virtual_terminal_processing, enable_virtual_terminal_processing};
+use crossterm::execute;
+use crossterm::terminal::{disable_raw_mode, LeaveAlternateScreen};
+use std::cell::Cell;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Once;
+
+/// Whether a command switched to the alternate screen with `AlternateScreen`
+static ALTERNATE_SCREEN: AtomicBool = AtomicBool::new(false);
+
+thread_local! {
+    /// Whether this thread runs a REPL, panics of other threads leave the terminal alone
+    static RUNNING: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Sets the terminal up for the REPL and restores it however the REPL stops: when dropped
+/// at the end of `run` or while a panic unwinds through it, from the panic hook so the
+/// panic message isn't printed in raw mode, and on SIGTERM before the process exits
+pub(crate) struct TerminalGuard {
+    #[cfg(unix)]
+    signals: Option<signal_hook::iterator::Handle>,
+}
+
+impl TerminalGuard {
+    pub(crate) fn new() -> Self {
+        enable_virtual_terminal_processing();
+        install_panic_hook();
+        RUNNING.with(|running| running.set(true));
+        TerminalGuard {
+            #[cfg(unix)]
+            signals: restore_on_sigterm(),
+        }
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        if let Some(signals) = self.signals.take() {
+            signals.close();
+        }
+        RUNNING.with(|running| running.set(false));
+        let _ = disable_raw_mode();
+        disable_virtual_terminal_processing();
+    }
+}
+
+/// Records that a command switched to the alternate screen or back, so it is left before
+/// a panic message is printed or the process exits on SIGTERM
+pub(crate) fn set_alternate_screen(active: bool) {
+    install_panic_hook();
+    ALTERNATE_SCREEN.store(active, Ordering::SeqCst);
+}
+
+/// Leaves raw mode and the alternate screen a command may have entered
+fn restore_screen() {
+    let _ = disable_raw_mode();
+    if ALTERNATE_SCREEN.swap(false, Ordering::SeqCst) {
+        let _ = execute!(std::io::stdout(), LeaveAlternateScreen);
+    }
+}
+
+/// Chains a panic hook restoring the screen in front of the current one, once per process
+fn install_panic_hook() {
+    static INSTALLED: Once = Once::new();
+    INSTALLED.call_once(|| {
+        let previous = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            if RUNNING.with(Cell::get) || ALTERNATE_SCREEN.load(Ordering::SeqCst) {
+                restore_screen();
+            }
+            previous(info);
+        }));
+    });
+}
+
+/// Restores the terminal on SIGTERM until the returned handle is closed, then raises the
+/// signal again with its default disposition so the process ends as if it wasn't caught.
+/// The signal is handled on a thread as restoring the terminal isn't signal safe.
+#[cfg(unix)]
+fn restore_on_sigterm() -> Option<signal_hook::iterator::Handle> {
+    use signal_hook::consts::SIGTERM;
+
+    let mut signals = signal_hook::iterator::Signals::new([SIGTERM]).ok()?;
+    let handle = signals.handle();
+    std::thread::spawn(move || {
+        if signals.forever().next().is_some() {
+            restore_screen();
+            disable_virtual_terminal_processing();
+            if signal_hook::low_level::emulate_default_handler(SIGTERM).is_err() {
+                std::process::exit(128 + SIGTERM);
+            }
+        }
+    });
+    Some(handle)
+}
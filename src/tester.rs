@@ -0,0 +1,134 @@
+use crate::error::Error;
+use crate::repl::Repl;
+use crate::response::ReplResponse;
+use std::fmt::Display;
+
+/// Sends lines to a REPL and checks what they produced, without a terminal or a process,
+/// e.g. `ReplTester::new(repl).send("add 1 2").expect_output("3")`. The checks panic like
+/// `assert!` so they fail the test they are used in.
+pub struct ReplTester<Context, E: Display, Output = String> {
+    repl: Repl<Context, E, Output>,
+    last: Option<(String, ReplResponse)>,
+}
+
+impl<Context, E, Output> ReplTester<Context, E, Output>
+where
+    E: Display + From<Error> + std::fmt::Debug,
+{
+    /// Tests `repl`, with colors turned off so output can be compared as text
+    pub fn new(repl: Repl<Context, E, Output>) -> Self {
+        Self {
+            repl: repl.with_ansi(false),
+            last: None,
+        }
+    }
+
+    /// Executes `line` like typed input, the checks that follow apply to it
+    pub fn send(mut self, line: &str) -> Self {
+        let response = self.repl.feed_line(line);
+        self.last = Some((line.to_string(), response));
+
+        self
+    }
+
+    /// Checks that the last line succeeded and printed exactly `expected`, apart from the
+    /// final line break
+    #[track_caller]
+    pub fn expect_output(self, expected: &str) -> Self {
+        let (line, response) = self.expect_success_response();
+        let output = response
+            .output
+            .strip_suffix('\n')
+            .unwrap_or(&response.output);
+        assert_eq!(output, expected, "unexpected output of '{}'", line);
+
+        self
+    }
+
+    /// Checks that the last line succeeded and its output contains `expected`
+    #[track_caller]
+    pub fn expect_output_contains(self, expected: &str) -> Self {
+        let (line, response) = self.expect_success_response();
+        assert!(
+            response.output.contains(expected),
+            "output of '{}' doesn't contain '{}':\n{}",
+            line,
+            expected,
+            response.output
+        );
+
+        self
+    }
+
+    /// Checks that the last line succeeded
+    #[track_caller]
+    pub fn expect_success(self) -> Self {
+        self.expect_success_response();
+
+        self
+    }
+
+    /// Checks that the last line failed with an error containing `expected`, from the
+    /// command or about its arguments
+    #[track_caller]
+    pub fn expect_error(self, expected: &str) -> Self {
+        let (line, response) = self.last_response();
+        assert!(
+            !response.success,
+            "'{}' succeeded with output:\n{}",
+            line, response.output
+        );
+        assert!(
+            response.errors.contains(expected),
+            "errors of '{}' don't contain '{}':\n{}\noutput:\n{}",
+            line,
+            expected,
+            response.errors,
+            response.output
+        );
+
+        self
+    }
+
+    /// Checks that the last line asked the REPL to quit
+    #[track_caller]
+    pub fn expect_quit(self) -> Self {
+        let (line, response) = self.last_response();
+        assert!(response.quit, "'{}' didn't quit", line);
+
+        self
+    }
+
+    /// What Tab at the end of `line` offers, e.g. to test argument completers
+    pub fn complete(&self, line: &str) -> Vec<String> {
+        self.repl.complete(line)
+    }
+
+    /// What the last line produced, for checks of your own
+    #[track_caller]
+    pub fn response(&self) -> &ReplResponse {
+        &self.last_response().1
+    }
+
+    /// The tested REPL
+    pub fn into_repl(self) -> Repl<Context, E, Output> {
+        self.repl
+    }
+
+    #[track_caller]
+    fn last_response(&self) -> &(String, ReplResponse) {
+        self.last.as_ref().expect("no line was sent yet")
+    }
+
+    #[track_caller]
+    fn expect_success_response(&self) -> &(String, ReplResponse) {
+        let last = self.last_response();
+        let (line, response) = last;
+        assert!(
+            response.success,
+            "'{}' failed with:\n{}",
+            line, response.errors
+        );
+        last
+    }
+}
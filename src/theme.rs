@@ -0,0 +1,82 @@
+use nu_ansi_term::{Color, Style};
+
+/// Colors and styles of the REPL, set with
+/// [Repl::with_theme](struct.Repl.html#method.with_theme)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Theme {
+    /// Prompt built from the REPL name
+    pub prompt_style: Style,
+    /// Fish-style history autosuggestions
+    pub hint_style: Style,
+    /// Known commands in the line being typed
+    pub command_color: Color,
+    /// Unknown commands, flags and surplus arguments in the line being typed
+    pub unknown_command_color: Color,
+    /// Arguments in the line being typed
+    pub argument_color: Color,
+    /// Flags like `--verbose` in the line being typed
+    pub flag_color: Color,
+    /// Quoted strings in the line being typed
+    pub string_color: Color,
+    /// The `error:` tag in front of errors
+    pub error_style: Style,
+    /// Warnings like the one before a deprecated command runs
+    pub warning_style: Style,
+    /// Headings of the help output
+    pub help_heading_style: Style,
+    /// Status segment in front of the prompt after a line succeeded
+    pub status_success_style: Style,
+    /// Status segment in front of the prompt after a line failed
+    pub status_failure_style: Style,
+    /// Entries of the completion and help menus
+    pub menu_style: Style,
+    /// The selected entry of the completion and help menus
+    pub menu_selected_style: Style,
+    /// Descriptions of the entries of the completion and help menus
+    pub menu_description_style: Style,
+}
+
+impl Theme {
+    /// Theme without any colors or styles
+    pub fn no_color() -> Self {
+        Self {
+            prompt_style: Style::new(),
+            hint_style: Style::new(),
+            command_color: Color::Default,
+            unknown_command_color: Color::Default,
+            argument_color: Color::Default,
+            flag_color: Color::Default,
+            string_color: Color::Default,
+            error_style: Style::new(),
+            warning_style: Style::new(),
+            help_heading_style: Style::new(),
+            status_success_style: Style::new(),
+            status_failure_style: Style::new(),
+            menu_style: Style::new(),
+            menu_selected_style: Style::new().reverse(),
+            menu_description_style: Style::new(),
+        }
+    }
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            prompt_style: Style::new().bold().fg(Color::Green),
+            hint_style: Style::new().italic().fg(Color::LightGray),
+            command_color: Color::Green,
+            unknown_command_color: Color::Red,
+            argument_color: Color::White,
+            flag_color: Color::Cyan,
+            string_color: Color::Yellow,
+            error_style: Style::new().bold().fg(Color::Red),
+            warning_style: Style::new().fg(Color::Yellow),
+            help_heading_style: Style::new().bold().fg(Color::Yellow),
+            status_success_style: Style::new().fg(Color::Green),
+            status_failure_style: Style::new().fg(Color::Red),
+            menu_style: Style::new().fg(Color::Green),
+            menu_selected_style: Style::new().bold().fg(Color::Black).on(Color::Green),
+            menu_description_style: Style::new().fg(Color::Yellow),
+        }
+    }
+}
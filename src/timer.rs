@@ -0,0 +1,102 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+use std::time::Duration;
+
+/// Future completing after a duration, for waiting in async commands without blocking the
+/// executor. The crate doesn't depend on a runtime, so a thread sleeps and wakes the task.
+pub(crate) struct Delay {
+    duration: Duration,
+    state: Option<Arc<Mutex<State>>>,
+}
+
+#[derive(Default)]
+struct State {
+    elapsed: bool,
+    waker: Option<Waker>,
+}
+
+/// Waits for `duration`
+pub(crate) fn delay(duration: Duration) -> Delay {
+    Delay {
+        duration,
+        state: None,
+    }
+}
+
+impl Future for Delay {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if self.duration.is_zero() {
+            return Poll::Ready(());
+        }
+        let duration = self.duration;
+        let state = self.state.get_or_insert_with(|| {
+            let state = Arc::new(Mutex::new(State::default()));
+            let timer = state.clone();
+            std::thread::spawn(move || {
+                std::thread::sleep(duration);
+                let mut timer = timer.lock().unwrap_or_else(|err| err.into_inner());
+                timer.elapsed = true;
+                if let Some(waker) = timer.waker.take() {
+                    waker.wake();
+                }
+            });
+            state
+        });
+        let mut state = state.lock().unwrap_or_else(|err| err.into_inner());
+        if state.elapsed {
+            return Poll::Ready(());
+        }
+        state.waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::task::Wake;
+    use std::time::Instant;
+
+    /// Counts its wakes and unparks the polling thread
+    struct Unpark(std::thread::Thread, AtomicUsize);
+
+    impl Wake for Unpark {
+        fn wake(self: Arc<Self>) {
+            self.1.fetch_add(1, Ordering::SeqCst);
+            self.0.unpark();
+        }
+    }
+
+    #[test]
+    fn completes_after_the_duration_without_blocking_the_poll() {
+        let unpark = Arc::new(Unpark(std::thread::current(), AtomicUsize::new(0)));
+        let waker = Waker::from(unpark.clone());
+        let mut cx = Context::from_waker(&waker);
+        let start = Instant::now();
+        let mut delay = delay(Duration::from_millis(50));
+        assert!(Pin::new(&mut delay).poll(&mut cx).is_pending());
+        assert!(start.elapsed() < Duration::from_millis(50));
+        while Pin::new(&mut delay).poll(&mut cx).is_pending() {
+            std::thread::park();
+        }
+        assert!(start.elapsed() >= Duration::from_millis(50));
+        assert_eq!(unpark.1.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn zero_duration_is_ready() {
+        let waker = Waker::from(Arc::new(Unpark(
+            std::thread::current(),
+            AtomicUsize::new(0),
+        )));
+        let mut cx = Context::from_waker(&waker);
+        assert!(Pin::new(&mut delay(Duration::ZERO))
+            .poll(&mut cx)
+            .is_ready());
+    }
+}
@@ -0,0 +1,294 @@
+use crate::error::*;
+use crate::redirect::Redirect;
+
+/// Separator following a command in a chained line
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Separator {
+    /// `;` or end of line, the next command runs regardless of the result
+    Sequence,
+    /// `&&`, the next command only runs if this one succeeded
+    And,
+}
+
+/// Splits a command like `report | grep error` or `report > out.txt` at the first
+/// redirection. `|` and `>` inside quotes or escaped with a backslash don't split.
+pub(crate) fn split_redirect(line: &str) -> Result<(String, Option<Redirect>)> {
+    let mut quote: Option<char> = None;
+    let mut chars = line.char_indices().peekable();
+    while let Some((index, c)) = chars.next() {
+        match (c, quote) {
+            ('\\', q) if q != Some('\'') => {
+                chars.next();
+            }
+            ('"' | '\'', None) => quote = Some(c),
+            (_, Some(open)) if c == open => quote = None,
+            ('|', None) => {
+                let program = line[index + 1..].trim();
+                if program.is_empty() {
+                    return Err(Error::MissingRedirectTarget(line.to_string()));
+                }
+                let redirect = Redirect::Pipe(program.to_string());
+                return Ok((line[..index].to_string(), Some(redirect)));
+            }
+            ('>', None) => {
+                let append = chars.next_if(|(_, next)| *next == '>').is_some();
+                let start = index + if append { 2 } else { 1 };
+                let path = split_words(&line[start..])?
+                    .into_iter()
+                    .next()
+                    .ok_or_else(|| Error::MissingRedirectTarget(line.to_string()))?;
+                let redirect = Redirect::File { path, append };
+                return Ok((line[..index].to_string(), Some(redirect)));
+            }
+            _ => {}
+        }
+    }
+    Ok((line.to_string(), None))
+}
+
+/// Splits a line like `load data.csv && summarize; status` into its commands.
+/// Separators inside quotes or escaped with a backslash don't split.
+pub(crate) fn split_chain(line: &str) -> Vec<(String, Separator)> {
+    let mut commands = vec![];
+    let mut current = String::new();
+    let mut quote: Option<char> = None;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (c, quote) {
+            ('\\', q) if q != Some('\'') => {
+                current.push(c);
+                if let Some(escaped) = chars.next() {
+                    current.push(escaped);
+                }
+            }
+            ('"' | '\'', None) => {
+                quote = Some(c);
+                current.push(c);
+            }
+            (_, Some(open)) if c == open => {
+                quote = None;
+                current.push(c);
+            }
+            (';', None) => {
+                commands.push((std::mem::take(&mut current), Separator::Sequence));
+            }
+            ('&', None) if chars.peek() == Some(&'&') => {
+                chars.next();
+                commands.push((std::mem::take(&mut current), Separator::And));
+            }
+            _ => current.push(c),
+        }
+    }
+    commands.push((current, Separator::Sequence));
+    commands
+}
+
+/// Splits a command into its words like a POSIX shell. Whitespace separates words,
+/// `"double"` and `'single'` quotes group them, and a backslash escapes the next
+/// character. Inside double quotes only `\"` and `\\` are escapes, inside single
+/// quotes everything is literal. `""` yields an empty word and `--` is kept as a word,
+/// so the command sees it as the end of its options.
+pub(crate) fn split_words(line: &str) -> Result<Vec<String>> {
+    let mut words = vec![];
+    let mut current: Option<String> = None;
+    let mut quote: Option<char> = None;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (c, quote) {
+            (_, Some(open)) if c == open => quote = None,
+            ('\\', Some('"')) => {
+                let word = current.get_or_insert_with(String::new);
+                match chars.next_if(|next| *next == '"' || *next == '\\') {
+                    Some(escaped) => word.push(escaped),
+                    None => word.push(c),
+                }
+            }
+            ('\\', None) => {
+                let escaped = chars.next().unwrap_or(c);
+                current.get_or_insert_with(String::new).push(escaped);
+            }
+            ('"' | '\'', None) => {
+                quote = Some(c);
+                current.get_or_insert_with(String::new);
+            }
+            (_, None) if c.is_whitespace() => {
+                if let Some(word) = current.take() {
+                    words.push(word);
+                }
+            }
+            _ => current.get_or_insert_with(String::new).push(c),
+        }
+    }
+    if quote.is_some() {
+        return Err(Error::UnterminatedQuote(line.to_string()));
+    }
+    words.extend(current);
+    Ok(words)
+}
+
+/// Joins the lines of a multiline input by removing each backslash-newline continuation.
+/// Lines without a backslash are returned as they are.
+pub(crate) fn join_continuations(line: String) -> String {
+    if !line.contains('\\') {
+        return line;
+    }
+    let mut joined = String::with_capacity(line.len());
+    let mut quote: Option<char> = None;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match (c, quote) {
+            ('\\', q) if q != Some('\'') => match chars.next() {
+                Some('\n') => {}
+                Some(escaped) => {
+                    joined.push(c);
+                    joined.push(escaped);
+                }
+                None => joined.push(c),
+            },
+            ('"' | '\'', None) => {
+                quote = Some(c);
+                joined.push(c);
+            }
+            (_, Some(open)) if c == open => {
+                quote = None;
+                joined.push(c);
+            }
+            _ => joined.push(c),
+        }
+    }
+    joined
+}
+
+/// Removes comments, a `#` at the start of a line or after whitespace up to the end of
+/// its line. A `#` inside quotes, escaped with a backslash or inside a word is kept.
+pub(crate) fn strip_comments(line: &str) -> String {
+    let mut stripped = String::with_capacity(line.len());
+    let mut quote: Option<char> = None;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match (c, quote) {
+            ('\\', q) if q != Some('\'') => {
+                stripped.push(c);
+                if let Some(escaped) = chars.next() {
+                    stripped.push(escaped);
+                }
+            }
+            ('"' | '\'', None) => {
+                quote = Some(c);
+                stripped.push(c);
+            }
+            (_, Some(open)) if c == open => {
+                quote = None;
+                stripped.push(c);
+            }
+            ('#', None) if stripped.is_empty() || stripped.ends_with(char::is_whitespace) => {
+                if chars.any(|skipped| skipped == '\n') {
+                    stripped.push('\n');
+                }
+            }
+            _ => stripped.push(c),
+        }
+    }
+    stripped
+}
+
+/// Whether `word` is a flag like `-v` or `--verbose`, negative numbers like `-5` are values
+pub(crate) fn is_flag(word: &str) -> bool {
+    word.starts_with('-') && word.len() > 1 && word.parse::<f64>().is_err()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(line: &str) -> Vec<String> {
+        split_words(line).unwrap()
+    }
+
+    #[test]
+    fn words_follow_shell_quoting() {
+        assert_eq!(words("  add 1   2 "), ["add", "1", "2"]);
+        assert_eq!(
+            words(r#"say "hello world" 'a b'"#),
+            ["say", "hello world", "a b"]
+        );
+        assert_eq!(words(r#"say "" -- x"#), ["say", "", "--", "x"]);
+        assert_eq!(words(r"say a\ b \'"), ["say", "a b", "'"]);
+        assert_eq!(words(r#"say "a\"b\\c\d""#), ["say", r#"a"b\c\d"#]);
+        assert_eq!(words(r"say 'a\b'"), ["say", r"a\b"]);
+    }
+
+    #[test]
+    fn unterminated_quote_is_an_error() {
+        assert_eq!(
+            split_words("say \"hello"),
+            Err(Error::UnterminatedQuote("say \"hello".to_string()))
+        );
+    }
+
+    #[test]
+    fn chain_splits_outside_quotes() {
+        assert_eq!(
+            split_chain(r"a && b; 'c; d' \; e"),
+            [
+                ("a ".to_string(), Separator::And),
+                (" b".to_string(), Separator::Sequence),
+                (r" 'c; d' \; e".to_string(), Separator::Sequence),
+            ]
+        );
+        assert_eq!(
+            split_chain("a & b"),
+            [("a & b".to_string(), Separator::Sequence)]
+        );
+    }
+
+    #[test]
+    fn redirect_splits_at_first_pipe_or_file() {
+        assert_eq!(
+            split_redirect("report | grep error").unwrap(),
+            (
+                "report ".to_string(),
+                Some(Redirect::Pipe("grep error".to_string()))
+            )
+        );
+        assert_eq!(
+            split_redirect("report >> 'out file.txt'").unwrap(),
+            (
+                "report ".to_string(),
+                Some(Redirect::File {
+                    path: "out file.txt".to_string(),
+                    append: true
+                })
+            )
+        );
+        assert_eq!(
+            split_redirect("say 'a > b' \\| c").unwrap(),
+            ("say 'a > b' \\| c".to_string(), None)
+        );
+        assert!(split_redirect("report >").is_err());
+        assert!(split_redirect("report | ").is_err());
+    }
+
+    #[test]
+    fn comments_start_at_words() {
+        assert_eq!(strip_comments("# note"), "");
+        assert_eq!(strip_comments("say hi # note\nnext"), "say hi \nnext");
+        assert_eq!(strip_comments("say a#b '#c' \\#d"), "say a#b '#c' \\#d");
+    }
+
+    #[test]
+    fn continuations_are_joined_outside_single_quotes() {
+        assert_eq!(join_continuations("a \\\nb".to_string()), "a b");
+        assert_eq!(join_continuations("a \\n".to_string()), "a \\n");
+        assert_eq!(join_continuations("'a \\\nb'".to_string()), "'a \\\nb'");
+    }
+
+    #[test]
+    fn negative_numbers_are_not_flags() {
+        assert!(is_flag("-v"));
+        assert!(is_flag("--verbose"));
+        assert!(!is_flag("-"));
+        assert!(!is_flag("-5"));
+        assert!(!is_flag("-1.5"));
+    }
+}
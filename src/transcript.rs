@@ -0,0 +1,69 @@
+use regex::Regex;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::OnceLock;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Transcript file of a session, every line is prefixed with a UTC timestamp and a marker
+/// telling input, output and errors apart
+pub(crate) struct Transcript {
+    file: File,
+}
+
+impl Transcript {
+    /// Opens the transcript at `path`, appending to an existing file
+    pub(crate) fn open(path: &Path) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Transcript { file })
+    }
+
+    /// Appends `text` line by line, without colors
+    pub(crate) fn record(&mut self, marker: char, text: &str) -> io::Result<()> {
+        let timestamp = timestamp(SystemTime::now());
+        let text = strip_ansi(text);
+        for line in text.lines() {
+            writeln!(self.file, "[{}] {} {}", timestamp, marker, line)?;
+        }
+        if text.is_empty() {
+            writeln!(self.file, "[{}] {}", timestamp, marker)?;
+        }
+        Ok(())
+    }
+}
+
+pub(crate) fn strip_ansi(text: &str) -> String {
+    static ESCAPES: OnceLock<Regex> = OnceLock::new();
+    ESCAPES
+        .get_or_init(|| Regex::new(r"\x1b\[[0-9;?]*[A-Za-z]").unwrap())
+        .replace_all(text, "")
+        .to_string()
+}
+
+/// Formats `time` like `2024-05-17T09:41:07Z`
+fn timestamp(time: SystemTime) -> String {
+    let seconds = time
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0);
+    let (days, seconds) = (seconds / 86400, seconds % 86400);
+    // civil date from days since the epoch, see http://howardhinnant.github.io/date_algorithms.html
+    let z = days as i64 + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        seconds / 3600,
+        seconds % 3600 / 60,
+        seconds % 60
+    )
+}
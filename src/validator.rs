@@ -0,0 +1,72 @@
+use crate::tokenizer::strip_comments;
+use reedline::{ValidationResult, Validator};
+
+/// Validator asking for another line while the input ends with a backslash or an `if` or
+/// `repeat` block is still open
+pub(crate) struct ReplValidator;
+
+impl Validator for ReplValidator {
+    fn validate(&self, line: &str) -> ValidationResult {
+        if is_complete(line) {
+            ValidationResult::Complete
+        } else {
+            ValidationResult::Incomplete
+        }
+    }
+}
+
+/// Whether the input is complete, i.e. has no trailing `\` and no unclosed block outside of
+/// comments. Quotes don't continue the input, so an apostrophe in a word can't swallow the
+/// lines after it, the unterminated quote is reported instead.
+pub(crate) fn is_complete(line: &str) -> bool {
+    let line = strip_comments(line);
+    let keyword = line.split_whitespace().next();
+    let block = matches!(keyword, Some("if" | "repeat"));
+    let mut quote: Option<char> = None;
+    let mut braces = 0usize;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match (c, quote) {
+            (_, Some(open)) if c == open => quote = None,
+            // a quote doesn't continue the input, so it can't hide the braces of later lines
+            ('\n', Some(_)) => quote = None,
+            ('\\', q) if q != Some('\'') && chars.next().is_none() => return false,
+            ('\\', q) if q != Some('\'') => {}
+            (_, Some(_)) => {}
+            ('"' | '\'', None) => quote = Some(c),
+            ('{', None) => braces += 1,
+            // stray closing braces are left for the block to report
+            ('}', None) => braces = braces.saturating_sub(1),
+            _ => {}
+        }
+    }
+    !block || braces == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn trailing_backslash_continues() {
+        assert!(!is_complete("say a \\"));
+        assert!(is_complete("say a \\\\"));
+        assert!(is_complete("say 'a \\'"));
+        assert!(is_complete("say a \\\nb"));
+    }
+
+    #[test]
+    fn quotes_and_brackets_of_commands_dont_continue() {
+        assert!(is_complete("say don't"));
+        assert!(is_complete("say \"open"));
+        assert!(is_complete("say (a [b {c"));
+    }
+
+    #[test]
+    fn open_blocks_continue() {
+        assert!(!is_complete("if $x == 1 {"));
+        assert!(!is_complete("repeat 3 {\n  say don't\n"));
+        assert!(is_complete("repeat 3 {\n  say don't\n}"));
+        assert!(is_complete("if ${x} { say '{' } # {"));
+    }
+}
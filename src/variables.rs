@@ -0,0 +1,46 @@
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+/// Session variables set with the `set name value` builtin and expanded as `$name` or
+/// `${name}` in input lines. Clones share the same variables, so keep a clone in your
+/// context and pass another one to
+/// [Repl::with_variables](struct.Repl.html#method.with_variables) to read and set them
+/// from command callbacks.
+#[derive(Debug, Clone, Default)]
+pub struct Variables {
+    values: Arc<Mutex<BTreeMap<String, String>>>,
+}
+
+impl Variables {
+    /// Create an empty variable store
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The value of the variable `name`
+    pub fn get(&self, name: &str) -> Option<String> {
+        self.values().get(name).cloned()
+    }
+
+    /// Set the variable `name` to `value`
+    pub fn set(&self, name: &str, value: &str) {
+        self.values().insert(name.to_string(), value.to_string());
+    }
+
+    /// Remove the variable `name` and return its value
+    pub fn remove(&self, name: &str) -> Option<String> {
+        self.values().remove(name)
+    }
+
+    /// All variables sorted by name
+    pub fn all(&self) -> Vec<(String, String)> {
+        self.values()
+            .iter()
+            .map(|(name, value)| (name.clone(), value.clone()))
+            .collect()
+    }
+
+    fn values(&self) -> MutexGuard<'_, BTreeMap<String, String>> {
+        self.values.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
@@ -0,0 +1,45 @@
+mod common;
+
+use reedline_repl_rs::clap::{ArgMatches, Command};
+use reedline_repl_rs::{Repl, Result};
+
+/// Set for the copy of the test binary that runs the REPL on its stdin
+const CHILD: &str = "REEDLINE_REPL_BANNER_CHILD";
+
+fn add(_: ArgMatches, rows: &mut u32) -> Result<Option<String>> {
+    *rows += 1;
+    Ok(None)
+}
+
+fn connect(rows: &mut u32) {
+    *rows = 10;
+}
+
+fn banner(rows: &u32) -> String {
+    format!("welcome, {} rows", rows)
+}
+
+fn goodbye(rows: &u32) -> String {
+    format!("bye, {} rows", rows)
+}
+
+#[test]
+fn banner_and_goodbye_show_the_context() {
+    if std::env::var_os(CHILD).is_some() {
+        Repl::new(0)
+            .with_command(Command::new("add"), add)
+            .with_on_start(connect)
+            .with_banner("rows")
+            .with_banner_fn(banner)
+            .with_goodbye_fn(goodbye)
+            .with_ansi(false)
+            .run()
+            .unwrap();
+        return;
+    }
+    let output = common::run_in_child("banner_and_goodbye_show_the_context", CHILD, b"add\nadd\n");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "{}", stdout);
+    assert!(stdout.contains("rows\nwelcome, 10 rows\n"), "{}", stdout);
+    assert!(stdout.contains("bye, 12 rows"), "{}", stdout);
+}
@@ -0,0 +1,34 @@
+mod common;
+
+use reedline_repl_rs::clap::{ArgMatches, Command};
+use reedline_repl_rs::{Error, Repl, Result};
+
+fn show(_: ArgMatches, _: &mut ()) -> Result<Option<String>> {
+    Ok(Some("own show".to_string()))
+}
+
+fn repl() -> Repl<(), Error> {
+    common::repl().with_command(Command::new("show").about("Own show command"), show)
+}
+
+#[test]
+fn command_replaces_builtin_of_same_name() {
+    let mut repl = repl();
+    let response = repl.feed_line("show");
+    assert!(response.success);
+    assert_eq!(response.output, "own show\n");
+}
+
+#[test]
+fn help_lists_replaced_builtin_once() {
+    let mut repl = repl();
+    let response = repl.feed_line("help");
+    assert!(response.success, "{}", response.errors);
+    let rows: Vec<&str> = response
+        .output
+        .lines()
+        .filter(|line| line.trim_start().starts_with("show "))
+        .collect();
+    assert_eq!(rows.len(), 1, "{}", response.output);
+    assert!(rows[0].contains("Own show command"));
+}
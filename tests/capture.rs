@@ -0,0 +1,24 @@
+mod common;
+
+use common::tester;
+
+#[test]
+fn output_is_stored_in_the_variable() {
+    tester()
+        .send("capture greeting say hello world")
+        .expect_output("")
+        .send("say $greeting!")
+        .expect_output("hello world!");
+}
+
+#[test]
+fn failed_commands_leave_the_variable_unchanged() {
+    tester()
+        .send("set result old")
+        .send("capture result fail")
+        .expect_error("failed on purpose")
+        .send("say $result")
+        .expect_output("old")
+        .send("capture result")
+        .expect_error("Missing required argument 'command' for command 'capture'");
+}
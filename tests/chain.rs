@@ -0,0 +1,49 @@
+mod common;
+
+use reedline_repl_rs::clap::{Arg, ArgMatches, Command};
+use reedline_repl_rs::{Error, Repl, Result};
+
+fn deploy(_: ArgMatches, _: &mut ()) -> Result<Option<String>> {
+    Ok(Some("deployed".to_string()))
+}
+
+fn repl() -> Repl<(), Error> {
+    common::repl().with_command(
+        Command::new("deploy").arg(Arg::new("env").long("env").required(true)),
+        deploy,
+    )
+}
+
+#[test]
+fn and_runs_the_next_command_after_success() {
+    let response = repl().feed_line("deploy --env prod && say marked");
+    assert_eq!(response.output, "deployed\nmarked\n");
+}
+
+#[test]
+fn and_stops_at_rejected_arguments() {
+    let response = repl().feed_line("deploy --bad && say marked");
+    assert!(!response.output.contains("marked"), "{}", response.output);
+    assert!(response.errors.contains("--bad"), "{}", response.errors);
+}
+
+#[test]
+fn sequence_continues_after_rejected_arguments() {
+    let response = repl().feed_line("deploy --bad; say marked");
+    assert!(response.output.contains("marked"), "{}", response.output);
+}
+
+#[test]
+fn paste_stops_at_rejected_arguments() {
+    let mut repl = repl();
+    for line in ["paste", "deploy --bad", "say marked", "EOF"] {
+        repl.feed_line(line);
+    }
+    let response = repl.feed_line("y");
+    assert!(!response.output.contains("marked"), "{}", response.output);
+    assert!(
+        response.errors.contains("paste stopped at line 1"),
+        "{:?}",
+        response
+    );
+}
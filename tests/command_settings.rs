@@ -0,0 +1,64 @@
+use reedline_repl_rs::clap::{ArgMatches, Command};
+use reedline_repl_rs::{Error, Modes, Repl, ReplTester, Result};
+
+#[derive(Default)]
+struct Session {
+    modes: Modes,
+}
+
+fn run(_: ArgMatches, _: &mut Session) -> Result<Option<String>> {
+    Ok(Some("ran".to_string()))
+}
+
+fn disconnected(_: &Session) -> std::result::Result<(), String> {
+    Err("not connected".to_string())
+}
+
+fn hidden(_: &Session) -> bool {
+    false
+}
+
+fn in_transaction() -> Session {
+    let mut session = Session::default();
+    session.modes.push("tx");
+    session
+}
+
+#[test]
+fn settings_before_the_command_apply() {
+    ReplTester::new(
+        Repl::<Session, Error>::new(Session::default())
+            .with_guard("deploy", disconnected)
+            .with_command(Command::new("deploy"), run),
+    )
+    .send("deploy")
+    .expect_error("not connected");
+}
+
+#[test]
+fn settings_apply_to_mode_commands() {
+    ReplTester::new(
+        Repl::<Session, Error>::new(in_transaction())
+            .with_modes(|session| &session.modes)
+            .with_mode_command("tx", Command::new("commit"), run)
+            .with_guard("commit", disconnected),
+    )
+    .send("commit")
+    .expect_error("not connected");
+}
+
+#[test]
+fn visible_when_before_the_command_hides_it() {
+    let tester = ReplTester::new(
+        Repl::<Session, Error>::new(Session::default())
+            .with_visible_when("secret", hidden)
+            .with_category("status", "Monitoring")
+            .with_command(Command::new("secret").about("Secret command"), run)
+            .with_command(Command::new("status").about("Show the status"), run),
+    )
+    .send("help");
+    let output = &tester.response().output;
+    assert!(!output.contains("Secret command"), "{}", output);
+    assert!(output.contains("MONITORING:"), "{}", output);
+    tester.send("secret").expect_output("ran");
+}
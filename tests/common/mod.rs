@@ -0,0 +1,51 @@
+//! Commands shared by the integration tests, each test file uses some of them
+#![allow(dead_code)]
+
+use reedline_repl_rs::clap::{Arg, ArgMatches, Command};
+use reedline_repl_rs::{Error, Repl, ReplTester, Result};
+use std::io::Write;
+use std::process::{self, Output, Stdio};
+
+/// Prints its words separated by spaces
+pub fn say(args: ArgMatches, _: &mut ()) -> Result<Option<String>> {
+    let words: Vec<&str> = args
+        .get_many::<String>("words")
+        .unwrap_or_default()
+        .map(String::as_str)
+        .collect();
+    Ok(Some(words.join(" ")))
+}
+
+/// Fails with `failed on purpose`
+pub fn fail(_: ArgMatches, _: &mut ()) -> Result<Option<String>> {
+    Err(Error::IoError("failed on purpose".to_string()))
+}
+
+/// A REPL with `say [words]...` and `fail`
+pub fn repl() -> Repl<(), Error> {
+    Repl::new(())
+        .with_command(
+            Command::new("say").arg(Arg::new("words").num_args(0..)),
+            say,
+        )
+        .with_command(Command::new("fail"), fail)
+}
+
+/// A tester of [repl]
+pub fn tester() -> ReplTester<(), Error> {
+    ReplTester::new(repl())
+}
+
+/// Runs the test `test` again in a copy of the test binary with `variable` set and
+/// `input` on its stdin, for tests of REPLs reading from the terminal
+pub fn run_in_child(test: &str, variable: &str, input: &[u8]) -> Output {
+    let mut child = process::Command::new(std::env::current_exe().unwrap())
+        .args(["--exact", test])
+        .env(variable, "1")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(input).unwrap();
+    child.wait_with_output().unwrap()
+}
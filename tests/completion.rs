@@ -0,0 +1,47 @@
+mod common;
+
+use reedline_repl_rs::clap::Command;
+use reedline_repl_rs::{CompletionMatching, ReplTester};
+
+fn tester(matching: CompletionMatching) -> ReplTester<(), reedline_repl_rs::Error> {
+    ReplTester::new(
+        common::repl()
+            .with_command(Command::new("show-tables"), common::say)
+            .with_command(Command::new("drop-table"), common::say)
+            .with_completion_matching(matching),
+    )
+}
+
+#[test]
+fn commands_complete_by_prefix() {
+    let tester = tester(CompletionMatching::Prefix);
+    assert_eq!(tester.complete("show-"), ["show-tables"]);
+    assert!(tester.complete("tables").is_empty());
+}
+
+#[test]
+fn commands_complete_by_substring() {
+    let tester = tester(CompletionMatching::Substring);
+    assert_eq!(tester.complete("tables"), ["show-tables"]);
+    assert_eq!(tester.complete("p-tab"), ["drop-table"]);
+}
+
+#[test]
+fn commands_complete_fuzzily() {
+    let completions = tester(CompletionMatching::Fuzzy).complete("tbl");
+    assert!(completions.contains(&"show-tables".to_string()));
+    assert!(completions.contains(&"drop-table".to_string()));
+    assert!(!completions.contains(&"say".to_string()));
+}
+
+#[test]
+fn large_command_sets_complete_in_help_order() {
+    let repl = (0..3000).fold(common::repl(), |repl, number| {
+        repl.with_command(Command::new(format!("cmd-{}", number)), common::say)
+    });
+    let completions = ReplTester::new(repl).complete("cmd-29");
+    assert_eq!(completions.len(), 111);
+    assert_eq!(completions[..3], ["cmd-29", "cmd-290", "cmd-2900"]);
+    assert_eq!(completions[12], "cmd-291");
+    assert_eq!(completions.last().unwrap(), "cmd-2999");
+}
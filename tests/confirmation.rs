@@ -0,0 +1,26 @@
+mod common;
+
+use reedline_repl_rs::clap::{Arg, Command};
+use reedline_repl_rs::ReplTester;
+
+#[test]
+fn commands_needing_confirmation_are_refused_without_a_terminal() {
+    let repl = common::repl()
+        .with_command(
+            Command::new("drop").arg(Arg::new("words").required(true)),
+            common::say,
+        )
+        .with_confirmation("drop", "Drop the table?");
+    let tester = ReplTester::new(repl)
+        .send("drop users && say done")
+        .expect_error("'drop' needs confirmation, pass --yes to run it");
+    assert!(tester.response().output.is_empty());
+    tester
+        .send("drop --yes users")
+        .expect_output("users")
+        .send("drop -y users")
+        .expect_output("users")
+        .send("set confirm off")
+        .send("drop users")
+        .expect_output("users");
+}
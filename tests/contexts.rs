@@ -0,0 +1,58 @@
+use reedline_repl_rs::clap::{Arg, ArgMatches, Command};
+use reedline_repl_rs::{Error, Repl, ReplTester, Result};
+
+#[derive(Clone)]
+struct Connection {
+    url: String,
+}
+
+impl Connection {
+    fn new(url: &str) -> Self {
+        Connection {
+            url: url.to_string(),
+        }
+    }
+}
+
+fn url(_: ArgMatches, connection: &mut Connection) -> Result<Option<String>> {
+    Ok(Some(connection.url.clone()))
+}
+
+fn connect(args: ArgMatches, connection: &mut Connection) -> Result<Option<String>> {
+    connection.url = args.get_one::<String>("url").unwrap().clone();
+    Ok(None)
+}
+
+fn tester() -> ReplTester<Connection, Error> {
+    ReplTester::new(
+        Repl::new(Connection::new("localhost"))
+            .with_command(Command::new("url"), url)
+            .with_command(
+                Command::new("connect").arg(Arg::new("url").required(true)),
+                connect,
+            )
+            .with_context("prod", Connection::new("db.example.com")),
+    )
+}
+
+#[test]
+fn switch_changes_the_context_of_the_commands() {
+    let repl = tester()
+        .send("switch")
+        .expect_output("* default\n  prod")
+        .send("switch prod && url")
+        .expect_output("db.example.com")
+        .send("switch")
+        .expect_output("  default\n* prod")
+        .into_repl();
+    assert_eq!(repl.context_name(), "prod");
+}
+
+#[test]
+fn contexts_keep_their_changes() {
+    tester()
+        .send("connect other.example.com; switch prod; switch default; url")
+        .expect_output("other.example.com")
+        .send("switch stage")
+        .expect_error("Unknown context 'stage', known contexts: default, prod");
+}
@@ -0,0 +1,38 @@
+mod common;
+
+use reedline_repl_rs::{ReplEvent, ReplTester};
+use std::sync::Mutex;
+
+static EVENTS: Mutex<Vec<ReplEvent>> = Mutex::new(vec![]);
+
+fn listen(event: ReplEvent) {
+    EVENTS.lock().unwrap().push(event);
+}
+
+#[test]
+fn commands_are_reported_to_the_listener() {
+    ReplTester::new(common::repl().with_event_listener(listen))
+        .send("say hi")
+        .send("fail")
+        .send("shout hi");
+    let events = EVENTS.lock().unwrap();
+    let summary: Vec<String> = events
+        .iter()
+        .map(|event| match event {
+            ReplEvent::CommandStarted { command } => format!("start {}", command),
+            ReplEvent::CommandFinished { command, ok, .. } => format!("end {} {}", command, ok),
+            ReplEvent::UnknownCommand { command } => format!("unknown {}", command),
+            other => format!("{:?}", other),
+        })
+        .collect();
+    assert_eq!(
+        summary,
+        [
+            "start say",
+            "end say true",
+            "start fail",
+            "end fail false",
+            "unknown shout"
+        ]
+    );
+}
@@ -0,0 +1,34 @@
+mod common;
+
+use reedline_repl_rs::clap::{ArgMatches, Command};
+use reedline_repl_rs::{Repl, ReplResponse, Result};
+
+fn count(_: ArgMatches, count: &mut u32) -> Result<Option<String>> {
+    *count += 1;
+    Ok(Some(count.to_string()))
+}
+
+#[test]
+fn output_and_errors_are_returned_apart() {
+    let mut repl = common::repl();
+    let response = repl.feed_line("say a; fail; say b");
+    assert_eq!(response.output, "a\nb\n");
+    assert!(response.errors.contains("failed on purpose"));
+    assert!(!response.success);
+    assert!(!response.quit);
+    assert_eq!(
+        repl.feed_line("quit"),
+        ReplResponse {
+            success: true,
+            quit: true,
+            ..Default::default()
+        }
+    );
+}
+
+#[test]
+fn the_context_is_kept_between_lines() {
+    let mut repl = Repl::new(0).with_command(Command::new("count"), count);
+    assert_eq!(repl.feed_line("count").output, "1\n");
+    assert_eq!(repl.feed_line("count; count").output, "2\n3\n");
+}
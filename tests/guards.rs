@@ -0,0 +1,31 @@
+use reedline_repl_rs::clap::{ArgMatches, Command};
+use reedline_repl_rs::{Repl, ReplTester, Result};
+
+fn connect(_: ArgMatches, connected: &mut bool) -> Result<Option<String>> {
+    *connected = true;
+    Ok(None)
+}
+
+fn query(_: ArgMatches, _: &mut bool) -> Result<Option<String>> {
+    Ok(Some("3 rows".to_string()))
+}
+
+fn connected(connected: &bool) -> std::result::Result<(), String> {
+    match connected {
+        true => Ok(()),
+        false => Err("not connected".to_string()),
+    }
+}
+
+#[test]
+fn guards_reject_commands_until_they_pass() {
+    let repl = Repl::new(false)
+        .with_command(Command::new("connect"), connect)
+        .with_command(Command::new("query"), query)
+        .with_guard("query", connected);
+    ReplTester::new(repl)
+        .send("query")
+        .expect_error("Can't run 'query': not connected")
+        .send("connect && query")
+        .expect_output("3 rows");
+}
@@ -0,0 +1,149 @@
+mod common;
+
+use common::{repl, tester};
+use reedline_repl_rs::clap::{Arg, ArgMatches, Command};
+use reedline_repl_rs::{ReplTester, Result};
+
+fn login(_: ArgMatches, _: &mut ()) -> Result<Option<String>> {
+    Ok(Some("logged in".to_string()))
+}
+
+#[test]
+fn expanded_entry_runs_its_whole_chain() {
+    tester()
+        .send("say a; say b")
+        .expect_output("a\nb")
+        .send("!1")
+        .expect_output("say a; say b\na\nb");
+}
+
+#[test]
+fn repeat_last_uses_the_history() {
+    tester()
+        .send("say a && say b")
+        .expect_output("a\nb")
+        .send("!!")
+        .expect_output("say a && say b\na\nb")
+        .send("!!")
+        .expect_output("say a && say b\na\nb");
+}
+
+#[test]
+fn prefix_finds_the_most_recent_entry() {
+    tester()
+        .send("say a")
+        .send("say b")
+        .send("!say")
+        .expect_output("say b\nb")
+        .send("!x")
+        .expect_error("!x");
+}
+
+#[test]
+fn repeated_lines_are_one_entry() {
+    tester()
+        .send("say a")
+        .send("say a")
+        .send("say b")
+        .send("!2")
+        .expect_output("say b\nb");
+}
+
+#[test]
+fn history_lists_matching_entries_and_clears() {
+    tester()
+        .send("say a")
+        .send("say b")
+        .send("history say")
+        .expect_output("    1  say a\n    2  say b\n    3  history say")
+        .send("history clear")
+        .expect_success()
+        .send("history")
+        .expect_output("    1  history");
+}
+
+#[test]
+fn history_keeps_only_its_capacity() {
+    let path = std::env::temp_dir().join("reedline-repl-rs-history-capacity");
+    let _ = std::fs::remove_file(&path);
+    ReplTester::new(repl().with_history(path, 2))
+        .send("say a")
+        .send("say b")
+        .send("say c")
+        .send("!1")
+        .expect_output("say c\nc")
+        .send("!say a")
+        .expect_error("!say a");
+}
+
+#[test]
+fn excluded_commands_anywhere_in_a_chain_never_reach_the_file() {
+    let path = std::env::temp_dir().join("reedline-repl-rs-history-excluded-chain");
+    let _ = std::fs::remove_file(&path);
+    let tester = ReplTester::new(
+        repl()
+            .with_history(path.clone(), 10)
+            .with_command(
+                Command::new("login").arg(Arg::new("password").required(true)),
+                login,
+            )
+            .with_history_excluded("login"),
+    )
+    .send("say a")
+    .send("say a && login hunter2")
+    .send("say a; login hunter2")
+    .send(" say b")
+    .send("history")
+    .expect_output("    1  say a\n    2  history");
+    // the history file is written when the REPL is dropped
+    drop(tester);
+    let saved = std::fs::read_to_string(&path).unwrap();
+    assert!(saved.contains("say a"));
+    assert!(!saved.contains("hunter2"));
+    assert!(!saved.contains("say b"));
+}
+
+#[test]
+fn sensitive_values_never_reach_the_file_while_running() {
+    let path = std::env::temp_dir().join("reedline-repl-rs-history-sensitive");
+    let _ = std::fs::remove_file(&path);
+    let tester = ReplTester::new(
+        repl()
+            .with_history(path.clone(), 10)
+            .with_shared_history(true)
+            .with_command(Command::new("login").arg(Arg::new("password")), login)
+            .with_sensitive_arg("login", "password"),
+    )
+    .send("login hunter2")
+    .send("say a; login hunter2")
+    .send("say b");
+    // a shared history file is written after every line
+    let saved = std::fs::read_to_string(&path).unwrap();
+    assert!(saved.contains("say b"));
+    assert!(!saved.contains("hunter2"));
+    drop(tester);
+    assert!(!std::fs::read_to_string(&path).unwrap().contains("hunter2"));
+}
+
+#[test]
+fn concurrent_sessions_merge_their_lines() {
+    let path = std::env::temp_dir().join("reedline-repl-rs-history-shared");
+    let _ = std::fs::remove_file(&path);
+    let session = || {
+        ReplTester::new(
+            repl()
+                .with_history(path.clone(), 10)
+                .with_shared_history(true),
+        )
+    };
+    let first = session().send("say a");
+    let second = session().send("say b");
+    let first = first.send("say c");
+    // dropping a session writes its file again, under the lock, keeping the other's lines
+    drop(first);
+    let second = second.send("say d");
+    drop(second);
+    session()
+        .send("history")
+        .expect_output("    1  say a\n    2  say b\n    3  say c\n    4  say d\n    5  history");
+}
@@ -0,0 +1,47 @@
+mod common;
+
+use reedline_repl_rs::clap::{ArgMatches, Command};
+use reedline_repl_rs::{confirm, input, Error, Repl, ReplTester, Result};
+
+/// Set for the copy of the test binary that reads the answers from its stdin
+const CHILD: &str = "REEDLINE_REPL_INTERACT_CHILD";
+
+fn delete(_: ArgMatches, _: &mut ()) -> Result<Option<String>> {
+    match confirm("Delete 3 rows?")? {
+        true => Ok(Some("deleted".to_string())),
+        false => Ok(Some("kept".to_string())),
+    }
+}
+
+fn greet(_: ArgMatches, _: &mut ()) -> Result<Option<String>> {
+    let name = input("name: ")?;
+    Ok(Some(format!("hello {}", name)))
+}
+
+#[test]
+fn callbacks_read_answers_from_stdin() {
+    if std::env::var_os(CHILD).is_some() {
+        ReplTester::new(
+            Repl::<(), Error>::new(())
+                .with_command(Command::new("delete"), delete)
+                .with_command(Command::new("greet"), greet),
+        )
+        .send("delete")
+        .expect_output("deleted")
+        .send("delete")
+        .expect_output("kept")
+        .send("greet")
+        .expect_output("hello world");
+        return;
+    }
+    let output = common::run_in_child(
+        "callbacks_read_answers_from_stdin",
+        CHILD,
+        b"yes\nno\nworld\n",
+    );
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stdout)
+    );
+}
@@ -0,0 +1,91 @@
+mod common;
+
+use reedline_repl_rs::clap::{ArgMatches, Command};
+use reedline_repl_rs::{CancellationToken, Error, Repl, ReplTester, Result};
+use std::thread;
+use std::time::Duration;
+
+fn tester() -> ReplTester<(), Error> {
+    ReplTester::new(common::repl().with_background_jobs())
+}
+
+#[test]
+fn foreground_prints_the_job_output() {
+    tester()
+        .send("say hi there &")
+        .expect_output_contains("[1] say hi there")
+        .send("fg 1")
+        .expect_output("hi there");
+}
+
+#[test]
+fn jobs_lists_started_jobs() {
+    tester()
+        .send("say one &")
+        .expect_success()
+        .send("say two &")
+        .expect_success()
+        .send("jobs")
+        .expect_output_contains("[1]")
+        .send("jobs")
+        .expect_output_contains("say two");
+}
+
+#[test]
+fn foreground_reports_job_errors() {
+    tester()
+        .send("fail &")
+        .expect_success()
+        .send("fg")
+        .expect_error("failed on purpose");
+}
+
+#[test]
+fn foreground_unknown_job_fails() {
+    tester().send("fg 7").expect_error("7");
+}
+
+fn interrupt(_: ArgMatches, token: &mut CancellationToken) -> Result<Option<String>> {
+    thread::sleep(Duration::from_millis(50));
+    token.cancel();
+    thread::sleep(Duration::from_millis(200));
+    Ok(Some("finished".to_string()))
+}
+
+#[test]
+fn interrupted_foreground_leaves_the_job_running() {
+    let token = CancellationToken::new();
+    ReplTester::new(
+        Repl::new(token.clone())
+            .with_cancellation_token(token)
+            .with_background_jobs()
+            .with_command(Command::new("interrupt"), interrupt),
+    )
+    .send("interrupt &")
+    .expect_success()
+    .send("fg 1")
+    .expect_output_contains("[1] Running")
+    .send("fg 1")
+    .expect_output("finished");
+}
+
+#[cfg(feature = "async")]
+#[tokio::test]
+async fn foreground_waits_for_the_job_in_async_repls() {
+    let mut repl: Repl<(), Error> = common::repl().with_background_jobs();
+    assert!(repl.feed_line_async("say later &").await.success);
+    let response = repl.feed_line_async("fg").await;
+    assert!(response.success);
+    assert_eq!(response.output.trim_end(), "later");
+}
+
+// ConPTY hands lines over with the carriage return of the Enter key
+#[cfg(windows)]
+#[test]
+fn background_lines_may_end_with_a_carriage_return() {
+    tester()
+        .send("say hi &\r")
+        .expect_output_contains("[1] say hi")
+        .send("fg 1\r")
+        .expect_output("hi");
+}
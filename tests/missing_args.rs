@@ -0,0 +1,19 @@
+mod common;
+
+use reedline_repl_rs::clap::{Arg, Command};
+use reedline_repl_rs::ReplTester;
+
+#[test]
+fn missing_arguments_fail_without_a_terminal() {
+    let repl = common::repl()
+        .with_command(
+            Command::new("greet").arg(Arg::new("words").required(true)),
+            common::say,
+        )
+        .with_prompt_for_missing_args(true);
+    ReplTester::new(repl)
+        .send("greet")
+        .expect_error("required")
+        .send("greet ann")
+        .expect_output("ann");
+}
@@ -0,0 +1,60 @@
+use reedline_repl_rs::clap::{ArgMatches, Command};
+use reedline_repl_rs::{Error, Modes, Repl, ReplTester, Result};
+
+#[derive(Default)]
+struct Router {
+    modes: Modes,
+}
+
+fn configure(_: ArgMatches, router: &mut Router) -> Result<Option<String>> {
+    router.modes.push("config");
+    Ok(None)
+}
+
+fn end(_: ArgMatches, router: &mut Router) -> Result<Option<String>> {
+    router.modes.pop();
+    Ok(None)
+}
+
+fn show(_: ArgMatches, _: &mut Router) -> Result<Option<String>> {
+    Ok(Some("running config".to_string()))
+}
+
+fn show_candidate(_: ArgMatches, _: &mut Router) -> Result<Option<String>> {
+    Ok(Some("candidate config".to_string()))
+}
+
+fn tester() -> ReplTester<Router, Error> {
+    ReplTester::new(
+        Repl::new(Router::default())
+            .with_modes(|router| &router.modes)
+            .with_command(Command::new("configure"), configure)
+            .with_command(Command::new("show"), show)
+            .with_mode_command("config", Command::new("show"), show_candidate)
+            .with_mode_command("config", Command::new("end"), end),
+    )
+}
+
+#[test]
+fn mode_commands_are_only_available_in_their_mode() {
+    tester()
+        .send("end")
+        .expect_error("Unknown command 'end'")
+        .send("configure")
+        .expect_success()
+        .send("end")
+        .expect_success()
+        .send("end")
+        .expect_error("Unknown command 'end'");
+}
+
+#[test]
+fn mode_commands_take_precedence_over_global_ones() {
+    tester()
+        .send("show")
+        .expect_output("running config")
+        .send("configure; show")
+        .expect_output("candidate config")
+        .send("end; show")
+        .expect_output("running config");
+}
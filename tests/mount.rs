@@ -0,0 +1,30 @@
+mod common;
+
+use reedline_repl_rs::clap::{Arg, ArgMatches, Command};
+use reedline_repl_rs::{Repl, ReplTester, Result};
+
+struct Database {
+    name: String,
+}
+
+fn query(args: ArgMatches, database: &mut Database) -> Result<Option<String>> {
+    let sql = args.get_one::<String>("sql").unwrap();
+    Ok(Some(format!("{}: {}", database.name, sql)))
+}
+
+#[test]
+fn mounted_output_is_returned_by_the_parent() {
+    let database = Repl::new(Database {
+        name: "users".to_string(),
+    })
+    .with_command(
+        Command::new("query").arg(Arg::new("sql").required(true)),
+        query,
+    );
+    let repl = common::repl().with_mount("db", database);
+    ReplTester::new(repl)
+        .send("db query select")
+        .expect_output("users: select")
+        .send("db help")
+        .expect_output_contains("query");
+}
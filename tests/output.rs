@@ -0,0 +1,46 @@
+use reedline_repl_rs::clap::{Arg, ArgMatches, Command};
+use reedline_repl_rs::{Error, Repl, ReplTester, Result};
+use std::fmt;
+
+struct Usage {
+    mount: String,
+    used: u64,
+}
+
+impl fmt::Display for Usage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {} GB used", self.mount, self.used)
+    }
+}
+
+fn usage(args: ArgMatches, _: &mut ()) -> Result<Option<Usage>> {
+    Ok(Some(Usage {
+        mount: args.get_one::<String>("mount").unwrap().clone(),
+        used: 42,
+    }))
+}
+
+fn json(usage: &Usage) -> String {
+    format!(r#"{{"mount": "{}", "used": {}}}"#, usage.mount, usage.used)
+}
+
+fn repl() -> Repl<(), Error, Usage> {
+    Repl::new(()).with_command(
+        Command::new("usage").arg(Arg::new("mount").required(true)),
+        usage,
+    )
+}
+
+#[test]
+fn structured_output_is_displayed_by_default() {
+    ReplTester::new(repl())
+        .send("usage /home")
+        .expect_output("/home: 42 GB used");
+}
+
+#[test]
+fn renderer_formats_structured_output() {
+    ReplTester::new(repl().with_output_renderer(json))
+        .send("usage /home")
+        .expect_output(r#"{"mount": "/home", "used": 42}"#);
+}
@@ -0,0 +1,48 @@
+use reedline_repl_rs::clap::{ArgMatches, Command};
+use reedline_repl_rs::{Error, PagerMode, Repl, ReplTester, Result};
+
+fn log(_: ArgMatches, _: &mut ()) -> Result<Option<String>> {
+    let lines: Vec<String> = (1..=500).map(|line| format!("line {}", line)).collect();
+    Ok(Some(lines.join("\n")))
+}
+
+fn repl() -> Repl<(), Error> {
+    Repl::new(()).with_command(Command::new("log"), log)
+}
+
+#[test]
+fn long_output_is_returned_whole_without_a_terminal() {
+    let tester = ReplTester::new(repl().with_pager(PagerMode::External("false".to_string())))
+        .send("log")
+        .expect_success();
+    let output = &tester.response().output;
+    assert_eq!(output.lines().count(), 500);
+    assert_eq!(output.lines().last(), Some("line 500"));
+}
+
+/// The value of the paging option in the listing of `show options`
+fn paging(options: &str) -> Option<String> {
+    let line = options
+        .lines()
+        .find(|line| line.trim_start().starts_with("paging"))?;
+    line.split_whitespace().nth(1).map(str::to_string)
+}
+
+#[test]
+fn paging_can_be_changed_with_set() {
+    let tester = ReplTester::new(repl().with_pager(PagerMode::Internal)).send("show options");
+    assert_eq!(
+        paging(&tester.response().output).as_deref(),
+        Some("internal")
+    );
+    let tester = tester
+        .send("set paging less")
+        .expect_success()
+        .send("show options");
+    assert_eq!(paging(&tester.response().output).as_deref(), Some("less"));
+    let tester = tester
+        .send("set paging off")
+        .expect_success()
+        .send("show options");
+    assert_eq!(paging(&tester.response().output).as_deref(), Some("off"));
+}
@@ -0,0 +1,56 @@
+mod common;
+
+use reedline_repl_rs::clap::{Arg, ArgMatches, Command};
+use reedline_repl_rs::{Error, ReplTester, Result};
+use std::collections::HashMap;
+
+fn filter(args: ArgMatches, _: &mut ()) -> Result<Option<String>> {
+    let status = args
+        .get_one::<String>("status")
+        .cloned()
+        .unwrap_or_default();
+    let age = args.get_one::<String>("age").cloned().unwrap_or_default();
+    Ok(Some(format!("status {} age {}", status, age)))
+}
+
+/// Parses `name=value` and `name>value` conditions joined with `AND`
+fn conditions(raw: &str, _: &()) -> Result<HashMap<String, String>> {
+    raw.split(" AND ")
+        .filter(|condition| !condition.trim().is_empty())
+        .map(|condition| {
+            condition
+                .split_once(['=', '>'])
+                .map(|(name, value)| (name.trim().to_string(), value.trim().to_string()))
+                .ok_or_else(|| Error::InvalidValue(condition.to_string()))
+        })
+        .collect()
+}
+
+fn tester() -> ReplTester<(), Error> {
+    ReplTester::new(
+        common::repl()
+            .with_parser("filter", conditions)
+            .with_command(
+                Command::new("filter").arg(
+                    Arg::new("status")
+                        .required(true)
+                        .value_parser(["open", "closed"]),
+                ),
+                filter,
+            ),
+    )
+}
+
+#[test]
+fn parsed_values_reach_the_callback_without_clap() {
+    tester()
+        .send("filter status=pending AND age>3d")
+        .expect_output("status pending age 3d")
+        .send("filter age>-1")
+        .expect_output("status  age -1");
+}
+
+#[test]
+fn parser_errors_fail_the_line() {
+    tester().send("filter status").expect_error("status");
+}
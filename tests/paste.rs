@@ -0,0 +1,12 @@
+mod common;
+
+use reedline_repl_rs::PastePolicy;
+
+#[test]
+fn reviewed_pastes_run_line_by_line() {
+    let mut repl = common::repl().with_paste_policy(PastePolicy::Review);
+    let response = repl.feed_line("say a\nfail\nsay b");
+    assert_eq!(response.output, "a\nb\n");
+    assert!(response.errors.contains("paste:2: fail"));
+    assert!(response.errors.contains("failed on purpose"));
+}
@@ -0,0 +1,41 @@
+#![cfg(feature = "scripts")]
+
+use reedline_repl_rs::clap::{ArgMatches, Command};
+use reedline_repl_rs::{Repl, ReplError, Result};
+use std::path::Path;
+
+fn count(_: ArgMatches, count: &mut u32) -> Result<Option<String>> {
+    *count += 1;
+    Ok(Some(count.to_string()))
+}
+
+fn save(count: &u32) -> std::result::Result<String, ReplError> {
+    Ok(count.to_string())
+}
+
+fn load(count: &mut u32, snapshot: &str) -> std::result::Result<(), ReplError> {
+    *count = snapshot.trim().parse()?;
+    Ok(())
+}
+
+fn session(path: &Path) -> Repl<u32, reedline_repl_rs::Error> {
+    Repl::new(0)
+        .with_command(Command::new("count"), count)
+        .with_context_persistence(save, load, path)
+        .with_output(std::io::sink())
+        .with_error_output(std::io::sink())
+}
+
+#[test]
+fn sessions_continue_with_the_saved_context() {
+    let path = std::env::temp_dir().join("reedline-repl-rs-context");
+    let _ = std::fs::remove_file(&path);
+    session(&path).run_with_reader("count\n".as_bytes()).unwrap();
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "1");
+    session(&path).run_with_reader("count\n".as_bytes()).unwrap();
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "2");
+    // a snapshot that can't be loaded starts over with the initial context
+    std::fs::write(&path, "many").unwrap();
+    session(&path).run_with_reader("count\n".as_bytes()).unwrap();
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "1");
+}
@@ -0,0 +1,33 @@
+mod common;
+
+use common::say;
+use reedline_repl_rs::clap::{Arg, Command};
+use reedline_repl_rs::{Error, Repl, ReplTester};
+
+fn repl() -> Repl<(), Error> {
+    Repl::new(())
+        .with_command(
+            Command::new("deploy").arg(Arg::new("words").num_args(0..)),
+            say,
+        )
+        .with_command(
+            Command::new("describe").arg(Arg::new("words").num_args(0..)),
+            say,
+        )
+}
+
+#[test]
+fn unambiguous_prefixes_run_their_command() {
+    ReplTester::new(repl().with_command_prefixes(true))
+        .send("dep prod")
+        .expect_output("prod")
+        .send("de prod")
+        .expect_error("Ambiguous command 'de'");
+}
+
+#[test]
+fn prefixes_are_unknown_commands_by_default() {
+    ReplTester::new(repl())
+        .send("dep prod")
+        .expect_error("Unknown command 'dep'");
+}
@@ -0,0 +1,20 @@
+mod common;
+
+use reedline_repl_rs::ReplTester;
+
+/// Expands `twice <words>` to two `say` commands
+fn twice(line: String, _: &()) -> String {
+    match line.strip_prefix("twice ") {
+        Some(words) => format!("say {0}; say {0}", words),
+        None => line,
+    }
+}
+
+#[test]
+fn rewritten_lines_run_instead_of_the_typed_ones() {
+    ReplTester::new(common::repl().with_line_preprocessor(twice))
+        .send("twice hi # greeting")
+        .expect_output("hi\nhi")
+        .send("say once")
+        .expect_output("once");
+}
@@ -0,0 +1,21 @@
+use reedline_repl_rs::clap::{ArgMatches, Command};
+use reedline_repl_rs::{Error, Progress, Repl, ReplTester, Result};
+
+fn import(_: ArgMatches, _: &mut ()) -> Result<Option<String>> {
+    let mut progress = Progress::bar(3, "importing");
+    for _ in 0..3 {
+        progress.inc(1);
+    }
+    progress.finish();
+    let mut spinner = Progress::spinner("indexing");
+    spinner.tick();
+    spinner.set_message("done");
+    Ok(Some("imported 3 rows".to_string()))
+}
+
+#[test]
+fn progress_stays_out_of_the_output() {
+    ReplTester::new(Repl::<(), Error>::new(()).with_command(Command::new("import"), import))
+        .send("import")
+        .expect_output("imported 3 rows");
+}
@@ -0,0 +1,113 @@
+use reedline_repl_rs::clap::{Arg, ArgMatches, Command};
+use reedline_repl_rs::{CommandRegistry, Error, Modes, Repl, ReplTester, Result};
+
+#[derive(Default)]
+struct Context {
+    registry: CommandRegistry<Context, Error>,
+    modes: Modes,
+}
+
+fn load(_: ArgMatches, context: &mut Context) -> Result<Option<String>> {
+    context.registry.register(
+        Command::new("greet").arg(Arg::new("name").required(true)),
+        greet,
+    );
+    Ok(Some("loaded".to_string()))
+}
+
+fn unload(_: ArgMatches, context: &mut Context) -> Result<Option<String>> {
+    context.registry.unregister("greet");
+    Ok(None)
+}
+
+fn configure(_: ArgMatches, context: &mut Context) -> Result<Option<String>> {
+    context.modes.push("config");
+    Ok(None)
+}
+
+fn greet(args: ArgMatches, _: &mut Context) -> Result<Option<String>> {
+    let name: &String = args.get_one("name").unwrap();
+    Ok(Some(format!("hello {}", name)))
+}
+
+fn repl() -> Repl<Context, Error> {
+    let context = Context::default();
+    let registry = context.registry.clone();
+    Repl::new(context)
+        .with_command_registry(registry)
+        .with_command(Command::new("load"), load)
+        .with_command(Command::new("unload"), unload)
+}
+
+fn tester() -> ReplTester<Context, Error> {
+    ReplTester::new(repl())
+}
+
+#[test]
+fn callbacks_register_commands() {
+    tester()
+        .send("greet you")
+        .expect_error("Unknown command 'greet'")
+        .send("load")
+        .expect_output("loaded")
+        .send("greet you")
+        .expect_output("hello you");
+}
+
+#[test]
+fn registered_commands_run_later_in_the_chain() {
+    tester()
+        .send("load && greet chain")
+        .expect_output("loaded\nhello chain");
+}
+
+#[test]
+fn callbacks_unregister_commands() {
+    tester()
+        .send("load; unload")
+        .expect_success()
+        .send("greet you")
+        .expect_error("Unknown command 'greet'");
+}
+
+#[test]
+fn session_report_lists_registered_commands_as_plugins() {
+    tester()
+        .send("session info")
+        .expect_output_contains("plugin commands: none")
+        .send("load")
+        .expect_success()
+        .send("session info")
+        .expect_output_contains("plugin commands: greet");
+}
+
+#[test]
+fn unregistering_finds_the_command_of_the_active_mode() {
+    ReplTester::new(
+        repl()
+            .with_modes(|context| &context.modes)
+            .with_command(Command::new("configure"), configure)
+            .with_mode_command(
+                "config",
+                Command::new("greet").arg(Arg::new("name").required(true)),
+                greet,
+            ),
+    )
+    .send("configure")
+    .expect_success()
+    .send("greet you")
+    .expect_output("hello you")
+    .send("unload")
+    .expect_success()
+    .send("greet you")
+    .expect_error("Unknown command 'greet'");
+}
+
+#[test]
+fn registered_commands_are_completed() {
+    let tester = tester();
+    assert!(tester.complete("gre").is_empty());
+    let tester = tester.send("load");
+    assert_eq!(tester.complete("gre"), ["greet"]);
+    assert!(tester.send("unload").complete("gre").is_empty());
+}
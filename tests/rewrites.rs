@@ -0,0 +1,19 @@
+mod common;
+
+use reedline_repl_rs::ReplTester;
+
+#[test]
+fn lines_are_rewritten_where_they_ask_for_it() {
+    ReplTester::new(common::repl().with_arithmetic(true))
+        .send("say plain words")
+        .expect_output("plain words")
+        .send("say a # note")
+        .expect_output("a")
+        .send("say 'a # b' c#d")
+        .expect_output("a # b c#d")
+        .send("say a \\\n b")
+        .expect_output("a b")
+        .send("set n 2")
+        .send("say $n $(2*3) 4")
+        .expect_output("2 6 4");
+}
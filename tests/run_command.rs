@@ -0,0 +1,59 @@
+mod common;
+
+use reedline_repl_rs::Error;
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex};
+
+/// Output shared with the test after the REPL took it
+#[derive(Clone, Default)]
+struct Shared(Arc<Mutex<Vec<u8>>>);
+
+impl Shared {
+    fn text(&self) -> String {
+        String::from_utf8_lossy(&self.0.lock().unwrap()).to_string()
+    }
+}
+
+impl Write for Shared {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+fn run(args: &[&str]) -> (reedline_repl_rs::Result<()>, String, String) {
+    let (output, errors) = (Shared::default(), Shared::default());
+    let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+    let result = common::repl()
+        .with_output(output.clone())
+        .with_error_output(errors.clone())
+        .run_command(&args);
+    (result, output.text(), errors.text())
+}
+
+#[test]
+fn commands_run_from_the_arguments() {
+    let (result, output, errors) = run(&["say", "a b", "c"]);
+    assert!(result.is_ok());
+    assert_eq!(output, "a b c\n");
+    assert!(errors.is_empty());
+}
+
+#[test]
+fn failures_are_printed_and_returned() {
+    let (result, _, errors) = run(&["fail"]);
+    assert!(matches!(result, Err(Error::CommandFailed(command)) if command == "fail"));
+    assert!(errors.contains("failed on purpose"));
+    let (result, _, _) = run(&["bogus"]);
+    assert!(result.is_err());
+}
+
+#[test]
+fn help_is_shown_without_arguments() {
+    let (result, output, _) = run(&[]);
+    assert!(result.is_ok());
+    assert!(output.contains("say"));
+}
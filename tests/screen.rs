@@ -0,0 +1,16 @@
+use reedline_repl_rs::clap::{ArgMatches, Command};
+use reedline_repl_rs::{with_alternate_screen, Error, Repl, ReplTester, Result};
+
+fn dashboard(_: ArgMatches, _: &mut ()) -> Result<Option<String>> {
+    let summary = with_alternate_screen(|| "3 services up".to_string())?;
+    Ok(Some(summary))
+}
+
+#[test]
+fn commands_return_to_the_main_screen_with_their_output() {
+    ReplTester::new(Repl::<(), Error>::new(()).with_command(Command::new("dashboard"), dashboard))
+        .send("dashboard")
+        .expect_output("3 services up")
+        .send("dashboard && echo again")
+        .expect_output("3 services up\nagain");
+}
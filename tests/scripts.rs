@@ -0,0 +1,38 @@
+#![cfg(feature = "scripts")]
+
+use reedline_repl_rs::{Error, Repl, ReplTester};
+
+fn source(name: &str, script: &str) -> ReplTester<(), Error> {
+    let path = std::env::temp_dir().join(format!("reedline-repl-rs-{}.txt", name));
+    std::fs::write(&path, script).unwrap();
+    ReplTester::new(Repl::new(())).send(&format!("source {}", path.display()))
+}
+
+#[test]
+fn apostrophe_doesnt_swallow_later_lines() {
+    let tester = source("apostrophe", "echo don't\necho next\n");
+    let response = tester.response();
+    assert!(response.errors.contains("don't"), "{}", response.errors);
+    assert!(response.output.contains("next"), "{}", response.output);
+}
+
+#[test]
+fn blocks_and_continuations_span_lines() {
+    source(
+        "blocks",
+        "repeat 2 {\n  echo \"it's\" \\\n    here\n}\nif 1 == 2 {\n  echo no\n} else {\n  echo yes\n}\n",
+    )
+    .expect_output("it's here\nit's here\nyes");
+}
+
+#[test]
+fn unclosed_block_at_the_end_is_reported() {
+    let tester = source("unclosed", "echo first\nrepeat 2 {\n  echo again\n");
+    let response = tester.response();
+    assert!(
+        response.errors.contains("missing '}'"),
+        "{}",
+        response.errors
+    );
+    assert_eq!(response.output, "first\n");
+}
@@ -0,0 +1,8 @@
+use reedline_repl_rs::Repl;
+
+fn assert_send<T: Send>() {}
+
+#[test]
+fn repl_is_send() {
+    assert_send::<Repl<Vec<String>, reedline_repl_rs::Error>>();
+}
@@ -0,0 +1,142 @@
+#![cfg(feature = "serve")]
+
+mod common;
+
+use reedline_repl_rs::{Error, Listener, Repl};
+use std::cell::RefCell;
+use std::io::{self, Cursor, Read, Write};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Connection to a client sending `input`, whose writes fail once it disconnected
+#[derive(Clone)]
+struct Client {
+    input: Arc<Mutex<Cursor<Vec<u8>>>>,
+    output: Arc<Mutex<Vec<u8>>>,
+    disconnected: bool,
+    timeout: Arc<Mutex<Option<Duration>>>,
+}
+
+impl Client {
+    fn new(input: &str, disconnected: bool) -> Self {
+        Client {
+            input: Arc::new(Mutex::new(Cursor::new(input.as_bytes().to_vec()))),
+            output: Arc::default(),
+            disconnected,
+            timeout: Arc::default(),
+        }
+    }
+
+    fn received(&self) -> String {
+        String::from_utf8(self.output.lock().unwrap().clone()).unwrap()
+    }
+}
+
+impl Read for Client {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.input.lock().unwrap().read(buf)
+    }
+}
+
+impl Write for Client {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.disconnected {
+            return Err(io::ErrorKind::BrokenPipe.into());
+        }
+        self.output.lock().unwrap().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Hands out the clients in order, then fails to end `serve`
+struct Clients(RefCell<Vec<Client>>);
+
+impl Listener for Clients {
+    type Stream = Client;
+
+    fn accept_client(&self) -> io::Result<Client> {
+        let mut clients = self.0.borrow_mut();
+        if clients.is_empty() {
+            return Err(io::ErrorKind::ConnectionAborted.into());
+        }
+        Ok(clients.remove(0))
+    }
+
+    fn try_clone(stream: &Client) -> io::Result<Client> {
+        Ok(stream.clone())
+    }
+
+    fn set_read_timeout(stream: &Client, timeout: Option<Duration>) -> io::Result<()> {
+        *stream.timeout.lock().unwrap() = timeout;
+        Ok(())
+    }
+}
+
+fn repl() -> Repl<(), Error> {
+    common::repl()
+        .with_error_output(io::sink())
+        .with_shell_escape('!')
+        .with_redirects(true)
+}
+
+fn serve(clients: &[Client]) {
+    let listener = Clients(RefCell::new(clients.to_vec()));
+    assert!(repl().serve(listener).is_err());
+}
+
+#[test]
+fn clients_have_no_shell_escape_or_redirects() {
+    let path = std::env::temp_dir().join("reedline-repl-rs-serve-redirect");
+    let _ = std::fs::remove_file(&path);
+    let client = Client::new(
+        &format!("!echo hi\nsay a > {}\nclear\n", path.display()),
+        false,
+    );
+    serve(std::slice::from_ref(&client));
+    let received = client.received();
+    assert!(received.contains("!echo"), "{}", received);
+    assert!(received.contains("a > "), "{}", received);
+    assert!(received.contains("clear"), "{}", received);
+    assert!(!path.exists());
+}
+
+#[test]
+fn disconnected_client_ends_its_session() {
+    let gone = Client::new("say hello\nsay hello\n", true);
+    let next = Client::new("say again\n", false);
+    serve(&[gone, next.clone()]);
+    assert!(next.received().contains("again"));
+}
+
+#[test]
+fn clients_get_the_read_timeout() {
+    let client = Client::new("say hello\n", false);
+    let listener = Clients(RefCell::new(vec![client.clone()]));
+    let mut repl = repl().with_client_timeout(Some(Duration::from_secs(7)));
+    assert!(repl.serve(listener).is_err());
+    assert_eq!(
+        *client.timeout.lock().unwrap(),
+        Some(Duration::from_secs(7))
+    );
+}
+
+#[test]
+fn colors_are_restored_after_a_client() {
+    let client = Client::new("show options\n", false);
+    let listener = Clients(RefCell::new(vec![client.clone()]));
+    let mut repl = repl().with_ansi(true);
+    assert!(repl.serve(listener).is_err());
+    let color = |options: &str| {
+        options
+            .lines()
+            .find(|line| line.trim_start().starts_with("color"))
+            .and_then(|line| line.split_whitespace().nth(1))
+            .map(str::to_string)
+    };
+    assert_eq!(color(&client.received()).as_deref(), Some("off"));
+    let response = repl.feed_line("show options");
+    assert_eq!(color(&response.output).as_deref(), Some("on"));
+}
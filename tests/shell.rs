@@ -0,0 +1,15 @@
+#![cfg(unix)]
+mod common;
+
+use reedline_repl_rs::ReplTester;
+
+#[test]
+fn escaped_lines_run_in_the_shell() {
+    ReplTester::new(common::repl().with_shell_escape('!'))
+        .send("!echo one; echo two")
+        .expect_output("one\ntwo")
+        .send("!exit 3")
+        .expect_error("Shell command 'exit 3' failed")
+        .send("say !echo")
+        .expect_output("!echo");
+}
@@ -0,0 +1,22 @@
+mod common;
+
+use reedline_repl_rs::{CtrlCAction, CtrlDAction, ReplTester};
+
+#[test]
+fn session_report_shows_the_ctrl_c_and_ctrl_d_actions() {
+    ReplTester::new(common::repl())
+        .send("session info")
+        .expect_output_contains("ctrl-c: ClearLine")
+        .expect_output_contains("ctrl-d: Quit");
+    ReplTester::new(
+        common::repl()
+            .with_on_ctrl_c(CtrlCAction::Quit)
+            .with_stop_on_ctrl_d(false),
+    )
+    .send("session info")
+    .expect_output_contains("ctrl-c: Quit")
+    .expect_output_contains("ctrl-d: Ignore");
+    ReplTester::new(common::repl().with_on_ctrl_d(CtrlDAction::Quit))
+        .send("session info")
+        .expect_output_contains("ctrl-d: Quit");
+}
@@ -0,0 +1,18 @@
+mod common;
+
+use common::tester;
+
+#[test]
+fn commands_are_counted() {
+    let repl = tester()
+        .send("stats")
+        .expect_output("No commands have run yet")
+        .send("say a; say b; fail")
+        .send("stats")
+        .expect_output_contains("fail")
+        .into_repl();
+    let stats = repl.stats();
+    assert_eq!((stats["say"].invocations, stats["say"].errors), (2, 0));
+    assert_eq!((stats["fail"].invocations, stats["fail"].errors), (1, 1));
+    assert!(!stats.contains_key("stats"));
+}
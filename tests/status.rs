@@ -0,0 +1,28 @@
+mod common;
+
+/// Set for the copy of the test binary that runs the REPL on its stdin
+const CHILD: &str = "REEDLINE_REPL_STATUS_CHILD";
+
+#[test]
+fn the_prompt_shows_the_status_of_the_last_line() {
+    if std::env::var_os(CHILD).is_some() {
+        common::repl()
+            .with_name("db")
+            .with_status_segment(true)
+            .with_ansi(false)
+            .run()
+            .unwrap();
+        return;
+    }
+    let output = common::run_in_child(
+        "the_prompt_shows_the_status_of_the_last_line",
+        CHILD,
+        b"say a\nfail\n",
+    );
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "{}", stdout);
+    let prompts: Vec<&str> = stdout.split("db").skip(1).collect();
+    // each prompt starts with the status of the line before it
+    assert!(prompts[0].contains("a\n✔ "), "{}", stdout);
+    assert!(prompts[1].contains("✘ "), "{}", stdout);
+}
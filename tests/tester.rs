@@ -0,0 +1,80 @@
+mod common;
+
+use reedline_repl_rs::clap::{Arg, ArgMatches, Command};
+use reedline_repl_rs::{Error, ReplTester, Result};
+
+fn add(args: ArgMatches, _: &mut ()) -> Result<Option<String>> {
+    let first: i32 = *args.get_one("first").unwrap();
+    let second: i32 = *args.get_one("second").unwrap();
+    Ok(Some((first + second).to_string()))
+}
+
+fn tester() -> ReplTester<(), Error> {
+    let number = |name| {
+        Arg::new(name)
+            .value_parser(reedline_repl_rs::clap::value_parser!(i32))
+            .required(true)
+    };
+    ReplTester::new(
+        common::repl().with_command(
+            Command::new("add")
+                .arg(number("first"))
+                .arg(number("second")),
+            add,
+        ),
+    )
+}
+
+#[test]
+fn output_of_successful_commands() {
+    tester()
+        .send("add 1 2")
+        .expect_output("3")
+        .send("add -- -1 -2")
+        .expect_output("-3")
+        .send("add 40 2; add 1 1")
+        .expect_output("42\n2");
+}
+
+#[test]
+fn command_errors_fail() {
+    tester()
+        .send("fail")
+        .expect_error("failed on purpose")
+        .send("bogus")
+        .expect_error("Unknown command 'bogus'");
+}
+
+#[test]
+fn rejected_arguments_fail() {
+    let tester = tester().send("add 1").expect_error("required");
+    assert!(!tester.response().success);
+    tester
+        .send("add one 2")
+        .expect_error("invalid value 'one'")
+        .send("add 1 2 3")
+        .expect_error("unexpected argument '3'");
+}
+
+#[test]
+fn quit_is_reported() {
+    tester().send("quit").expect_quit();
+}
+
+#[test]
+#[should_panic(expected = "unexpected output of 'add 1 2'")]
+fn unexpected_output_fails_the_test() {
+    tester().send("add 1 2").expect_output("4");
+}
+
+#[test]
+#[should_panic(expected = "'say hi' succeeded with output")]
+fn expected_errors_fail_the_test_on_success() {
+    tester().send("say hi").expect_error("failed");
+}
+
+#[test]
+fn the_repl_is_returned_after_testing() {
+    let mut repl = tester().send("say hi").expect_output("hi").into_repl();
+    assert_eq!(repl.feed_line("add 2 2").output, "4\n");
+}
@@ -0,0 +1,25 @@
+mod common;
+
+use reedline_repl_rs::ReplTester;
+
+#[test]
+fn fed_lines_are_written_to_the_transcript() {
+    let path = std::env::temp_dir().join("reedline-repl-rs-transcript");
+    let _ = std::fs::remove_file(&path);
+    let tester = ReplTester::new(common::repl().with_transcript(&path))
+        .send("say hello")
+        .expect_output("hello")
+        .send("fail")
+        .expect_error("failed on purpose")
+        .send(" say secret");
+    drop(tester);
+    let transcript = std::fs::read_to_string(&path).unwrap();
+    let lines: Vec<&str> = transcript
+        .lines()
+        .map(|line| line.split_once("] ").unwrap().1)
+        .collect();
+    assert_eq!(&lines[..3], ["> say hello", "  hello", "> fail"]);
+    assert!(lines[3].starts_with("! ") && lines[3].contains("failed on purpose"));
+    assert_eq!(lines[4], "> (line not recorded)");
+    assert!(!transcript.contains("say secret"));
+}
@@ -0,0 +1,27 @@
+mod common;
+
+use common::tester;
+
+#[test]
+fn dollar_escape_is_expanded_once() {
+    tester()
+        .send("echo cost $$5")
+        .expect_output("cost $5")
+        .send("print '{} each' $$5")
+        .expect_output("$5 each")
+        .send("set x 1")
+        .send("echo $x $$x")
+        .expect_output("1 $x")
+        .send("say $x $$x")
+        .expect_output("1 $x");
+}
+
+#[test]
+fn echo_expands_environment_variables() {
+    std::env::set_var("REEDLINE_REPL_RS_ECHO_TEST", "value");
+    tester()
+        .send("echo ${REEDLINE_REPL_RS_ECHO_TEST}s [$REEDLINE_REPL_RS_ECHO_UNSET]")
+        .expect_output("values []")
+        .send("say $REEDLINE_REPL_RS_ECHO_TEST")
+        .expect_output("$REEDLINE_REPL_RS_ECHO_TEST");
+}
@@ -0,0 +1,37 @@
+use reedline_repl_rs::clap::{ArgMatches, Command};
+use reedline_repl_rs::{Error, Repl, ReplTester, Result};
+
+#[derive(Default)]
+struct Session {
+    admin: bool,
+}
+
+fn login(_: ArgMatches, session: &mut Session) -> Result<Option<String>> {
+    session.admin = true;
+    Ok(None)
+}
+
+fn audit(_: ArgMatches, _: &mut Session) -> Result<Option<String>> {
+    Ok(Some("audited".to_string()))
+}
+
+fn is_admin(session: &Session) -> bool {
+    session.admin
+}
+
+#[test]
+fn help_follows_the_context() {
+    let tester = ReplTester::new(
+        Repl::<Session, Error>::new(Session::default())
+            .with_command(Command::new("login"), login)
+            .with_command(Command::new("audit").about("Audit the logs"), audit)
+            .with_visible_when("audit", is_admin),
+    )
+    .send("help");
+    assert!(!tester.response().output.contains("Audit the logs"));
+    tester
+        .send("login")
+        .expect_success()
+        .send("help")
+        .expect_output_contains("Audit the logs");
+}
@@ -0,0 +1,84 @@
+mod common;
+
+use reedline_repl_rs::clap::{Arg, ArgMatches, Command};
+use reedline_repl_rs::{Error, ReplTester, Result};
+use std::time::{Duration, Instant};
+
+fn count(args: ArgMatches, _: &mut ()) -> Result<Option<String>> {
+    Ok(Some(args.get_one::<u32>("times").unwrap().to_string()))
+}
+
+fn tester() -> ReplTester<(), Error> {
+    ReplTester::new(
+        common::repl().with_command(
+            Command::new("count").arg(
+                Arg::new("times")
+                    .required(true)
+                    .value_parser(reedline_repl_rs::clap::value_parser!(u32)),
+            ),
+            count,
+        ),
+    )
+}
+
+#[test]
+fn waits_until_the_command_succeeds() {
+    tester()
+        .send("wait-for 'count 3' && echo done")
+        .expect_output("3\ndone");
+}
+
+#[test]
+fn own_argument_errors_fail_the_line() {
+    let tester = tester()
+        .send("wait-for --timeout soon 'count 3' && echo next")
+        .expect_error("invalid value 'soon'");
+    assert!(!tester.response().output.contains("next"));
+}
+
+#[test]
+fn rejected_arguments_of_the_command_end_the_wait() {
+    let start = Instant::now();
+    let tester = tester()
+        .send("wait-for --timeout 5s --interval 1s 'count many' && echo next")
+        .expect_error("invalid value 'many'");
+    assert!(start.elapsed() < Duration::from_secs(1));
+    assert!(!tester.response().output.contains("next"));
+    assert_eq!(tester.response().errors.matches("invalid value").count(), 1);
+}
+
+#[test]
+fn sleep_waits_for_the_duration() {
+    let start = Instant::now();
+    tester()
+        .send("sleep 20ms")
+        .expect_success()
+        .send("sleep soon")
+        .expect_error("soon");
+    assert!(start.elapsed() >= Duration::from_millis(20));
+}
+
+#[cfg(feature = "async")]
+#[tokio::test]
+async fn sleep_lets_other_tasks_run_in_async_repls() {
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    let mut repl = common::repl();
+    let slept = AtomicBool::new(false);
+    let mut polls = 0;
+    let sleep = async {
+        let response = repl.feed_line_async("sleep 50ms; sleep 50ms").await;
+        slept.store(true, Ordering::SeqCst);
+        response
+    };
+    // a sleep blocking the thread finishes before this task is polled the first time
+    let other = async {
+        while !slept.load(Ordering::SeqCst) {
+            polls += 1;
+            tokio::task::yield_now().await;
+        }
+    };
+    let (response, ()) = tokio::join!(sleep, other);
+    assert!(response.success);
+    assert!(polls > 1);
+}